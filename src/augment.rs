@@ -0,0 +1,137 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// An experimental generation algorithm by canonical augmentation:
+// each component of n vertices which is not a cycle is obtained from
+// a component of n - 1 vertices, its parent, by attaching a new leaf
+// to one of its vertices. The parent of a component is defined by
+// deleting the last leaf (vertex of in-degree 0) in the order of its
+// code, and an augmentation is only kept if the parent of the result
+// is the component which was augmented, so that each component is
+// obtained exactly once (the augmentations of a component producing
+// the same code are also only kept once). As for the direct
+// algorithm, the components of each size are then sorted in
+// generation order and kept in memory, and digraphs are built as
+// multisets of components
+
+
+use std::rc::Rc;
+use crate::{Comp, Emit, cycle};
+use crate::canon::canon_map;
+use crate::digraph6::comp_adj;
+use crate::direct::generate_funcs_from_tables;
+use crate::error::{Error, MAX_VERTICES};
+use crate::order::merge_path;
+
+
+// Compute the code of the component having function table f, which
+// must describe a connected functional digraph
+
+fn comp_code(f: &[usize]) -> Result<Comp, Error> {
+    match canon_map(f)?.pop() {
+        Some(c) => Ok(Rc::try_unwrap(c).unwrap_or_else(|c| (*c).clone())),
+        None => Err(Error::Internal("empty component")),
+    }
+}
+
+
+// Compute the parent of component c, by deleting the last leaf in the
+// order of its code (the vertices are numbered as in its adjacency
+// vector); cycles have no leaves, and thus no parent
+
+pub fn parent(c: &Comp) -> Result<Option<Comp>, Error> {
+    let a = comp_adj(c, 0);
+    let mut indegree = vec![0; a.len()];
+    for &w in &a {
+        indegree[w as usize] += 1;
+    }
+    let Some(v) = indegree.iter().rposition(|&d| d == 0) else {
+        return Ok(None);
+    };
+    let f: Vec<usize> = (0..a.len()).filter(|&u| u != v).map(|u| {
+        let w = a[u] as usize;
+        if w > v { w - 1 } else { w }
+    }).collect();
+    comp_code(&f).map(Some)
+}
+
+
+// Compute the components having c as their parent, each one once
+
+pub fn augmentations(c: &Comp) -> Result<Vec<Comp>, Error> {
+    let mut f: Vec<usize> = comp_adj(c, 0).iter().map(|&w| w as usize)
+        .collect();
+    let mut children = Vec::new();
+    for v in 0..f.len() {
+        f.push(v);
+        let d = comp_code(&f)?;
+        f.pop();
+        if !children.contains(&d) && parent(&d)?.as_ref() == Some(c) {
+            children.push(d);
+        }
+    }
+    Ok(children)
+}
+
+
+// Compute the codes of all components of each number of vertices m
+// from 0 to n (at index m), in generation order, by augmenting the
+// components of m - 1 vertices (and adding the cycle of length m)
+
+pub fn comps_by_augmentation(n: usize) -> Result<Vec<Vec<Rc<Comp>>>, Error> {
+    let mut levels: Vec<Vec<Comp>> = vec![Vec::new()];
+    for m in 1..=n {
+        let mut level = vec![cycle(m)];
+        for c in &levels[m - 1] {
+            level.extend(augmentations(c)?);
+        }
+        levels.push(level);
+    }
+    Ok(levels.into_iter().map(|mut level| {
+        level.sort_by_cached_key(merge_path);
+        level.into_iter().map(Rc::new).collect()
+    }).collect())
+}
+
+
+// Generate all components of n vertices by canonical augmentation, in
+// the same way as generate_comps
+
+pub fn generate_comps_augment<E: From<Error>>(n: usize, emit: &mut Emit<E>)
+                                              -> Result<u64, E> {
+    if n > MAX_VERTICES {
+        return Err(Error::TooManyVertices(n).into());
+    }
+    let comps = comps_by_augmentation(n)?;
+    for c in &comps[n] {
+        emit(&vec![c.clone()])?;
+    }
+    Ok(comps[n].len() as u64)
+}
+
+
+// Generate all functional digraphs of n vertices by canonical
+// augmentation of their components, in the same way as generate_funcs
+
+pub fn generate_funcs_augment<E: From<Error>>(n: usize, emit: &mut Emit<E>)
+                                              -> Result<u64, E> {
+    if n > MAX_VERTICES {
+        return Err(Error::TooManyVertices(n).into());
+    }
+    generate_funcs_from_tables(n, &comps_by_augmentation(n)?, emit)
+}
@@ -0,0 +1,325 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Boltzmann samplers for (connected) functional digraphs, generating
+// random digraphs of random size, with all digraphs of the same size
+// being equally likely up to isomorphism; the samplers follow
+// Philippe Flajolet, Eric Fusy, Carine Pivoteau, "Boltzmann sampling
+// of unlabelled structures", ANALCO 2007, for the specification
+// F = MSET(C), C = CYC(T), T = Z × MSET(T)
+
+
+use std::f64::consts::E;
+use std::rc::Rc;
+use crate::{Comp, Func, Tree, comp_size};
+use crate::canon::{canon_comp, canon_func, canon_tree};
+use crate::error::MAX_VERTICES;
+use crate::gf::totient;
+use crate::random::Rng;
+
+
+// Maximum number of vertices of the sampled digraphs, which is
+// limited by the representation of the tree codes
+
+pub const MAX_SIZE: usize = MAX_VERTICES;
+
+
+// Values of x^k below this are considered negligible, so that the
+// generating functions are only evaluated at x, x^2, ..., x^K with
+// x^K >= EPSILON
+
+const EPSILON: f64 = 1e-20;
+
+
+// Boltzmann sampler with parameter x, with the values of the
+// generating functions T of rooted trees, log(1 / (1 - T)) of
+// sequences of trees taken as cycles, and C of components, evaluated
+// at x^k (at index k, for 1 <= k <= K; index 0 is unused)
+
+#[derive(Clone, Debug)]
+pub struct Sampler {
+    connected: bool,
+    x: f64,
+    trees: Vec<f64>,
+    cycles: Vec<f64>,
+    comps: Vec<f64>,
+}
+
+
+// Draw a Poisson-distributed number of parameter lambda (by
+// inversion, which is efficient since lambda is always small here)
+
+fn poisson(rng: &mut Rng, lambda: f64) -> usize {
+    let mut p = (-lambda).exp();
+    let mut s = p;
+    let u = rng.next_f64();
+    let mut k = 0;
+    while u >= s && p > 0.0 {
+        k += 1;
+        p *= lambda / k as f64;
+        s += p;
+    }
+    k
+}
+
+
+impl Sampler {
+
+    // Evaluate the generating functions at x^k for 1 <= k <= K, if x
+    // is smaller than their radius of convergence. The value T(x^k)
+    // is the smaller solution y of y = a * e^y with a = x^k *
+    // exp(sum(T(x^(jk)) / j for j >= 2)), computed by Newton's method
+    // starting from 0, and it only exists if a <= 1 / e
+
+    fn with_param(x: f64, connected: bool) -> Option<Sampler> {
+        let mut k_max = 1;
+        while x.powi(k_max as i32 + 1) >= EPSILON {
+            k_max += 1;
+        }
+        let mut trees = vec![0.0; k_max + 1];
+        let mut cycles = vec![0.0; k_max + 1];
+        let mut comps = vec![0.0; k_max + 1];
+        for k in (1..=k_max).rev() {
+            let mut s = 0.0;
+            let mut j = 2;
+            while k * j <= k_max {
+                s += trees[k * j] / j as f64;
+                j += 1;
+            }
+            let a = x.powi(k as i32) * s.exp();
+            if a > 1.0 / E {
+                return None;
+            }
+            let mut y: f64 = 0.0;
+            loop {
+                let f = a * y.exp();
+                let z = y - (y - f) / (1.0 - f);
+                if f >= 1.0 || z <= y {
+                    break;
+                }
+                y = z;
+            }
+            if y >= 1.0 {
+                return None;
+            }
+            trees[k] = y;
+            cycles[k] = -(1.0 - y).ln();
+        }
+        for k in 1..=k_max {
+            let mut j = 1;
+            while k * j <= k_max {
+                comps[k] += totient(j) as f64 / j as f64 * cycles[k * j];
+                j += 1;
+            }
+        }
+        Some(Sampler { connected, x, trees, cycles, comps })
+    }
+
+    // Compute the logarithm of the generating function of the
+    // sampled class, i.e., of log(C) for components, and of
+    // log(F) = sum(C(x^k) / k for k >= 1) for functional digraphs
+
+    fn log_gf(&self) -> f64 {
+        if self.connected {
+            self.comps[1].ln()
+        } else {
+            (1..self.comps.len()).map(|k| self.comps[k] / k as f64).sum()
+        }
+    }
+
+    // Compute the radius of convergence of the generating functions
+    // (approximately 0.3383, where T = 1) by bisection
+
+    fn singularity() -> f64 {
+        let (mut lo, mut hi) = (0.0, 0.5);
+        for _ in 0..100 {
+            let mid = (lo + hi) / 2.0;
+            if Sampler::with_param(mid, false).is_some() {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+
+    // Compute the expected size of the digraphs sampled with parameter
+    // x, i.e., x * d/dx log(F(x)) (or log(C(x)) for components), by
+    // numerical differentiation; rho is the singularity
+
+    fn expected_size(x: f64, rho: f64, connected: bool) -> f64 {
+        let h = x.min(rho - x) * 1e-4;
+        let lo = Sampler::with_param(x - h, connected).unwrap();
+        let hi = Sampler::with_param(x + h, connected).unwrap();
+        x * (hi.log_gf() - lo.log_gf()) / (2.0 * h)
+    }
+
+    // Create a sampler for digraphs (connected ones only, if requested)
+    // with expected size n; the parameter is found by bisection, since
+    // the expected size grows with it (diverging at the singularity)
+
+    pub fn new(n: f64, connected: bool) -> Sampler {
+        let rho = Sampler::singularity();
+        let (mut lo, mut hi) = (0.0, rho);
+        for _ in 0..100 {
+            let mid = (lo + hi) / 2.0;
+            if Sampler::expected_size(mid, rho, connected) < n {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        Sampler::with_param(lo, connected).unwrap()
+    }
+
+    // Parameter x of the sampler
+
+    pub fn param(&self) -> f64 {
+        self.x
+    }
+
+    // Draw random digraphs until one has at least min and at most max
+    // vertices (max is at most MAX_SIZE), and return it; each attempt
+    // is abandoned as soon as it grows larger than max, so that it
+    // takes time linear in max
+
+    pub fn sample(&self, rng: &mut Rng, min: usize, max: usize) -> Func {
+        loop {
+            let mut draw = Draw {
+                s: self,
+                rng,
+                size: 0,
+                max: max.min(MAX_SIZE),
+            };
+            let g = if self.connected {
+                draw.comp(1).map(|c| vec![Rc::new(c)])
+            } else {
+                draw.func()
+            };
+            if let Some(g) = g {
+                if draw.size >= min {
+                    return g;
+                }
+            }
+        }
+    }
+
+}
+
+
+// State of a single attempt at drawing a digraph, with the number of
+// vertices drawn so far and the maximum number allowed
+
+struct Draw<'a> {
+    s: &'a Sampler,
+    rng: &'a mut Rng,
+    size: usize,
+    max: usize,
+}
+
+
+impl Draw<'_> {
+
+    // Add k vertices to the size of the digraph being drawn, unless
+    // this exceeds the maximum
+
+    fn grow(&mut self, k: usize) -> Option<()> {
+        self.size += k;
+        if self.size <= self.max { Some(()) } else { None }
+    }
+
+    // Draw a rooted tree with parameter x^k: a root with a multiset
+    // of subtrees, consisting of a Poisson(T(x^(jk)) / j) number of
+    // subtrees drawn with parameter x^(jk), each repeated j times,
+    // for each j >= 1
+
+    fn tree(&mut self, k: usize) -> Option<Tree> {
+        self.grow(1)?;
+        let mut children = Vec::new();
+        let mut j = 1;
+        while k * j < self.s.trees.len() {
+            let lambda = self.s.trees[k * j] / j as f64;
+            for _ in 0..poisson(self.rng, lambda) {
+                let t = self.tree(k * j)?;
+                self.grow((j - 1) * t.len())?;
+                for _ in 0..j {
+                    children.push(t.clone());
+                }
+            }
+            j += 1;
+        }
+        Some(canon_tree(children))
+    }
+
+    // Draw a component with parameter x^k: a sequence of l trees
+    // with parameter x^(jk), repeated j times along the cycle, where j
+    // and l have probability proportional to totient(j) / j *
+    // T(x^(jk))^l / l
+
+    fn comp(&mut self, k: usize) -> Option<Comp> {
+        let len = self.s.trees.len();
+        let mut u = self.rng.next_f64() * self.s.comps[k];
+        let mut j = 1;
+        loop {
+            let w = totient(j) as f64 / j as f64 * self.s.cycles[k * j];
+            if u < w || k * (j + 1) >= len {
+                break;
+            }
+            u -= w;
+            j += 1;
+        }
+        let lambda = self.s.trees[k * j];
+        let mut u = self.rng.next_f64() * self.s.cycles[k * j];
+        let mut l = 1;
+        let mut p = lambda;
+        while u >= p / l as f64 && l <= self.max {
+            u -= p / l as f64;
+            l += 1;
+            p *= lambda;
+        }
+        let mut seq = Comp::new();
+        for _ in 0..l {
+            seq.push(Rc::new(self.tree(k * j)?));
+        }
+        self.grow((j - 1) * comp_size(&seq))?;
+        let mut c = Comp::new();
+        for _ in 0..j {
+            c.extend_from_slice(&seq);
+        }
+        Some(canon_comp(c))
+    }
+
+    // Draw a functional digraph: a multiset of components, consisting
+    // of a Poisson(C(x^k) / k) number of components drawn with
+    // parameter x^k, each repeated k times, for each k >= 1
+
+    fn func(&mut self) -> Option<Func> {
+        let mut g = Func::new();
+        for k in 1..self.s.comps.len() {
+            for _ in 0..poisson(self.rng, self.s.comps[k] / k as f64) {
+                let c = Rc::new(self.comp(k)?);
+                self.grow((k - 1) * comp_size(&c))?;
+                for _ in 0..k {
+                    g.push(c.clone());
+                }
+            }
+        }
+        Some(canon_func(g))
+    }
+
+}
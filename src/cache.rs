@@ -0,0 +1,222 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Cache of the tables of counts of the gf module, kept on disk so
+// that repeated runs do not compute them again; each table is stored
+// in a text file with a header line giving the version of funkdigen2
+// which wrote it (files written by other versions are ignored and
+// overwritten) and the maximum size n, followed by a line for each
+// size m <= n with the numbers separated by spaces. The cache is only
+// an optimization, so it is bypassed silently if it cannot be read or
+// written
+
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::process;
+use crate::gf::{comp_counts, component_number_counts, fixed_point_counts,
+                func_counts, tree_counts};
+
+
+// Version written into the header of the files
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+
+// Tables of counts which can be cached; the counts for sizes up to n
+// do not depend on n, so a table computed for a larger size contains
+// those for all smaller ones
+
+#[derive(Clone, Copy)]
+enum Table {
+    Trees,
+    Comps,
+    Funcs,
+    FixedPoints,
+    ComponentNumbers,
+}
+
+
+impl Table {
+
+    fn name(self) -> &'static str {
+        match self {
+            Table::Trees => "trees",
+            Table::Comps => "comps",
+            Table::Funcs => "funcs",
+            Table::FixedPoints => "fixed-points",
+            Table::ComponentNumbers => "component-numbers",
+        }
+    }
+
+    // Compute the table for sizes up to n, with the counts of trees
+    // as a single line
+
+    fn compute(self, n: usize) -> Option<Vec<Vec<u128>>> {
+        match self {
+            Table::Trees => tree_counts(n).map(|t| vec![t]),
+            Table::Comps => comp_counts(n),
+            Table::Funcs => func_counts(n),
+            Table::FixedPoints => fixed_point_counts(n),
+            Table::ComponentNumbers => component_number_counts(n),
+        }
+    }
+
+}
+
+
+// Default directory of the cache, following the conventions of each
+// platform: funkdigen2 under $XDG_CACHE_HOME (or ~/.cache) on Unix,
+// ~/Library/Caches on macOS and %LOCALAPPDATA% on Windows
+
+pub fn default_cache_dir() -> Option<PathBuf> {
+    let var = |name| env::var_os(name).filter(|v| !v.is_empty())
+        .map(PathBuf::from);
+    let base = if cfg!(windows) {
+        var("LOCALAPPDATA")
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|h| h.join("Library").join("Caches"))
+    } else {
+        var("XDG_CACHE_HOME")
+            .or_else(|| var("HOME").map(|h| h.join(".cache")))
+    };
+    base.map(|d| d.join("funkdigen2"))
+}
+
+
+// Cache of the tables of counts in directory dir, or no cache at all
+// (i.e., the tables are always computed) if dir is None
+
+pub struct CountCache {
+    dir: Option<PathBuf>,
+}
+
+
+impl CountCache {
+
+    pub fn new(dir: Option<PathBuf>) -> CountCache {
+        CountCache { dir }
+    }
+
+    // Same as gf::tree_counts
+
+    pub fn tree_counts(&self, n: usize) -> Option<Vec<u128>> {
+        self.get(Table::Trees, n).map(|mut t| t.swap_remove(0))
+    }
+
+    // Same as gf::comp_counts
+
+    pub fn comp_counts(&self, n: usize) -> Option<Vec<Vec<u128>>> {
+        self.get(Table::Comps, n)
+    }
+
+    // Same as gf::func_counts
+
+    pub fn func_counts(&self, n: usize) -> Option<Vec<Vec<u128>>> {
+        self.get(Table::Funcs, n)
+    }
+
+    // Same as gf::fixed_point_counts
+
+    pub fn fixed_point_counts(&self, n: usize) -> Option<Vec<Vec<u128>>> {
+        self.get(Table::FixedPoints, n)
+    }
+
+    // Same as gf::component_number_counts
+
+    pub fn component_number_counts(&self, n: usize)
+                                   -> Option<Vec<Vec<u128>>> {
+        self.get(Table::ComponentNumbers, n)
+    }
+
+    // Path of the file of a table
+
+    fn path(&self, table: Table) -> Option<PathBuf> {
+        let dir = self.dir.as_ref()?;
+        Some(dir.join(format!("{}.counts", table.name())))
+    }
+
+    // Get the table for sizes up to n from the cache, or compute it
+    // and store it (unless it does not fit in 128 bits)
+
+    fn get(&self, table: Table, n: usize) -> Option<Vec<Vec<u128>>> {
+        if let Some(rows) = self.read(table, n) {
+            return Some(rows);
+        }
+        let rows = table.compute(n)?;
+        let _ = self.write(table, n, &rows);
+        Some(rows)
+    }
+
+    // Read the table for sizes up to n, if the file was written by
+    // this version for at least n, truncating the rows (and, except
+    // for the trees, their columns) to n + 1 entries
+
+    fn read(&self, table: Table, n: usize) -> Option<Vec<Vec<u128>>> {
+        let file = File::open(self.path(table)?).ok()?;
+        let mut lines = BufReader::new(file).lines();
+        let header = lines.next()?.ok()?;
+        let fields: Vec<&str> = header.split(' ').collect();
+        let ["funkdigen2", version, name, size] = fields[..] else {
+            return None;
+        };
+        let size: usize = size.parse().ok()?;
+        if version != VERSION || name != table.name() || size < n {
+            return None;
+        }
+        let mut rows = Vec::new();
+        for line in lines {
+            let row = line.ok()?.split_whitespace().map(|x| x.parse().ok())
+                .collect::<Option<Vec<u128>>>()?;
+            rows.push(row);
+        }
+        let len = if let Table::Trees = table { 1 } else { size + 1 };
+        if rows.len() != len || rows.iter().any(|r| r.len() != size + 1) {
+            return None;
+        }
+        rows.truncate(n + 1);
+        for row in &mut rows {
+            row.truncate(n + 1);
+        }
+        Some(rows)
+    }
+
+    // Write the table for sizes up to n to a temporary file, which is
+    // then renamed, so that concurrent runs never read a partial table
+
+    fn write(&self, table: Table, n: usize, rows: &[Vec<u128>])
+             -> io::Result<()> {
+        let Some(path) = self.path(table) else {
+            return Ok(());
+        };
+        fs::create_dir_all(path.parent().unwrap())?;
+        let tmp = path.with_extension(format!("tmp{}", process::id()));
+        let mut out = BufWriter::new(File::create(&tmp)?);
+        writeln!(out, "funkdigen2 {VERSION} {} {n}", table.name())?;
+        for row in rows {
+            let row: Vec<String> = row.iter().map(u128::to_string).collect();
+            writeln!(out, "{}", row.join(" "))?;
+        }
+        out.flush()?;
+        drop(out);
+        fs::rename(&tmp, &path)
+    }
+
+}
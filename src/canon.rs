@@ -0,0 +1,374 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Computation of the isomorphism codes of trees, components and
+// functional digraphs built from arbitrary (non-canonical) parts
+
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::rc::Rc;
+use crate::{Tree, Comp, Func};
+use crate::order::cmp_comps;
+
+
+// Compute the isomorphism code of the tree having a root with the
+// given immediate subtrees (whose codes are assumed to be canonical);
+// the subtrees are sorted lexicographically
+
+pub fn canon_tree(mut children: Vec<Tree>) -> Tree {
+    children.sort();
+    let mut t = vec![1];
+    for c in &children {
+        t.extend_from_slice(c);
+        t[0] += c.len() as u8;
+    }
+    t
+}
+
+
+// Compute the isomorphism code of tree t, given by a code which is
+// not necessarily canonical (i.e., with the subtrees of each vertex
+// in any order)
+
+pub fn canon_tree_code(t: &[u8]) -> Tree {
+    let mut children = Vec::new();
+    let mut i = 1;
+    while i < t.len() {
+        let size = t[i] as usize;
+        children.push(canon_tree_code(&t[i..i + size]));
+        i += size;
+    }
+    canon_tree(children)
+}
+
+
+// Compute the isomorphism code of the component having a cycle with
+// trees c[0], ..., c[k - 1] (whose codes are assumed to be canonical)
+// along it, i.e., the lexicographically minimal rotation of c; the
+// naive quadratic algorithm is used, as for checking minimal rotations
+
+pub fn canon_comp(mut c: Comp) -> Comp {
+    let best = min_rotation(&c);
+    c.rotate_left(best);
+    c
+}
+
+
+// Compute the number of positions by which c must be rotated to the
+// left to obtain its lexicographically minimal rotation, as for
+// canon_comp
+
+fn min_rotation(c: &Comp) -> usize {
+    let n = c.len();
+    let mut best = 0;
+    for k in 1..n {
+        for i in 0..n {
+            let (a, b) = (&c[(k + i) % n], &c[(best + i) % n]);
+            if a != b {
+                if a < b {
+                    best = k;
+                }
+                break;
+            }
+        }
+    }
+    best
+}
+
+
+// Compute the isomorphism code of the functional digraph having
+// components g[0], ..., g[k - 1] (whose codes are assumed to be
+// canonical), i.e., sort them in generation order
+
+pub fn canon_func(mut g: Func) -> Func {
+    g.sort_by(|a, b| cmp_comps(a, b));
+    g
+}
+
+
+// Compute the isomorphism code of the subtree rooted at vertex v of
+// the tree (or forest) where the children of each vertex u are
+// children[u]
+
+fn subtree_code(children: &[Vec<usize>], v: usize) -> Tree {
+    canon_tree(children[v].iter().map(|&w| subtree_code(children, w))
+               .collect())
+}
+
+
+// Compute the isomorphism code of the subtree rooted at vertex v, as
+// subtree_code, together with the vertices of the subtree in the
+// preorder of the code
+
+fn labelled_subtree(children: &[Vec<usize>], v: usize)
+                    -> (Tree, Vec<usize>) {
+    let mut subtrees: Vec<_> = children[v].iter()
+        .map(|&w| labelled_subtree(children, w))
+        .collect();
+    subtrees.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut t = vec![1];
+    let mut order = vec![v];
+    for (c, o) in subtrees {
+        t.extend_from_slice(&c);
+        t[0] += c.len() as u8;
+        order.extend(o);
+    }
+    (t, order)
+}
+
+
+// Compute the periodic points of the functional digraph of map f on
+// vertices 0..n, i.e., having an edge from each vertex v to f[v] < n,
+// and the children of each vertex in the forest formed by the other
+// vertices. The periodic points are those left after repeatedly
+// removing the vertices with in-degree 0. The input is rejected if
+// some f[v] is not a vertex, or if there are more than 255 vertices
+
+fn cycle_forest(f: &[usize])
+                -> Result<(Vec<bool>, Vec<Vec<usize>>), InvalidEntry> {
+    let error = |index, msg: &str| {
+        Err(InvalidEntry { index, msg: msg.to_string() })
+    };
+    let n = f.len();
+    if n > 255 {
+        return error(255, "function with more than 255 vertices");
+    }
+    if let Some(v) = f.iter().position(|&w| w >= n) {
+        return error(v, "image out of range");
+    }
+    let mut indegree = vec![0; n];
+    for &w in f {
+        indegree[w] += 1;
+    }
+    let mut leaves: Vec<usize> = (0..n).filter(|&v| indegree[v] == 0)
+        .collect();
+    let mut periodic = vec![true; n];
+    while let Some(v) = leaves.pop() {
+        periodic[v] = false;
+        indegree[f[v]] -= 1;
+        if indegree[f[v]] == 0 {
+            leaves.push(f[v]);
+        }
+    }
+    let mut children = vec![Vec::new(); n];
+    for v in (0..n).filter(|&v| !periodic[v]) {
+        children[f[v]].push(v);
+    }
+    Ok((periodic, children))
+}
+
+
+// Compute the isomorphism code of the functional digraph of map f on
+// vertices 0..n: each periodic point is the root of a tree formed by
+// the other vertices, and the trees are read along the cycles in the
+// direction of the edges. The input is rejected as by cycle_forest
+
+pub fn canon_map(f: &[usize]) -> Result<Func, InvalidEntry> {
+    let (periodic, children) = cycle_forest(f)?;
+    let n = f.len();
+    let mut visited = vec![false; n];
+    let mut g = Func::new();
+    for u in (0..n).filter(|&u| periodic[u]) {
+        let mut c = Comp::new();
+        let mut v = u;
+        while !visited[v] {
+            visited[v] = true;
+            c.push(Rc::new(subtree_code(&children, v)));
+            v = f[v];
+        }
+        if !c.is_empty() {
+            g.push(Rc::new(canon_comp(c)));
+        }
+    }
+    Ok(canon_func(g))
+}
+
+
+// Compute the isomorphism code of the functional digraph of map f, as
+// canon_map, together with a canonical labelling of its vertices,
+// i.e., the name label[v] of each vertex v in the adjacency vector of
+// the code (as in digraph6 format), so that the edge from v to f[v]
+// becomes the edge from label[v] to label[f[v]]
+
+pub fn canon_labelling(f: &[usize])
+                       -> Result<(Func, Vec<usize>), InvalidEntry> {
+    let (periodic, children) = cycle_forest(f)?;
+    let n = f.len();
+    let mut visited = vec![false; n];
+    let mut comps = Vec::new();
+    for u in (0..n).filter(|&u| periodic[u]) {
+        let mut c = Comp::new();
+        let mut orders = Vec::new();
+        let mut v = u;
+        while !visited[v] {
+            visited[v] = true;
+            let (t, order) = labelled_subtree(&children, v);
+            c.push(Rc::new(t));
+            orders.push(order);
+            v = f[v];
+        }
+        if !c.is_empty() {
+            let k = min_rotation(&c);
+            c.rotate_left(k);
+            orders.rotate_left(k);
+            comps.push((Rc::new(c), orders.concat()));
+        }
+    }
+    comps.sort_by(|a, b| cmp_comps(&a.0, &b.0));
+    let mut g = Func::new();
+    let mut label = vec![0; n];
+    let mut x = 0;
+    for (c, order) in comps {
+        for v in order {
+            label[v] = x;
+            x += 1;
+        }
+        g.push(c);
+    }
+    Ok((g, label))
+}
+
+
+// Find an isomorphism phi between the functional digraphs of maps f
+// and g (i.e., a bijection with phi[f[v]] = g[phi[v]] for all v), if
+// any, by aligning their canonical labellings: the digraphs are
+// isomorphic if and only if they have the same code, and then phi
+// maps each vertex of f to the vertex of g having the same name. The
+// maps are rejected as by canon_map
+
+pub fn canonical_isomorphism(f: &[usize], g: &[usize])
+                             -> Result<Option<Vec<usize>>, InvalidEntry> {
+    let (cf, lf) = canon_labelling(f)?;
+    let (cg, lg) = canon_labelling(g)?;
+    if cf != cg {
+        return Ok(None);
+    }
+    let mut inverse = vec![0; lg.len()];
+    for (w, &x) in lg.iter().enumerate() {
+        inverse[x] = w;
+    }
+    Ok(Some(lf.iter().map(|&x| inverse[x]).collect()))
+}
+
+
+// Error found while building an isomorphism code from an explicit
+// description of a tree or function, with the index of the offending
+// entry in the input
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidEntry {
+    pub index: usize,
+    pub msg: String,
+}
+
+
+impl Display for InvalidEntry {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} at index {}", self.msg, self.index)
+    }
+}
+
+
+impl Error for InvalidEntry {}
+
+
+// Construction of the isomorphism code of a tree given by the parent
+// of each of its vertices, e.g., Tree::from_parent_array(&[0, 0, 1])
+
+pub trait FromParentArray: Sized {
+
+    // Compute the code of the rooted tree on vertices 0..n where the
+    // parent of each vertex v is parent[v], except for the root r,
+    // which has parent[r] = r; the vertices can be numbered in any
+    // order, and the subtrees are sorted as in canon_tree. The input
+    // is rejected if it does not describe a tree of 1 to 255 vertices.
+
+    fn from_parent_array(parent: &[usize]) -> Result<Self, InvalidEntry>;
+
+}
+
+
+impl FromParentArray for Tree {
+    fn from_parent_array(parent: &[usize]) -> Result<Tree, InvalidEntry> {
+        let error = |index, msg: &str| {
+            Err(InvalidEntry { index, msg: msg.to_string() })
+        };
+        let n = parent.len();
+        if n == 0 {
+            return error(0, "tree without vertices");
+        }
+        if n > 255 {
+            return error(255, "tree with more than 255 vertices");
+        }
+        let mut root = None;
+        let mut children = vec![Vec::new(); n];
+        for (v, &p) in parent.iter().enumerate() {
+            if p >= n {
+                return error(v, "parent out of range");
+            }
+            if p == v {
+                if root.is_some() {
+                    return error(v, "second root");
+                }
+                root = Some(v);
+            } else {
+                children[p].push(v);
+            }
+        }
+        let Some(root) = root else {
+            return error(0, "tree without a root");
+        };
+        // All vertices must be reachable from the root, otherwise some
+        // of them are on a cycle instead
+        let mut reached = vec![false; n];
+        let mut stack = vec![root];
+        while let Some(v) = stack.pop() {
+            reached[v] = true;
+            stack.extend_from_slice(&children[v]);
+        }
+        if let Some(v) = reached.iter().position(|&r| !r) {
+            return error(v, "vertex not reachable from the root");
+        }
+        Ok(subtree_code(&children, root))
+    }
+}
+
+
+// Construction of the isomorphism codes of the components of the
+// functional digraph of a map, e.g., Comp::from_function(&[1, 0, 0])
+
+pub trait FromFunction: Sized {
+
+    // Compute the codes of the components of the functional digraph
+    // of map f on vertices 0..n, as in canon_map, in generation order
+    // (so isomorphic components are adjacent). The input is rejected
+    // as by canon_map.
+
+    fn from_function(f: &[usize]) -> Result<Vec<Self>, InvalidEntry>;
+
+}
+
+
+impl FromFunction for Comp {
+    fn from_function(f: &[usize]) -> Result<Vec<Comp>, InvalidEntry> {
+        Ok(canon_map(f)?.into_iter()
+           .map(|c| Rc::try_unwrap(c).unwrap_or_else(|c| (*c).clone()))
+           .collect())
+    }
+}
@@ -0,0 +1,91 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Wrapper for isomorphism codes of functional digraphs, used when
+// they must be handled as values on their own (e.g., printed, parsed
+// or compared) rather than just generated
+
+
+use std::cmp::Ordering;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use crate::Func;
+use crate::format::pack;
+use crate::order::cmp_funcs;
+use crate::parse::{ParseError, parse_func};
+
+
+// Isomorphism code of a functional digraph; it is displayed in
+// internal format, and can be parsed from either the internal format
+// or the nested-bracket notation. Codes are ordered as they are
+// generated, so a < b if and only if a is output before b (when all
+// sizes are generated in increasing order), which makes it possible
+// to binary search or merge sorted output files. Codes can also be
+// hashed (consistently with equality), e.g., for storing them into
+// a HashSet
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Code(pub Func);
+
+
+impl Code {
+
+    // Compute a 64-bit digest of the code (the FNV-1a hash of its
+    // packed bytes), which is stable across runs, platforms and
+    // versions, unlike the values computed via Hash
+
+    pub fn digest(&self) -> u64 {
+        let mut h: u64 = 0xcbf29ce484222325;
+        for b in pack(&self.0) {
+            h ^= b as u64;
+            h = h.wrapping_mul(0x100000001b3);
+        }
+        h
+    }
+
+}
+
+
+impl Display for Code {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+
+impl FromStr for Code {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Code, ParseError> {
+        parse_func(s).map(Code)
+    }
+}
+
+
+impl Ord for Code {
+    fn cmp(&self, other: &Code) -> Ordering {
+        cmp_funcs(&self.0, &other.0)
+    }
+}
+
+
+impl PartialOrd for Code {
+    fn partial_cmp(&self, other: &Code) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
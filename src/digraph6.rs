@@ -0,0 +1,238 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Conversion of isomorphism codes to adjacency vectors, and of these
+// to and from the digraph6 format
+
+
+use std::io::{self, Write};
+use crate::{Tree, Comp, Func};
+use crate::parse::ParseError;
+
+
+// Types for adjacency vectors (i.e., adjacency lists for digraphs
+// with uniform outdegree 1) and bit strings
+
+pub type Adj = Vec<u8>;
+pub type Bits = Vec<bool>;
+
+
+// Compute the adjacency vector of a tree; use b (base) as the name of
+// the root (it is 0 for an isolated tree, but of course can be > 0
+// when there are several trees)
+
+pub fn tree_adj(t: &Tree, b: usize) -> Adj {
+    let mut a = vec![0; t.len()];
+    fill_tree_adj(t, &mut a, 0, 0, b);
+    a
+}
+
+
+// Fill a with the adjacency vector of tree t starting from its
+// subtree having root at position i; r is the position of the parent
+// of this subtree (if any); use b as the name of the root of t
+
+fn fill_tree_adj(t: &Tree, a: &mut Adj, i: usize, r: usize, b: usize) {
+    a[i] = (r + b) as u8;
+    let mut j = i + 1;
+    while j < i + t[i] as usize {
+        fill_tree_adj(t, a, j, i, b);
+        j += t[j] as usize;
+    }
+}
+
+
+// Compute the adjacency vector of component c, using b as the name of
+// the first vertex of c
+
+pub fn comp_adj(c: &Comp, b: usize) -> Adj {
+    let mut a = Adj::new();
+    let mut j = 0;
+    for i in 0..c.len() {
+        let mut a1 = tree_adj(&c[i], b + j);
+        if i < c.len() - 1 {
+            a1[0] = (b + j + c[i].len()) as u8;
+        } else {
+            a1[0] = b as u8;
+        }
+        a.extend(a1);
+        j += c[i].len();
+    }
+    a
+}
+
+
+// Compute the adjacency vector of functional digraph g
+
+pub fn func_adj(g: &Func) -> Adj {
+    let mut a = Adj::new();
+    let mut b = 0;
+    for i in 0..g.len() {
+        let a1 = comp_adj(&g[i], b);
+        b += a1.len();
+        a.extend(a1);
+    }
+    a
+}
+
+
+// Write the adjacency matrix corresponding to adjacency vector a
+// (i.e., the concatenation of the rows of the matrix, deleting
+// self-loops if loopless is true) to out as digraph6 ASCII
+// characters, six bits at a time; the matrix itself is never
+// materialized, so only O(n) memory is needed
+
+fn write_adj_matrix(out: &mut dyn Write, a: &Adj, loopless: bool)
+                    -> io::Result<()> {
+    let n = a.len();
+    let mut k = 0;
+    while k < n * n {
+        let mut x = 0;
+        for b in k..k + 6 {
+            let (i, j) = (b / n, b % n);
+            let bit = b < n * n && (!loopless || i != j)
+                && a[i] == j as u8;
+            x = 2 * x + bit as u8;
+        }
+        out.write_all(&[x + 63])?;
+        k += 6;
+    }
+    Ok(())
+}
+
+
+// Convert a bit vector into an ASCII string according to the digraph6
+// specifications
+
+pub fn bits_to_ascii(x: &Bits) -> String {
+    let mut s = String::new();
+    let mut i = 0;
+    while i < x.len() {
+        let mut n = 0;
+        for j in i..i + 6 {
+            if j < x.len() {
+                n = 2 * n + x[j] as u8;
+            } else {
+                n *= 2;
+            }
+        }
+        s.push((n + 63) as char);
+        i += 6;
+    }
+    s
+}
+
+
+// Returns a string representing integer n (in the range 0..255) in
+// digraph6 ASCII format, implements function N(n) of the digraph6
+// specifications
+
+pub fn int_to_ascii(mut n: usize) -> String {
+    if n < 63 {
+        ((n as u8 + 63) as char).to_string()
+    } else {
+        // 63 <= n <= 255, since digraphs have at most 255 vertices
+        let mut b = Bits::new();
+        for _ in 0..18 {
+            b.push(n % 2 == 1);
+            n /= 2;
+        }
+        b.reverse();
+        bits_to_ascii(&b)
+    }
+}
+
+
+// Write the digraph having adjacency vector a to out in digraph6
+// format (described at
+// https://users.cecs.anu.edu.au/~bdm/data/formats.txt), deleting
+// self-loops first if loopless is true, without terminating the line
+
+pub fn write_digraph6_code(out: &mut dyn Write, a: &Adj, loopless: bool)
+                           -> io::Result<()> {
+    write!(out, "&{}", int_to_ascii(a.len()))?;
+    write_adj_matrix(out, a, loopless)
+}
+
+
+// Write the digraph having adjacency vector a to out as a line in
+// digraph6 format, as in write_digraph6_code
+
+pub fn write_digraph6(out: &mut dyn Write, a: &Adj, loopless: bool)
+                      -> io::Result<()> {
+    write_digraph6_code(out, a, loopless)?;
+    writeln!(out)
+}
+
+
+// Parse a line in digraph6 format describing a functional digraph,
+// returning its adjacency vector; the vertices without outgoing edges
+// are given a self-loop, so that the output of --loopless can also be
+// read. Besides the standard encoding of the number n >= 63 of
+// vertices (~ followed by three characters), the one without ~
+// written by int_to_ascii is also accepted
+
+pub fn parse_digraph6(s: &str) -> Result<Adj, ParseError> {
+    fn error<T>(pos: usize, msg: &str) -> Result<T, ParseError> {
+        Err(ParseError { pos, msg: msg.to_string() })
+    }
+    let s = s.trim_end().as_bytes();
+    let digit = |i: usize| match s.get(i) {
+        Some(&c) if (63..127).contains(&c) => Ok((c - 63) as usize),
+        Some(_) => error(i, "invalid character in digraph6"),
+        None => error(i, "truncated digraph6"),
+    };
+    if s.first() != Some(&b'&') {
+        return error(0, "digraph6 must begin with &");
+    }
+    let (n, start) = match s.get(1) {
+        Some(b'~') if s.get(2) == Some(&b'~') => (usize::MAX, 2),
+        Some(b'~') => (digit(2)? << 12 | digit(3)? << 6 | digit(4)?, 5),
+        Some(b'?') if s.len() > 2 => (digit(2)? << 6 | digit(3)?, 4),
+        _ => (digit(1)?, 2),
+    };
+    if n > 255 {
+        return error(1, "digraph with more than 255 vertices");
+    }
+    if s.len() != start + (n * n).div_ceil(6) {
+        return error(s.len().min(start + (n * n).div_ceil(6)),
+                     "wrong length of digraph6");
+    }
+    let mut a = Adj::new();
+    for i in 0..n {
+        let mut image = None;
+        for j in 0..n {
+            let b = i * n + j;
+            let pos = start + b / 6;
+            if digit(pos)? >> (5 - b % 6) & 1 == 1 {
+                if image.is_some() {
+                    return error(pos, "vertex with more than one \
+                                       outgoing edge in digraph6");
+                }
+                image = Some(j as u8);
+            }
+        }
+        a.push(image.unwrap_or(i as u8));
+    }
+    // The bits after the matrix, if any, must be zero
+    let padding = (6 - n * n % 6) % 6;
+    if n > 0 && digit(s.len() - 1)? & ((1 << padding) - 1) != 0 {
+        return error(s.len() - 1, "nonzero padding bits in digraph6");
+    }
+    Ok(a)
+}
@@ -0,0 +1,202 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// A second generation algorithm, which enumerates the isomorphism
+// codes directly instead of by reverse search: first all rooted
+// trees of each size, as multisets of smaller trees, then all
+// components, as sequences of trees which are their own minimal
+// rotation, and finally all functional digraphs, as multisets of
+// components. The components of each size are sorted according to
+// order::cmp_comps, so the output order is the same as that of the reverse
+// search; however, all components of up to n vertices are kept in
+// memory. This is mostly useful for differential testing, since the
+// set of codes is computed independently from the reverse search
+// (only the order depends on it)
+
+
+use std::rc::Rc;
+use crate::{Comp, Func, Tree, Emit, naive_is_min_rotation, next_part};
+use crate::error::{Error, MAX_VERTICES};
+use crate::order::merge_path;
+
+
+// Compute the codes of all rooted trees of each number of vertices m
+// from 0 to n (at index m), in lexicographic order
+
+pub fn trees_by_size(n: usize) -> Vec<Vec<Rc<Tree>>> {
+    let mut trees = vec![Vec::new()];
+    let mut smaller = Vec::new();
+    for m in 1..=n {
+        let mut t = vec![m as u8];
+        let mut new = Vec::new();
+        push_trees(&smaller, 0, m - 1, &mut t, &mut new);
+        new.sort();
+        smaller.extend(new.iter().cloned());
+        trees.push(new);
+    }
+    trees
+}
+
+
+// Push onto out the codes of the trees obtained by appending to t
+// (the code of a root, possibly followed by some of its subtrees)
+// subtrees with rest vertices in total, taken from trees with indices
+// at least from, which must be sorted lexicographically (and then
+// also by number of vertices)
+
+fn push_trees(trees: &[Rc<Tree>], from: usize, rest: usize, t: &mut Tree,
+              out: &mut Vec<Rc<Tree>>) {
+    if rest == 0 {
+        out.push(Rc::new(t.clone()));
+        return;
+    }
+    for i in from..trees.len() {
+        if trees[i].len() > rest {
+            break;
+        }
+        let k = t.len();
+        t.extend_from_slice(&trees[i]);
+        push_trees(trees, i, rest - trees[i].len(), t, out);
+        t.truncate(k);
+    }
+}
+
+
+// Compute the codes of all components of each number of vertices m
+// from 0 to n (at index m), in generation order
+
+pub fn comps_by_size(n: usize) -> Vec<Vec<Rc<Comp>>> {
+    let trees: Vec<Rc<Tree>> = trees_by_size(n).into_iter().flatten()
+        .collect();
+    let mut comps = vec![Vec::new()];
+    for m in 1..=n {
+        let mut new = Vec::new();
+        // The first tree of a minimal rotation is not larger than the
+        // others, i.e., they come later in the list of trees
+        for i in 0..trees.len() {
+            if trees[i].len() > m {
+                break;
+            }
+            let mut c = vec![trees[i].clone()];
+            push_comps(&trees, i, m - trees[i].len(), &mut c, &mut new);
+        }
+        // Same order as cmp_comps, with the keys computed only once
+        new.sort_by_cached_key(merge_path);
+        comps.push(new.into_iter().map(Rc::new).collect());
+    }
+    comps
+}
+
+
+// Push onto out the components obtained by appending to c trees with
+// rest vertices in total, taken from trees with indices at least
+// from, and which are their own minimal rotation
+
+fn push_comps(trees: &[Rc<Tree>], from: usize, rest: usize, c: &mut Comp,
+              out: &mut Vec<Comp>) {
+    if rest == 0 {
+        if naive_is_min_rotation(c) {
+            out.push(c.clone());
+        }
+        return;
+    }
+    for i in from..trees.len() {
+        if trees[i].len() > rest {
+            break;
+        }
+        c.push(trees[i].clone());
+        push_comps(trees, from, rest - trees[i].len(), c, out);
+        c.pop();
+    }
+}
+
+
+// Generate all components of n vertices with the direct algorithm,
+// in the same way as generate_comps
+
+pub fn generate_comps_direct<E: From<Error>>(n: usize, emit: &mut Emit<E>)
+                                             -> Result<u64, E> {
+    if n > MAX_VERTICES {
+        return Err(Error::TooManyVertices(n).into());
+    }
+    let comps = comps_by_size(n);
+    for c in &comps[n] {
+        emit(&vec![c.clone()])?;
+    }
+    Ok(comps[n].len() as u64)
+}
+
+
+// Generate all functional digraphs of n vertices with the direct
+// algorithm, in the same way as generate_funcs: for each partition of
+// n, in the same order as next_part, the components of each size are
+// chosen as a nondecreasing sequence, the last one changing fastest
+
+pub fn generate_funcs_direct<E: From<Error>>(n: usize, emit: &mut Emit<E>)
+                                             -> Result<u64, E> {
+    if n > MAX_VERTICES {
+        return Err(Error::TooManyVertices(n).into());
+    }
+    generate_funcs_from_tables(n, &comps_by_size(n), emit)
+}
+
+
+// Generate all functional digraphs of n vertices whose components are
+// taken from comps, which contains all components of each number of
+// vertices m (at index m) in generation order, as for
+// generate_funcs_direct
+
+pub(crate) fn generate_funcs_from_tables<E>(n: usize,
+                                            comps: &[Vec<Rc<Comp>>],
+                                            emit: &mut Emit<E>)
+                                            -> Result<u64, E> {
+    let mut count = 0;
+    let mut p = Some(vec![1; n]);
+    while let Some(q) = p {
+        let mut g = Func::new();
+        let mut index = Vec::new();
+        emit_funcs(&q, comps, &mut g, &mut index, &mut count, emit)?;
+        p = next_part(&q);
+    }
+    Ok(count)
+}
+
+
+// Emit the functional digraphs having partition p whose first
+// components are g, with indices given by index in the lists of
+// components of their size
+
+fn emit_funcs<E>(p: &[u8], comps: &[Vec<Rc<Comp>>], g: &mut Func,
+                 index: &mut Vec<usize>, count: &mut u64,
+                 emit: &mut Emit<E>) -> Result<(), E> {
+    let i = g.len();
+    if i == p.len() {
+        *count += 1;
+        return emit(g);
+    }
+    let m = p[i] as usize;
+    let from = if i > 0 && p[i - 1] == p[i] { index[i - 1] } else { 0 };
+    for j in from..comps[m].len() {
+        g.push(comps[m][j].clone());
+        index.push(j);
+        emit_funcs(p, comps, g, index, count, emit)?;
+        g.pop();
+        index.pop();
+    }
+    Ok(())
+}
@@ -0,0 +1,130 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Output in the DOT language of Graphviz (https://graphviz.org)
+
+
+use std::io::{self, Write};
+use crate::{Comp, Func, comp_size, cycle, next_comp, unmerge};
+use crate::digraph6::func_adj;
+use crate::format::Pretty;
+use crate::invariants::FuncInvariants;
+
+
+// Options for the drawing of functional digraphs by write_dot: bold
+// red edges along the cycles, a cluster (drawn as a box) for each
+// connected component, and vertex labels with the preperiod and period
+// of each vertex
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DotStyle {
+    pub highlight_cycles: bool,
+    pub cluster_components: bool,
+    pub label_orbits: bool,
+}
+
+
+// Write functional digraph g to out as a DOT digraph styled according
+// to style, with the vertices named as in digraph6 format (without
+// renaming the distinguished vertex v, if any, which is drawn as a
+// double circle); the vertices are labelled with their names, followed
+// by their preperiod and period, as in 3\n(1, 2), if style.label_orbits
+// is true
+
+pub fn write_dot(out: &mut dyn Write, g: &Func, v: Option<usize>,
+                 style: &DotStyle) -> io::Result<()> {
+    let a = func_adj(g);
+    let orbits = g.vertex_orbits();
+    writeln!(out, "digraph {{")?;
+    writeln!(out, "  node [shape=circle];")?;
+    let mut x = 0;
+    for (i, c) in g.iter().enumerate() {
+        let indent = if style.cluster_components {
+            writeln!(out, "  subgraph cluster_{i} {{")?;
+            writeln!(out, "    style=rounded; color=gray;")?;
+            "    "
+        } else {
+            "  "
+        };
+        for _ in 0..comp_size(c) {
+            let mut attrs = Vec::new();
+            if style.label_orbits {
+                let (pre, per) = orbits[x];
+                attrs.push(format!("label=\"{x}\\n({pre}, {per})\""));
+            }
+            if v == Some(x) {
+                attrs.push("shape=doublecircle".to_string());
+            }
+            if attrs.is_empty() {
+                writeln!(out, "{indent}{x};")?;
+            } else {
+                writeln!(out, "{indent}{x} [{}];", attrs.join(", "))?;
+            }
+            x += 1;
+        }
+        if style.cluster_components {
+            writeln!(out, "  }}")?;
+        }
+    }
+    for (x, &y) in a.iter().enumerate() {
+        write!(out, "  {x} -> {y}")?;
+        if style.highlight_cycles && orbits[x].0 == 0 {
+            write!(out, " [style=bold, color=red]")?;
+        }
+        writeln!(out, ";")?;
+    }
+    write!(out, "}}")
+}
+
+
+// Write to out the reverse search trees of the components of each
+// number of vertices in sizes, as a DOT digraph with one cluster per
+// size; the nodes are the components (labelled in nested-bracket
+// notation) and each edge goes from a component to one of its merges,
+// labelled with the indices l, r of the merged trees. Only the path
+// from the root of the search to the current component is kept in
+// memory, but the output is huge unless the sizes are small
+
+pub fn write_search_tree(out: &mut dyn Write, sizes: &[usize])
+                         -> io::Result<()> {
+    writeln!(out, "digraph search {{")?;
+    writeln!(out, "  node [shape=box, fontname=monospace];")?;
+    for &n in sizes.iter().filter(|&&n| n > 0) {
+        writeln!(out, "  subgraph cluster_{n} {{")?;
+        writeln!(out, "    label=\"{n} vertices\";")?;
+        let mut path: Vec<(Comp, u64)> = Vec::new();
+        let mut count = 0;
+        let mut c = Some(cycle(n));
+        while let Some(d) = c {
+            writeln!(out, "    c{n}_{count} [label=\"{}\"];", Pretty(&d))?;
+            if let Some((u, l, r)) = unmerge(&d) {
+                while path.last().is_some_and(|(p, _)| *p != u) {
+                    path.pop();
+                }
+                let (_, parent) = path.last().unwrap();
+                writeln!(out, "    c{n}_{parent} -> c{n}_{count} \
+                               [label=\"{l}, {r}\"];")?;
+            }
+            c = next_comp(&d);
+            path.push((d, count));
+            count += 1;
+        }
+        writeln!(out, "  }}")?;
+    }
+    writeln!(out, "}}")
+}
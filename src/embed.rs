@@ -0,0 +1,316 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Embeddings of functional digraphs, component by component: a
+// rooted tree embeds into another one if its vertices can be mapped
+// injectively to those of the other tree, the root to the root and
+// the parent of each vertex to the parent of its image; a component
+// embeds into another one if their cycles have the same length and,
+// for some rotation, each tree along the cycle embeds into the tree
+// at the same position of the other component; finally, a functional
+// digraph embeds into another one if its components can be matched
+// to distinct components of the other digraph into which they embed.
+// The witness of an embedding of g into h is an injective map phi
+// from the vertices of g to those of h (named as in digraph6 format)
+// with phi[g(v)] = h(phi[v]) for all v
+
+
+use crate::{Comp, Func};
+
+
+// Children of the vertex at position i of tree t, i.e., the
+// positions of the roots of its immediate subtrees
+
+fn children(t: &[u8], i: usize) -> Vec<usize> {
+    let mut c = Vec::new();
+    let mut j = i + 1;
+    while j < i + t[i] as usize {
+        c.push(j);
+        j += t[j] as usize;
+    }
+    c
+}
+
+
+// Search for the embeddings of the subtrees of a tree s into those of
+// a tree t, remembering for each pair of positions (i, j) whether the
+// subtree of s at i embeds into the subtree of t at j: each pair is
+// examined at most once, since it is only reached from the pair of
+// the parents
+
+struct TreeEmbedding<'a> {
+    s: &'a [u8],
+    t: &'a [u8],
+    memo: Vec<Option<bool>>,
+}
+
+
+impl TreeEmbedding<'_> {
+
+    // Check whether the subtree of s at i embeds into the subtree of t
+    // at j, i.e., whether the children of i can be matched injectively
+    // to children of j into whose subtrees their subtrees embed
+
+    fn embeds(&mut self, i: usize, j: usize) -> bool {
+        let k = i * self.t.len() + j;
+        if let Some(e) = self.memo[k] {
+            return e;
+        }
+        let e = self.s[i] <= self.t[j] && self.matching(i, j).is_some();
+        self.memo[k] = Some(e);
+        e
+    }
+
+    // Compute a matching of the children of i (in s) to the children
+    // of j (in t) such that the subtree of each child of i embeds into
+    // that of its match, if any, by augmenting paths (as in Kuhn's
+    // algorithm); the result gives the match of each child of i
+
+    fn matching(&mut self, i: usize, j: usize) -> Option<Vec<usize>> {
+        let (ci, cj) = (children(self.s, i), children(self.t, j));
+        if ci.len() > cj.len() {
+            return None;
+        }
+        let mut ok = vec![vec![false; cj.len()]; ci.len()];
+        for (a, &x) in ci.iter().enumerate() {
+            for (b, &y) in cj.iter().enumerate() {
+                ok[a][b] = self.embeds(x, y);
+            }
+        }
+        let mut matched: Vec<Option<usize>> = vec![None; cj.len()];
+        for a in 0..ci.len() {
+            let mut visited = vec![false; cj.len()];
+            if !augment(a, &ok, &mut matched, &mut visited) {
+                return None;
+            }
+        }
+        let mut m = vec![0; ci.len()];
+        for (b, a) in matched.iter().enumerate() {
+            if let Some(a) = *a {
+                m[a] = cj[b];
+            }
+        }
+        Some(m)
+    }
+
+    // Fill phi with an embedding of the subtree of s at i into the
+    // subtree of t at j (which is assumed to exist), as positions
+
+    fn witness(&mut self, i: usize, j: usize, phi: &mut [usize]) {
+        phi[i] = j;
+        if let Some(m) = self.matching(i, j) {
+            for (x, y) in children(self.s, i).into_iter().zip(m) {
+                self.witness(x, y, phi);
+            }
+        }
+    }
+
+}
+
+
+// Try to match a (a child, or a component) to some b with ok[a][b],
+// possibly rematching the one currently matched to b, as in Kuhn's
+// algorithm
+
+fn augment(a: usize, ok: &[Vec<bool>], matched: &mut [Option<usize>],
+           visited: &mut [bool]) -> bool {
+    for b in 0..matched.len() {
+        if ok[a][b] && !visited[b] {
+            visited[b] = true;
+            if matched[b].is_none_or(|c| augment(c, ok, matched, visited)) {
+                matched[b] = Some(a);
+                return true;
+            }
+        }
+    }
+    false
+}
+
+
+// Compute an embedding of tree s into tree t (both given by their
+// codes, not necessarily canonical), as the position in t of the
+// image of each vertex of s in preorder, if any
+
+pub fn tree_embedding(s: &[u8], t: &[u8]) -> Option<Vec<usize>> {
+    let mut e = TreeEmbedding { s, t, memo: vec![None; s.len() * t.len()] };
+    if !e.embeds(0, 0) {
+        return None;
+    }
+    let mut phi = vec![0; s.len()];
+    e.witness(0, 0, &mut phi);
+    Some(phi)
+}
+
+
+// Compute an embedding of component c into component d, as the image
+// of each vertex of c in d (with the vertices of both components
+// named as in digraph6 format, i.e., from 0, tree after tree along
+// the cycle, each tree in preorder), if any; the first rotation of
+// the cycle of d that works is chosen
+
+pub fn comp_embedding(c: &Comp, d: &Comp) -> Option<Vec<usize>> {
+    let l = d.len();
+    if c.len() != l {
+        return None;
+    }
+    let mut start = vec![0; l];
+    for j in 1..l {
+        start[j] = start[j - 1] + d[j - 1].len();
+    }
+    'rotations: for r in 0..l {
+        let mut phi = Vec::new();
+        for (i, t) in c.iter().enumerate() {
+            let j = (i + r) % l;
+            let Some(e) = tree_embedding(t, &d[j]) else {
+                continue 'rotations;
+            };
+            phi.extend(e.iter().map(|&x| start[j] + x));
+        }
+        return Some(phi);
+    }
+    None
+}
+
+
+// Compute an embedding of functional digraph g into functional
+// digraph h, as the image of each vertex of g in h (with the vertices
+// named as in digraph6 format), if any; the components of g are
+// matched to distinct components of h by augmenting paths, and each
+// one is mapped by its embedding into its match
+
+pub fn func_embedding(g: &Func, h: &Func) -> Option<Vec<usize>> {
+    if g.len() > h.len() {
+        return None;
+    }
+    let mut start = vec![0; h.len()];
+    for j in 1..h.len() {
+        start[j] = start[j - 1] + h[j - 1].iter().map(|t| t.len())
+            .sum::<usize>();
+    }
+    let e: Vec<Vec<Option<Vec<usize>>>> = g.iter().map(|c| {
+        h.iter().map(|d| comp_embedding(c, d)).collect()
+    }).collect();
+    let ok: Vec<Vec<bool>> = e.iter().map(|row| {
+        row.iter().map(Option::is_some).collect()
+    }).collect();
+    let mut matched: Vec<Option<usize>> = vec![None; h.len()];
+    for a in 0..g.len() {
+        let mut visited = vec![false; h.len()];
+        if !augment(a, &ok, &mut matched, &mut visited) {
+            return None;
+        }
+    }
+    let mut m = vec![0; g.len()];
+    for (b, a) in matched.iter().enumerate() {
+        if let Some(a) = *a {
+            m[a] = b;
+        }
+    }
+    let mut phi = Vec::new();
+    for (a, &j) in m.iter().enumerate() {
+        phi.extend(e[a][j].iter().flatten().map(|&x| start[j] + x));
+    }
+    Some(phi)
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use crate::{Func, generate_funcs};
+    use crate::canon::canon_labelling;
+    use crate::digraph6::func_adj;
+    use crate::error::Error;
+    use super::*;
+
+
+    // Canonical code of the function given by its table
+
+    fn func(f: &[usize]) -> Func {
+        canon_labelling(f).unwrap().0
+    }
+
+
+    // Check whether some injective map phi from the vertices of f to
+    // those of g, extending the images phi of the vertices before v,
+    // satisfies phi[f(v)] = g(phi[v]) for all v, by brute force
+
+    fn embeds(f: &[u8], g: &[u8], phi: &mut Vec<usize>) -> bool {
+        let v = phi.len();
+        if v == f.len() {
+            return (0..v).all(|u| phi[f[u] as usize] == g[phi[u]] as usize);
+        }
+        for w in 0..g.len() {
+            if !phi.contains(&w) {
+                phi.push(w);
+                if embeds(f, g, phi) {
+                    return true;
+                }
+                phi.pop();
+            }
+        }
+        false
+    }
+
+
+    #[test]
+    fn no_embedding() {
+        // Two fixed points into one, a cycle of length 2 into a fixed
+        // point and two trees of one vertex into a tree of two
+        for (f, g) in [(&[0, 1][..], &[0][..]), (&[1, 0], &[0]),
+                       (&[1, 0], &[0, 0]), (&[0, 1], &[0, 0])] {
+            assert_eq!(func_embedding(&func(f), &func(g)), None);
+        }
+    }
+
+
+    // Check against brute force that all digraphs of up to 4 vertices
+    // embed into those of up to 5 vertices exactly when an injective
+    // map commuting with the functions exists, and that the witness
+    // is one
+
+    #[test]
+    fn embeddings_of_small_digraphs() {
+        let mut funcs = Vec::new();
+        for n in 1..=5 {
+            generate_funcs::<Error>(n, &mut |g| {
+                funcs.push(g.clone());
+                Ok(())
+            }).unwrap();
+        }
+        for g in funcs.iter().filter(|g| func_adj(g).len() <= 4) {
+            let a = func_adj(g);
+            for h in &funcs {
+                let b = func_adj(h);
+                let e = func_embedding(g, h);
+                assert_eq!(e.is_some(), embeds(&a, &b, &mut Vec::new()),
+                           "{g:?} into {h:?}");
+                if let Some(phi) = e {
+                    let mut images = phi.clone();
+                    images.sort();
+                    images.dedup();
+                    assert_eq!(images.len(), a.len(), "{phi:?}");
+                    assert!((0..a.len()).all(|v| {
+                        phi[a[v] as usize] == b[phi[v]] as usize
+                    }), "{phi:?}");
+                }
+            }
+        }
+    }
+
+}
@@ -0,0 +1,115 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Errors returned by the library, so that malformed inputs and
+// internal inconsistencies are reported to the caller instead of
+// aborting the process
+
+
+use std::error;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use crate::canon::InvalidEntry;
+use crate::parse::ParseError;
+
+
+// Maximum number of vertices of the digraphs, since the sizes of the
+// trees are stored as u8 in the isomorphism codes
+
+pub const MAX_VERTICES: usize = 255;
+
+
+// Crate-level error type: a code which cannot be parsed, an invalid
+// entry of an explicit description (e.g., a function table), a size
+// beyond MAX_VERTICES, an I/O error (e.g., while writing the output)
+// or an internal inconsistency, which is a bug of funkdigen2 and is
+// reported with a short description
+
+#[derive(Debug)]
+pub enum Error {
+    Parse(ParseError),
+    InvalidEntry(InvalidEntry),
+    TooManyVertices(usize),
+    Io(io::Error),
+    Internal(&'static str),
+}
+
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Error::Parse(e) => write!(f, "{e}"),
+            Error::InvalidEntry(e) => write!(f, "{e}"),
+            Error::TooManyVertices(n) => {
+                write!(f, "{n} vertices (at most {MAX_VERTICES} are \
+                           supported)")
+            }
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Internal(msg) => write!(f, "internal error: {msg}"),
+        }
+    }
+}
+
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Parse(e) => Some(e),
+            Error::InvalidEntry(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::TooManyVertices(_) | Error::Internal(_) => None,
+        }
+    }
+}
+
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Error {
+        Error::Parse(e)
+    }
+}
+
+
+impl From<InvalidEntry> for Error {
+    fn from(e: InvalidEntry) -> Error {
+        Error::InvalidEntry(e)
+    }
+}
+
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+
+// Errors can also be converted into I/O errors, so that the callbacks
+// of the generation functions can just return io::Result; the
+// original error can be recovered with io::Error::get_ref and
+// downcast_ref, except for I/O errors, which are returned unchanged
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> io::Error {
+        match e {
+            Error::Io(e) => e,
+            Error::Internal(_) => io::Error::other(e),
+            _ => io::Error::new(io::ErrorKind::InvalidInput, e),
+        }
+    }
+}
@@ -0,0 +1,286 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Completions of a partial function, i.e., the total functions on
+// vertices 0..n taking the given images on the vertices where they
+// are specified, up to the isomorphisms fixing every vertex mentioned
+// by the partial function (those with an image and the images
+// themselves); the other vertices are unlabelled, so only one
+// completion is generated for every way of renaming them
+
+
+use crate::{Tree, Func, comp_size, generate_comps, generate_funcs};
+use crate::digraph6::func_adj;
+use crate::error::Error;
+use crate::pointed::rotation_period;
+
+
+// Type for the callbacks receiving each completion, with its digraph
+// and its function table
+
+pub type EmitCompletion<'a, E> = dyn FnMut(&Func, &[usize]) -> Result<(), E>
+    + 'a;
+
+
+// Range of vertices start..end of a block
+
+type Range = (usize, usize);
+
+
+// Blocks of the representative of a functional digraph (as computed
+// by func_adj) whose labels must be in lexicographic order for a
+// labelling to be canonical: pairs of ranges of vertices, such as a
+// subtree and its previous sibling when they are isomorphic, or two
+// isomorphic components; and the components invariant under
+// rotations, with their range of vertices and the number of vertices
+// in each rotation by the period
+
+struct Blocks {
+    pairs: Vec<(Range, Range)>,
+    rotations: Vec<(Range, Vec<usize>)>,
+}
+
+
+// Add to pairs the isomorphic consecutive siblings in the subtree of
+// t having root at position i (named b + i) and in its subtrees
+
+fn add_tree_pairs(t: &Tree, i: usize, b: usize,
+                  pairs: &mut Vec<(Range, Range)>) {
+    let mut prev: Option<(usize, usize)> = None;
+    let mut j = i + 1;
+    while j < i + t[i] as usize {
+        let k = j + t[j] as usize;
+        if let Some((p, q)) = prev {
+            if t[p..q] == t[j..k] {
+                pairs.push(((b + p, b + q), (b + j, b + k)));
+            }
+        }
+        add_tree_pairs(t, j, b, pairs);
+        prev = Some((j, k));
+        j = k;
+    }
+}
+
+
+impl Blocks {
+
+    fn new(g: &Func) -> Blocks {
+        let mut pairs = Vec::new();
+        let mut rotations = Vec::new();
+        let mut b = 0;
+        for i in 0..g.len() {
+            let c = &g[i];
+            let m = comp_size(c);
+            if i > 0 && g[i] == g[i - 1] {
+                pairs.push(((b - m, b), (b, b + m)));
+            }
+            let p = rotation_period(c);
+            let mut shifts = Vec::new();
+            let mut r = b;
+            for j in 0..c.len() {
+                if j > 0 && j % p == 0 {
+                    shifts.push(r - b);
+                }
+                add_tree_pairs(&c[j], 0, r, &mut pairs);
+                r += c[j].len();
+            }
+            if !shifts.is_empty() {
+                rotations.push(((b, b + m), shifts));
+            }
+            b += m;
+        }
+        Blocks { pairs, rotations }
+    }
+
+    // Check if labelling l (where the unlabelled vertices have the
+    // largest label) is the canonical one of its orbit under the
+    // automorphisms of the representative: the isomorphic sibling
+    // subtrees and components are in nondecreasing order of their
+    // labels, as sequences, and so are the rotations of each
+    // component. Since the automorphisms are generated by swapping
+    // isomorphic siblings and rotating the cycles, exactly one
+    // labelling satisfies both conditions in each orbit
+
+    fn is_canonical(&self, l: &[usize]) -> bool {
+        self.pairs.iter().all(|&((p, q), (j, k))| l[p..q] <= l[j..k])
+            && self.rotations.iter().all(|((b, e), shifts)| {
+                let s = &l[*b..*e];
+                shifts.iter().all(|&r| {
+                    s.iter().cmp(s[r..].iter().chain(&s[..r])).is_le()
+                })
+            })
+    }
+
+}
+
+
+// State of the backtracking assigning the vertices mentioned by the
+// partial function p to vertices of the representative with adjacency
+// vector a: the vertex of the representative assigned to each of
+// them, if any, and which ones are used
+
+struct Completion<'a> {
+    p: &'a [Option<usize>],
+    a: Vec<usize>,
+    blocks: Blocks,
+    mentioned: &'a [usize],
+    assigned: Vec<Option<usize>>,
+    used: Vec<bool>,
+    label: Vec<usize>,
+    f: Vec<usize>,
+}
+
+
+impl Completion<'_> {
+
+    // Assign vertex x of the partial function to vertex y of the
+    // representative, together with the images forced by p, pushing
+    // the assignments to trail; return false if this contradicts p
+    // or the assignments made so far
+
+    fn assign(&mut self, x: usize, y: usize, trail: &mut Vec<usize>)
+              -> bool {
+        let mut stack = vec![(x, y)];
+        while let Some((x, y)) = stack.pop() {
+            match self.assigned[x] {
+                Some(z) if z == y => continue,
+                Some(_) => return false,
+                None if self.used[y] => return false,
+                None => (),
+            }
+            self.assigned[x] = Some(y);
+            self.used[y] = true;
+            trail.push(x);
+            if let Some(fx) = self.p[x] {
+                stack.push((fx, self.a[y]));
+            }
+            // The vertices already assigned whose image is x must be
+            // mapped to preimages of y
+            for &w in self.mentioned {
+                if self.p[w] == Some(x) {
+                    if let Some(z) = self.assigned[w] {
+                        if self.a[z] != y {
+                            return false;
+                        }
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    // Undo the assignments pushed to trail after position len
+
+    fn undo(&mut self, trail: &mut Vec<usize>, len: usize) {
+        for x in trail.drain(len..) {
+            if let Some(y) = self.assigned[x].take() {
+                self.used[y] = false;
+            }
+        }
+    }
+
+    // Assign the mentioned vertices from the i-th onwards in all
+    // possible ways, passing each canonical completion to emit
+
+    fn extend<E>(&mut self, g: &Func, i: usize, count: &mut u64,
+                 emit: &mut EmitCompletion<E>) -> Result<(), E> {
+        let n = self.a.len();
+        if i == self.mentioned.len() {
+            self.label.fill(n);
+            for &x in self.mentioned {
+                self.label[self.assigned[x].unwrap()] = x;
+            }
+            if !self.blocks.is_canonical(&self.label) {
+                return Ok(());
+            }
+            // The unlabelled vertices get the remaining labels in the
+            // order of the representative
+            let mut free = (0..n).filter(|&x| self.p[x].is_none()
+                                         && !self.mentioned.contains(&x));
+            for y in 0..n {
+                if self.label[y] == n {
+                    self.label[y] = free.next().unwrap();
+                }
+            }
+            for y in 0..n {
+                self.f[self.label[y]] = self.label[self.a[y]];
+            }
+            *count += 1;
+            return emit(g, &self.f);
+        }
+        let x = self.mentioned[i];
+        if self.assigned[x].is_some() {
+            return self.extend(g, i + 1, count, emit);
+        }
+        let mut trail = Vec::new();
+        for y in 0..n {
+            if !self.used[y] {
+                if self.assign(x, y, &mut trail) {
+                    self.extend(g, i + 1, count, emit)?;
+                }
+                self.undo(&mut trail, 0);
+            }
+        }
+        Ok(())
+    }
+
+}
+
+
+// Generate the completions of partial function p (where p[x] is the
+// image of vertex x, if specified), or only the connected ones, and
+// pass each of them to emit, both as its functional digraph and as
+// a function table, returning their number. The digraphs with the
+// right number of vertices are generated, and for each of them the
+// mentioned vertices are assigned to the vertices of its
+// representative in all ways compatible with p, by backtracking, up
+// to the automorphisms of the representative; the completions are
+// then in generation order of their digraphs
+
+pub fn generate_completions<E: From<Error>>(p: &[Option<usize>],
+                                            connected: bool,
+                                            emit: &mut EmitCompletion<E>)
+                                            -> Result<u64, E> {
+    let n = p.len();
+    let mut mentioned: Vec<usize> = (0..n).filter(|&x| {
+        p[x].is_some() || p.contains(&Some(x))
+    }).collect();
+    // The vertices with an image are assigned first, so that their
+    // images are forced
+    mentioned.sort_by_key(|&x| p[x].is_none());
+    let mut count = 0;
+    let mut each = |g: &Func| {
+        let mut state = Completion {
+            p,
+            a: func_adj(g).iter().map(|&w| w as usize).collect(),
+            blocks: Blocks::new(g),
+            mentioned: &mentioned,
+            assigned: vec![None; n],
+            used: vec![false; n],
+            label: vec![0; n],
+            f: vec![0; n],
+        };
+        state.extend(g, 0, &mut count, emit)
+    };
+    if connected {
+        generate_comps(n, &mut each)?;
+    } else {
+        generate_funcs(n, &mut each)?;
+    }
+    Ok(count)
+}
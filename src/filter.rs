@@ -0,0 +1,787 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Generation of functional digraphs satisfying some constraints,
+// which are checked as early as possible in order to prune the
+// search
+
+
+use std::fmt::{self, Display, Formatter};
+use std::rc::Rc;
+use std::str::FromStr;
+use crate::{Tree, Comp, Func, Part, Emit, cycle, next_comp, skip_comp,
+            next_part, part, comp_size, unmerge, generate_comps};
+use crate::error::{Error, MAX_VERTICES};
+use crate::invariants::{CompInvariants, FuncInvariants, Invariant, Value};
+use crate::pointed::{is_rigid_tree, rotation_period};
+
+
+// Constraints on the generated digraphs; a field set to None means
+// that the corresponding invariant is unconstrained, and value is an
+// invariant required to have a given value; with rigid, only the
+// digraphs without nontrivial automorphisms are accepted, with
+// distinct_comps only those without isomorphic components, and the
+// digraphs having a vertex whose subtree (the vertex together with
+// all vertices reaching it without going through the cycle) is one
+// of the forbidden_trees (given by their canonical codes) are rejected
+
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    pub min_height: Option<usize>,
+    pub max_height: Option<usize>,
+    pub min_comp_size: Option<usize>,
+    pub max_comp_size: Option<usize>,
+    pub min_leaves: Option<usize>,
+    pub max_leaves: Option<usize>,
+    pub min_trivial_trees: Option<usize>,
+    pub max_trivial_trees: Option<usize>,
+    pub indegrees: Option<IndegreeSeq>,
+    pub max_depth: Option<usize>,
+    pub min_cycle_length: Option<usize>,
+    pub max_cycle_length: Option<usize>,
+    pub rigid: bool,
+    pub distinct_comps: bool,
+    pub forbidden_trees: Vec<Tree>,
+    pub value: Option<(&'static dyn Invariant, Value)>,
+    pub slice: Option<Slice>,
+}
+
+
+// Slice res of mod of the digraphs, for splitting their generation
+// among mod processes: the digraphs are assigned to slices according
+// to their last component (the largest one which, in the generation
+// order, changes fastest), or rather to the ancestor of the last
+// component at depth SLICE_DEPTH in the reverse search, so that each
+// process can skip the subtrees of the search assigned to the others
+// (the digraphs whose last component is not as deep as that are
+// assigned according to the last component itself)
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Slice {
+    pub res: u64,
+    pub modulus: u64,
+}
+
+
+// Depth in the reverse search of the components determining the slice
+
+const SLICE_DEPTH: usize = 3;
+
+
+impl Slice {
+
+    // Check if the subtree of the reverse search rooted at component
+    // c, of depth SLICE_DEPTH (or just c, if it is not as deep), is
+    // assigned to this slice; this uses the FNV-1a hash of the code of
+    // c, for spreading the subtrees evenly among all slices
+
+    fn owns_subtree(&self, c: &Comp) -> bool {
+        let mut h: u64 = 0xcbf29ce484222325;
+        for t in c {
+            for &b in t.iter() {
+                h ^= b as u64;
+                h = h.wrapping_mul(0x100000001b3);
+            }
+        }
+        h % self.modulus == self.res
+    }
+
+}
+
+
+impl Display for Slice {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.res, self.modulus)
+    }
+}
+
+
+impl FromStr for Slice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Slice, String> {
+        let err = || format!("invalid slice '{s}' (expected RES/MOD \
+                              with RES < MOD)");
+        let (res, modulus) = s.split_once('/').ok_or_else(err)?;
+        let (Ok(res), Ok(modulus)) = (res.parse(), modulus.parse()) else {
+            return Err(err());
+        };
+        if res >= modulus {
+            return Err(err());
+        }
+        Ok(Slice { res, modulus })
+    }
+}
+
+
+// Multiset of in-degrees, represented by the number of vertices of
+// each in-degree d (up to the maximum in-degree); it is written as a
+// comma-separated list of in-degrees d, or d^m for in-degree d with
+// multiplicity m, e.g., 0^3,1^2,2,3
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndegreeSeq(pub Vec<usize>);
+
+
+impl Display for IndegreeSeq {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut sep = "";
+        for (d, &m) in self.0.iter().enumerate() {
+            if m > 0 {
+                write!(f, "{sep}{d}^{m}")?;
+                sep = ",";
+            }
+        }
+        Ok(())
+    }
+}
+
+
+impl FromStr for IndegreeSeq {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<IndegreeSeq, String> {
+        let mut counts = Vec::new();
+        for item in s.split(',') {
+            let item = item.trim();
+            let (d, m) = item.split_once('^').unwrap_or((item, "1"));
+            let (Ok(d), Ok(m)) = (d.parse::<usize>(), m.parse::<usize>())
+            else {
+                return Err(format!("invalid in-degree multiplicity '{item}'"));
+            };
+            if d >= counts.len() {
+                counts.resize(d + 1, 0);
+            }
+            counts[d] += m;
+        }
+        while counts.last() == Some(&0) {
+            counts.pop();
+        }
+        Ok(IndegreeSeq(counts))
+    }
+}
+
+
+// Check if tree t has a vertex whose subtree is s (both given by
+// their canonical codes, so the subtree codes of t are canonical too)
+
+fn has_subtree(t: &Tree, s: &Tree) -> bool {
+    (0..t.len()).any(|i| {
+        t[i] as usize == s.len() && t[i..i + s.len()] == s[..]
+    })
+}
+
+
+impl Filter {
+
+    // Check if the filter accepts all digraphs, i.e., no constraint
+    // is set and there is no slice
+
+    pub fn is_unconstrained(&self) -> bool {
+        self.min_height.is_none() && self.max_height.is_none()
+            && self.min_comp_size.is_none() && self.max_comp_size.is_none()
+            && self.min_leaves.is_none() && self.max_leaves.is_none()
+            && self.min_trivial_trees.is_none()
+            && self.max_trivial_trees.is_none()
+            && self.indegrees.is_none() && self.max_depth.is_none()
+            && self.min_cycle_length.is_none()
+            && self.max_cycle_length.is_none() && !self.rigid
+            && !self.distinct_comps && self.forbidden_trees.is_empty()
+            && self.value.is_none()
+            && self.slice.is_none()
+    }
+
+    // Check if the components of a digraph must be pairwise
+    // non-isomorphic, which is also the case for rigid digraphs
+
+    fn distinct(&self) -> bool {
+        self.distinct_comps || self.rigid
+    }
+
+    // Check if component c, as well as all components obtained from
+    // it by merging (i.e., its descendants in the reverse search
+    // tree), cannot appear in any accepted digraph; merging trees
+    // never decreases their height or their number of leaves (the
+    // merged trivial trees become leaves, which also bounds the
+    // number of vertices of in-degree 0) or their depth in the search
+    // (which grows by one with each merge), and does not change the
+    // size of the component (so components of the wrong size are
+    // pruned at the root of the search, and the corresponding
+    // partitions are skipped altogether); the merged trees become
+    // subtrees of the new one, so a tree which is not rigid remains so,
+    // and a forbidden subtree remains as well; finally, merging makes
+    // the cycle shorter, so a cycle which is too short stays so
+
+    pub fn prune_comp(&self, c: &Comp) -> bool {
+        self.max_height.is_some_and(|h| c.height() > h)
+            || self.min_comp_size.is_some_and(|m| c.size() < m)
+            || self.max_comp_size.is_some_and(|m| c.size() > m)
+            || self.max_leaves.is_some_and(|k| c.num_leaves() > k)
+            || self.indegrees.as_ref().is_some_and(|s| {
+                c.num_leaves() > s.0.first().copied().unwrap_or(0)
+            })
+            || self.max_depth.is_some_and(|d| c.depth() > d)
+            || self.min_cycle_length.is_some_and(|l| c.cycle_length() < l)
+            || self.rigid && !c.iter().all(|t| is_rigid_tree(t))
+            || !self.forbidden_trees.is_empty() && c.iter().any(|t| {
+                self.forbidden_trees.iter().any(|s| has_subtree(t, s))
+            })
+            || self.value.is_some_and(|(inv, v)| inv.prune_comp(c, v))
+    }
+
+    // Check if digraphs whose components have the sizes in partition
+    // p can be accepted; only the number of components is checked,
+    // if it is required to have a given value, so that the other
+    // partitions are skipped altogether
+
+    pub fn accept_part(&self, p: &Part) -> bool {
+        self.value.is_none_or(|(inv, v)| inv.accept_part(p, v))
+    }
+
+    // Check if component c can appear in an accepted digraph (c is
+    // assumed not to be pruned). A maximum length of the cycle cannot
+    // be used for pruning, since merging makes it shorter, i.e., the
+    // components with long cycles are the ancestors of all others;
+    // however, rejecting them here means that digraphs having one of
+    // them as a component are never built. Similarly, a rigid
+    // component must not be invariant under any nontrivial rotation
+
+    pub fn accept_comp(&self, c: &Comp) -> bool {
+        self.max_cycle_length.is_none_or(|l| c.cycle_length() <= l)
+            && (!self.rigid || rotation_period(c) == c.len())
+    }
+
+    // Check if component c, as well as all its descendants, cannot
+    // be the last component of an accepted digraph because of the
+    // slice; components deeper than SLICE_DEPTH are assumed to be
+    // descendants of one in the slice
+
+    fn prune_last(&self, c: &Comp) -> bool {
+        self.slice.is_some_and(|s| {
+            c.depth() == SLICE_DEPTH && !s.owns_subtree(c)
+        })
+    }
+
+    // Check if component c can be the last component of an accepted
+    // digraph (with the same assumptions as prune_last)
+
+    fn accept_last(&self, c: &Comp) -> bool {
+        self.slice.is_none_or(|s| {
+            c.depth() >= SLICE_DEPTH || s.owns_subtree(c)
+        })
+    }
+
+    // Check if functional digraph g is accepted (its components are
+    // assumed to be accepted, the last one also as such, and distinct
+    // if required); the empty digraph belongs to slice 0
+
+    pub fn accept_func(&self, g: &Func) -> bool {
+        if g.is_empty() && self.slice.is_some_and(|s| s.res != 0) {
+            return false;
+        }
+        self.min_height.is_none_or(|h| g.height() >= h)
+            && self.min_leaves.is_none_or(|k| g.num_leaves() >= k)
+            && self.max_leaves.is_none_or(|k| g.num_leaves() <= k)
+            && self.min_trivial_trees.is_none_or(|k| {
+                g.num_trivial_trees() >= k
+            })
+            && self.max_trivial_trees.is_none_or(|k| {
+                g.num_trivial_trees() <= k
+            })
+            && self.indegrees.as_ref().is_none_or(|s| {
+                g.indegree_counts() == s.0
+            })
+            && self.max_depth.is_none_or(|d| g.depth() <= d)
+            && self.value.is_none_or(|(inv, v)| inv.compute(g) == v)
+    }
+
+    // Check if functional digraph g (with canonical code) is accepted,
+    // without any assumption on its components, e.g., for checking a
+    // digraph given as the start of the generation; the slice is not
+    // checked, since components deeper than SLICE_DEPTH are assumed
+    // to be in the slice
+
+    pub fn accepts(&self, g: &Func) -> bool {
+        self.accept_part(&part(g))
+            && g.iter().all(|c| !self.prune_comp(c) && self.accept_comp(c))
+            && (!self.distinct() || g.windows(2).all(|w| w[0] != w[1]))
+            && self.accept_func(g)
+    }
+
+}
+
+
+// Compute the first component in generation order, starting from c
+// itself, which is accepted by filter f
+
+fn accepted_from(mut c: Comp, f: &Filter) -> Option<Comp> {
+    loop {
+        if f.prune_comp(&c) {
+            c = skip_comp(&c)?;
+        } else if f.accept_comp(&c) {
+            return Some(c);
+        } else {
+            c = next_comp(&c)?;
+        }
+    }
+}
+
+
+// Compute the next component after c (which is assumed to be
+// accepted) which is accepted by filter f
+
+fn next_accepted_comp(c: &Comp, f: &Filter) -> Option<Comp> {
+    accepted_from(next_comp(c)?, f)
+}
+
+
+// Compute the first component in generation order, starting from c
+// itself, which is accepted by filter f as the last component of a
+// digraph, i.e., which also belongs to the slice of f (if any). If c
+// is deeper than SLICE_DEPTH in another slice, the search restarts
+// after the subtree of its ancestor at depth SLICE_DEPTH; from then
+// on, the components deeper than that are only reached through an
+// ancestor in the slice
+
+fn last_accepted_from(mut c: Comp, f: &Filter)
+                      -> Result<Option<Comp>, Error> {
+    if let Some(s) = f.slice {
+        let d = c.depth();
+        if d > SLICE_DEPTH {
+            let mut a = c.clone();
+            for _ in SLICE_DEPTH..d {
+                a = unmerge(&a).ok_or(Error::Internal(
+                    "component deeper than its ancestors"))?.0;
+            }
+            if !s.owns_subtree(&a) {
+                let Some(b) = skip_comp(&a) else { return Ok(None) };
+                c = b;
+            }
+        }
+    }
+    loop {
+        let next = if f.prune_comp(&c) || f.prune_last(&c) {
+            skip_comp(&c)
+        } else if f.accept_comp(&c) && f.accept_last(&c) {
+            return Ok(Some(c));
+        } else {
+            next_comp(&c)
+        };
+        let Some(d) = next else { return Ok(None) };
+        c = d;
+    }
+}
+
+
+// Compute the next component after c (which is assumed to be
+// accepted as a last component) which is accepted by filter f as
+// the last component of a digraph
+
+fn next_last_comp(c: &Comp, f: &Filter) -> Result<Option<Comp>, Error> {
+    match next_comp(c) {
+        Some(d) => last_accepted_from(d, f),
+        None => Ok(None),
+    }
+}
+
+
+// Generate all connected functional digraphs of n vertices accepted
+// by filter f, pass each of them to emit and return their count; as
+// for generate_comps, sizes larger than MAX_VERTICES give an error.
+// Without constraints this is just generate_comps, whose explicit
+// search stack makes it faster than skipping through next_comp
+
+pub fn generate_comps_filtered<E: From<Error>>(n: usize, f: &Filter,
+                                               emit: &mut Emit<E>)
+                                               -> Result<u64, E> {
+    generate_comps_filtered_from(n, f, None, emit)
+}
+
+
+// Same as generate_comps_filtered, but starting from digraph start
+// (included) if given, e.g., for resuming an interrupted generation;
+// start must be a digraph accepted by filter f, or one visited by
+// visit_comps_filtered_from
+
+pub fn generate_comps_filtered_from<E: From<Error>>(n: usize, f: &Filter,
+                                                    start: Option<&Func>,
+                                                    emit: &mut Emit<E>)
+                                                    -> Result<u64, E> {
+    visit_comps_filtered_from(n, f, start, &mut |g, accepted| {
+        if accepted { emit(g) } else { Ok(()) }
+    })
+}
+
+
+// Type for the callbacks receiving each digraph visited by the
+// filtered generation, i.e., each digraph whose components are all
+// accepted by the filter, together with whether the digraph itself is
+// accepted. Only the digraphs having a rejected component are skipped
+// without being visited, so the callback is called regularly even if
+// the filter accepts few digraphs (e.g., for checking a time limit);
+// an error returned by the callback stops the generation
+
+pub type Visit<'a, E> = dyn FnMut(&Func, bool) -> Result<(), E> + 'a;
+
+
+// Same as generate_comps_filtered_from, but passing each digraph
+// visited to visit instead of only the accepted ones to emit; it
+// returns the number of accepted digraphs
+
+pub fn visit_comps_filtered_from<E: From<Error>>(n: usize, f: &Filter,
+                                                 start: Option<&Func>,
+                                                 visit: &mut Visit<E>)
+                                                 -> Result<u64, E> {
+    if n > MAX_VERTICES {
+        return Err(Error::TooManyVertices(n).into());
+    }
+    if f.is_unconstrained() && start.is_none() {
+        return generate_comps(n, &mut |g| visit(g, true));
+    }
+    if n == 0 || !f.accept_part(&vec![n as u8]) {
+        return Ok(0);
+    }
+    let mut count = 0;
+    let mut c = match start {
+        Some(g) => Some((*g[0]).clone()),
+        None => last_accepted_from(cycle(n), f)?,
+    };
+    while let Some(d) = c {
+        let g: Func = vec![Rc::new(d)];
+        let accepted = f.accept_func(&g);
+        visit(&g, accepted)?;
+        count += accepted as u64;
+        c = next_last_comp(&g[0], f)?;
+    }
+    Ok(count)
+}
+
+
+// First accepted components of each number m of vertices (at index
+// m), if any, both in general and as the last component of a digraph
+
+struct FirstComps {
+    any: Vec<Option<Rc<Comp>>>,
+    last: Vec<Option<Rc<Comp>>>,
+}
+
+
+// Compute the first component of m vertices accepted by filter f
+// which can follow component prev (if any) in a digraph, also as the
+// last component if last is true: this is the first accepted
+// component of that size if prev is smaller, otherwise prev itself,
+// unless f requires distinct components (then the next one after
+// prev is needed)
+
+fn following(prev: Option<&Rc<Comp>>, m: usize, f: &Filter,
+             first: &FirstComps, last: bool)
+             -> Result<Option<Rc<Comp>>, Error> {
+    let Some(prev) = prev.filter(|c| comp_size(c) == m) else {
+        let c = if last { &first.last[m] } else { &first.any[m] };
+        return Ok(c.clone());
+    };
+    Ok(match (f.distinct(), last) {
+        (false, false) => Some(prev.clone()),
+        (false, true) => last_accepted_from((**prev).clone(), f)?
+            .map(Rc::new),
+        (true, false) => next_accepted_comp(prev, f).map(Rc::new),
+        (true, true) => next_last_comp(prev, f)?.map(Rc::new),
+    })
+}
+
+
+// Complete the prefix h of a digraph with the first accepted
+// components having the sizes in sizes (the last one also accepted
+// as such), if any
+
+fn complete(mut h: Func, sizes: &[u8], f: &Filter, first: &FirstComps)
+            -> Result<Option<Func>, Error> {
+    for (j, &m) in sizes.iter().enumerate() {
+        let last = j == sizes.len() - 1;
+        match following(h.last(), m as usize, f, first, last)? {
+            Some(c) => h.push(c),
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(h))
+}
+
+
+// Compute the first functional digraph having partition p, or the
+// next partition after it accepted by filter f, whose components are
+// all accepted by f
+
+fn first_func_from(mut p: Part, f: &Filter, first: &FirstComps)
+                   -> Result<Option<Func>, Error> {
+    loop {
+        while !f.accept_part(&p) {
+            let Some(q) = next_part(&p) else { return Ok(None) };
+            p = q;
+        }
+        if let Some(g) = complete(Vec::new(), &p, f, first)? {
+            return Ok(Some(g));
+        }
+        let Some(q) = next_part(&p) else { return Ok(None) };
+        p = q;
+    }
+}
+
+
+// Compute the next functional digraph after g whose components are
+// all accepted by filter f, as in next_func. The last component must
+// also be accepted as such: if none is (for a given prefix g[0], ...,
+// g[i]) then the same holds for the following components at position
+// i, since the last component is either the first one of its size or
+// comes after g[i], so the search continues at position i - 1; the
+// same holds for the other components following g[i] if they must be
+// distinct
+
+fn next_accepted_func(g: &Func, f: &Filter, first: &FirstComps)
+                      -> Result<Option<Func>, Error> {
+    let k = g.len();
+    if k == 0 {
+        return Ok(None);
+    }
+    if let Some(c) = next_last_comp(&g[k - 1], f)? {
+        let mut h = g[0..k - 1].to_vec();
+        h.push(Rc::new(c));
+        return Ok(Some(h));
+    }
+    let sizes = part(g);
+    for i in (0..k - 1).rev() {
+        if let Some(c) = next_accepted_comp(&g[i], f) {
+            let mut h = g[0..i].to_vec();
+            h.push(Rc::new(c));
+            if let Some(h) = complete(h, &sizes[i + 1..], f, first)? {
+                return Ok(Some(h));
+            }
+        }
+    }
+    match next_part(&sizes) {
+        Some(p) => first_func_from(p, f, first),
+        None => Ok(None),
+    }
+}
+
+
+// Generate all functional digraphs of n vertices accepted by filter
+// f, pass each of them to emit and return their count; as for
+// generate_funcs, sizes larger than MAX_VERTICES give an error
+
+pub fn generate_funcs_filtered<E: From<Error>>(n: usize, f: &Filter,
+                                               emit: &mut Emit<E>)
+                                               -> Result<u64, E> {
+    generate_funcs_filtered_from(n, f, None, emit)
+}
+
+
+// Same as generate_funcs_filtered, but starting from digraph start
+// (included) if given, as for generate_comps_filtered_from
+
+pub fn generate_funcs_filtered_from<E: From<Error>>(n: usize, f: &Filter,
+                                                    start: Option<&Func>,
+                                                    emit: &mut Emit<E>)
+                                                    -> Result<u64, E> {
+    visit_funcs_filtered_from(n, f, start, &mut |g, accepted| {
+        if accepted { emit(g) } else { Ok(()) }
+    })
+}
+
+
+// Same as generate_funcs_filtered_from, but passing each digraph
+// visited to visit, as visit_comps_filtered_from
+
+pub fn visit_funcs_filtered_from<E: From<Error>>(n: usize, f: &Filter,
+                                                 start: Option<&Func>,
+                                                 visit: &mut Visit<E>)
+                                                 -> Result<u64, E> {
+    if n > MAX_VERTICES {
+        return Err(Error::TooManyVertices(n).into());
+    }
+    let mut first = FirstComps { any: vec![None], last: vec![None] };
+    for m in 1..=n {
+        let c = accepted_from(cycle(m), f);
+        let last = match c.clone() {
+            Some(c) => last_accepted_from(c, f)?,
+            None => None,
+        };
+        first.last.push(last.map(Rc::new));
+        first.any.push(c.map(Rc::new));
+    }
+    let mut count = 0;
+    let mut g = match start {
+        Some(g) => Some(g.clone()),
+        None => first_func_from(vec![1; n], f, &first)?,
+    };
+    while let Some(h) = g {
+        let accepted = f.accept_func(&h);
+        visit(&h, accepted)?;
+        count += accepted as u64;
+        g = next_accepted_func(&h, f, &first)?;
+    }
+    Ok(count)
+}
+
+
+// Generator of the functional digraphs accepted by a filter for a
+// range of sizes, which keeps the accepted components of each size
+// (in generation order, together with the positions of those also
+// accepted as last components) from one size to the next, instead of
+// enumerating them again for every digraph and every size as in
+// generate_funcs_filtered; the digraphs of each size are generated in
+// the same order, but all accepted components smaller than the
+// current size are kept in memory
+
+pub struct RangeGenerator<'a> {
+    filter: &'a Filter,
+    comps: Vec<Vec<Rc<Comp>>>,
+    lasts: Vec<Vec<usize>>,
+}
+
+
+impl<'a> RangeGenerator<'a> {
+
+    // Create a generator for the digraphs accepted by filter f
+
+    pub fn new(f: &'a Filter) -> RangeGenerator<'a> {
+        RangeGenerator { filter: f, comps: vec![Vec::new()],
+                         lasts: vec![Vec::new()] }
+    }
+
+    // Enumerate the accepted components of each size smaller than n
+    // not already kept; those accepted as last components are a
+    // subsequence of them, so both are enumerated together
+
+    fn extend(&mut self, n: usize) -> Result<(), Error> {
+        let f = self.filter;
+        for m in self.comps.len()..n {
+            let mut comps = Vec::new();
+            let mut lasts = Vec::new();
+            let mut c = accepted_from(cycle(m), f);
+            let mut last = match c.clone() {
+                Some(c) => last_accepted_from(c, f)?,
+                None => None,
+            };
+            while let Some(d) = c {
+                if last.as_ref() == Some(&d) {
+                    lasts.push(comps.len());
+                    last = next_last_comp(&d, f)?;
+                }
+                c = next_accepted_comp(&d, f);
+                comps.push(Rc::new(d));
+            }
+            self.comps.push(comps);
+            self.lasts.push(lasts);
+        }
+        Ok(())
+    }
+
+    // Generate all functional digraphs of n vertices accepted by the
+    // filter, pass each of them to emit and return their count, as
+    // for generate_funcs_filtered
+
+    pub fn generate<E: From<Error>>(&mut self, n: usize,
+                                    emit: &mut Emit<E>)
+                                    -> Result<u64, E> {
+        if n > MAX_VERTICES {
+            return Err(Error::TooManyVertices(n).into());
+        }
+        self.extend(n)?;
+        let f = self.filter;
+        let mut count = 0;
+        let mut p = vec![1; n];
+        loop {
+            if f.accept_part(&p) {
+                if p.len() == 1 {
+                    count += self.generate_connected(n, emit)?;
+                } else {
+                    let mut h = Func::new();
+                    count += self.complete(&mut h, &p, 0, emit)?;
+                }
+            }
+            let Some(q) = next_part(&p) else { break };
+            p = q;
+        }
+        Ok(count)
+    }
+
+    // Generate the connected digraphs of n vertices accepted by the
+    // filter, whose components are not kept
+
+    fn generate_connected<E: From<Error>>(&self, n: usize,
+                                          emit: &mut Emit<E>)
+                                          -> Result<u64, E> {
+        let f = self.filter;
+        let mut count = 0;
+        let mut c = match accepted_from(cycle(n), f) {
+            Some(c) => last_accepted_from(c, f)?,
+            None => None,
+        };
+        while let Some(d) = c {
+            let g: Func = vec![Rc::new(d)];
+            if f.accept_func(&g) {
+                emit(&g)?;
+                count += 1;
+            }
+            c = next_last_comp(&g[0], f)?;
+        }
+        Ok(count)
+    }
+
+    // Complete the prefix h of a digraph, whose last component (if
+    // any) has position i among those of its size, in all possible
+    // ways with components having the sizes in sizes, pass each
+    // accepted digraph to emit and return their count
+
+    fn complete<E: From<Error>>(&self, h: &mut Func, sizes: &[u8],
+                                i: usize, emit: &mut Emit<E>)
+                                -> Result<u64, E> {
+        let Some((&m, rest)) = sizes.split_first() else {
+            if self.filter.accept_func(h) {
+                emit(h)?;
+                return Ok(1);
+            }
+            return Ok(0);
+        };
+        let m = m as usize;
+        let start = match h.last() {
+            Some(c) if comp_size(c) == m => {
+                i + self.filter.distinct() as usize
+            }
+            _ => 0,
+        };
+        let comps = &self.comps[m];
+        let mut count = 0;
+        if rest.is_empty() {
+            let lasts = &self.lasts[m];
+            for &j in &lasts[lasts.partition_point(|&j| j < start)..] {
+                h.push(comps[j].clone());
+                count += self.complete(h, rest, j, emit)?;
+                h.pop();
+            }
+        } else {
+            for (j, c) in comps.iter().enumerate().skip(start) {
+                h.push(c.clone());
+                count += self.complete(h, rest, j, emit)?;
+                h.pop();
+            }
+        }
+        Ok(count)
+    }
+
+}
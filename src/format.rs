@@ -0,0 +1,298 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Textual representations of isomorphism codes, other than the
+// internal one (i.e., the Debug output of the codes) and digraph6
+
+
+use std::fmt::{self, Display, Formatter};
+use crate::{Tree, Comp, Func, TreePtr};
+use crate::invariants::FuncInvariants;
+
+
+// Wrapper for displaying isomorphism codes in nested-bracket
+// notation: a tree is written as a pair of square brackets enclosing
+// its immediate subtrees (so a single vertex is []), a component as a
+// pair of parentheses enclosing its trees in the order in which they
+// appear along the cycle, and a functional digraph as the list of its
+// components separated by spaces. For instance, the functional
+// digraph [[[1]], [[3, 1, 1], [1]]] is displayed as ([]) ([[][]] [])
+
+pub struct Pretty<'a, T: ?Sized>(pub &'a T);
+
+
+// Write the subtree of t having root at position i in nested-bracket
+// notation
+
+fn fmt_subtree(t: &[u8], i: usize, f: &mut Formatter) -> fmt::Result {
+    write!(f, "[")?;
+    let mut j = i + 1;
+    while j < i + t[i] as usize {
+        fmt_subtree(t, j, f)?;
+        j += t[j] as usize;
+    }
+    write!(f, "]")
+}
+
+
+impl Display for Pretty<'_, Tree> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        fmt_subtree(self.0, 0, f)
+    }
+}
+
+
+impl<P: TreePtr> Display for Pretty<'_, [P]> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "(")?;
+        for i in 0..self.0.len() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", Pretty(&*self.0[i]))?;
+        }
+        write!(f, ")")
+    }
+}
+
+
+// Components are displayed in the same way whether they use Rc (as
+// Comp) or Arc (as SyncComp)
+
+impl<P: TreePtr> Display for Pretty<'_, Vec<P>> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", Pretty(self.0.as_slice()))
+    }
+}
+
+
+impl Display for Pretty<'_, Func> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for i in 0..self.0.len() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", Pretty(&*self.0[i]))?;
+        }
+        Ok(())
+    }
+}
+
+
+// Wrapper for displaying isomorphism codes in Newick notation: a
+// tree is written as a pair of parentheses enclosing its immediate
+// subtrees separated by commas (so a single vertex is empty, and the
+// root is unlabelled) followed by a semicolon, a component as its
+// trees in the order in which they appear along the cycle, and a
+// functional digraph as the list of its components separated by
+// spaces. For instance, the functional digraph [[[1]], [[3, 1, 1],
+// [1]]] is displayed as ; (,);;
+
+pub struct Newick<'a, T: ?Sized>(pub &'a T);
+
+
+// Write the subtree of t having root at position i in Newick
+// notation (without the final semicolon)
+
+fn fmt_newick_subtree(t: &[u8], i: usize, f: &mut Formatter)
+                      -> fmt::Result {
+    if t[i] == 1 {
+        return Ok(());
+    }
+    write!(f, "(")?;
+    let mut j = i + 1;
+    while j < i + t[i] as usize {
+        if j > i + 1 {
+            write!(f, ",")?;
+        }
+        fmt_newick_subtree(t, j, f)?;
+        j += t[j] as usize;
+    }
+    write!(f, ")")
+}
+
+
+impl Display for Newick<'_, Tree> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        fmt_newick_subtree(self.0, 0, f)?;
+        write!(f, ";")
+    }
+}
+
+
+impl Display for Newick<'_, Comp> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for t in self.0 {
+            write!(f, "{}", Newick(&**t))?;
+        }
+        Ok(())
+    }
+}
+
+
+impl Display for Newick<'_, Func> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for i in 0..self.0.len() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", Newick(&*self.0[i]))?;
+        }
+        Ok(())
+    }
+}
+
+
+// Wrapper for displaying a functional digraph as a permutation of
+// forests: the cycle type of the permutation induced on the periodic
+// points, followed by an equals sign and by the components, each one
+// written as a pair of parentheses enclosing the forests hanging from
+// the vertices along its cycle. A forest is a pair of braces
+// enclosing its trees in nested-bracket notation, separated by
+// commas, with t^m denoting m copies of tree t. For instance, the
+// functional digraph [[[1]], [[3, 1, 1], [1]]] is displayed as
+// 1^1,2^1 = ({}) ({[]^2} {})
+
+pub struct Forests<'a>(pub &'a Func);
+
+
+// Write the forest of the immediate subtrees of tree t
+
+fn fmt_forest(t: &[u8], f: &mut Formatter) -> fmt::Result {
+    write!(f, "{{")?;
+    let mut j = 1;
+    while j < t.len() {
+        let s = &t[j..j + t[j] as usize];
+        let mut m = 1;
+        while j + m * s.len() < t.len()
+            && &t[j + m * s.len()..j + (m + 1) * s.len()] == s {
+            m += 1;
+        }
+        if j > 1 {
+            write!(f, ",")?;
+        }
+        fmt_subtree(s, 0, f)?;
+        if m > 1 {
+            write!(f, "^{m}")?;
+        }
+        j += m * s.len();
+    }
+    write!(f, "}}")
+}
+
+
+impl Display for Forests<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} =", self.0.cycle_type())?;
+        for c in self.0 {
+            write!(f, " (")?;
+            for i in 0..c.len() {
+                if i > 0 {
+                    write!(f, " ")?;
+                }
+                fmt_forest(&c[i], f)?;
+            }
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+
+// Pack the code of functional digraph g into a sequence of bytes:
+// each component is represented by the number of its trees, followed
+// by the codes of the trees themselves, which are self-delimiting
+// since each of them starts with its own size
+
+pub fn pack(g: &Func) -> Vec<u8> {
+    let mut b = Vec::new();
+    for c in g {
+        b.push(c.len() as u8);
+        for t in c.iter() {
+            b.extend_from_slice(t);
+        }
+    }
+    b
+}
+
+
+// Digits of the subtree sizes in the compact notation
+
+pub const COMPACT_DIGITS: &[u8; 62] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+
+// Wrapper for displaying functional digraphs in compact notation,
+// without whitespace: each component is written as a dot followed by
+// the codes of its trees along the cycle, each of them the list of
+// its subtree sizes in preorder (which determines where each tree
+// ends), with each size between 1 and 61 written as a single digit
+// in base 62 (1-9, A-Z, a-z) and the larger ones as _ followed by two
+// digits. For instance, the functional digraph [[[1]], [[3, 1, 1],
+// [1]]] is displayed as .1.3111, and its components are obtained by
+// splitting on the dots
+
+pub struct Compact<'a>(pub &'a Func);
+
+
+impl Display for Compact<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let digit = |d: u8| COMPACT_DIGITS[d as usize] as char;
+        for c in self.0 {
+            write!(f, ".")?;
+            for t in c.iter() {
+                for &s in t.iter() {
+                    if s < 62 {
+                        write!(f, "{}", digit(s))?;
+                    } else {
+                        write!(f, "_{}{}", digit(s / 62), digit(s % 62))?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+
+// Alphabet of the keys, the URL and filename safe variant of base64
+// (RFC 4648, Section 5)
+
+pub const KEY_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+
+// Compute the key of functional digraph g, i.e., its packed code
+// encoded in base64 (without padding); this is about as short as the
+// digraph6 encoding for small digraphs, and much shorter for larger
+// ones
+
+pub fn key(g: &Func) -> String {
+    let b = pack(g);
+    let mut s = String::new();
+    for chunk in b.chunks(3) {
+        let mut x = 0;
+        for i in 0..3 {
+            x = x << 8 | *chunk.get(i).unwrap_or(&0) as u32;
+        }
+        for i in 0..chunk.len() + 1 {
+            s.push(KEY_ALPHABET[(x >> (18 - 6 * i) & 63) as usize] as char);
+        }
+    }
+    s
+}
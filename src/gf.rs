@@ -0,0 +1,188 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Coefficients of the generating functions counting rooted trees,
+// components and functional digraphs up to isomorphism, possibly
+// refined by the number of cycle points; they are computed exactly by
+// the usual recurrences, and None is returned if they do not fit in
+// 128 bits (which happens for sizes above about 80)
+
+
+// Compute Euler's totient function of k
+
+pub fn totient(mut k: usize) -> usize {
+    let mut phi = k;
+    let mut p = 2;
+    while p * p <= k {
+        if k.is_multiple_of(p) {
+            while k.is_multiple_of(p) {
+                k /= p;
+            }
+            phi -= phi / p;
+        }
+        p += 1;
+    }
+    if k > 1 {
+        phi -= phi / k;
+    }
+    phi
+}
+
+
+// Compute a * b + c, if it does not overflow
+
+fn mul_add(a: u128, b: u128, c: u128) -> Option<u128> {
+    a.checked_mul(b)?.checked_add(c)
+}
+
+
+// Compute the number t[m] of rooted trees of m vertices for m <= n
+// (sequence A000081 on the OEIS), using the recurrence t[m + 1] =
+// sum(s[k] * t[m - k + 1] for 1 <= k <= m) / m, where s[k] is the sum
+// of d * t[d] over the divisors d of k
+
+pub fn tree_counts(n: usize) -> Option<Vec<u128>> {
+    let mut t = vec![0; n + 1];
+    let mut s = vec![0; n + 1];
+    if n >= 1 {
+        t[1] = 1;
+    }
+    for m in 1..n {
+        for d in 1..=m {
+            if m.is_multiple_of(d) {
+                s[m] = mul_add(d as u128, t[d], s[m])?;
+            }
+        }
+        let mut sum = 0;
+        for k in 1..=m {
+            sum = mul_add(s[k], t[m - k + 1], sum)?;
+        }
+        t[m + 1] = sum / m as u128;
+    }
+    Some(t)
+}
+
+
+// Compute the number c[m][k] of components of m vertices having a
+// cycle of length k, for k <= m <= n; a component is a cycle (up to
+// rotation) of k rooted trees, so by Burnside's lemma c[m][k] is the
+// sum of totient(d) * q[k / d][m / d] over the common divisors d of k
+// and m, divided by k, where q[j][i] is the number of sequences of j
+// rooted trees with i vertices in total
+
+pub fn comp_counts(n: usize) -> Option<Vec<Vec<u128>>> {
+    let t = tree_counts(n)?;
+    let mut q = vec![vec![0; n + 1]; n + 1];
+    q[0][0] = 1;
+    for j in 1..=n {
+        for i in j..=n {
+            for r in 1..=i - j + 1 {
+                q[j][i] = mul_add(t[r], q[j - 1][i - r], q[j][i])?;
+            }
+        }
+    }
+    let mut c = vec![vec![0; n + 1]; n + 1];
+    for m in 1..=n {
+        for k in 1..=m {
+            let mut sum = 0;
+            for d in 1..=k {
+                if k.is_multiple_of(d) && m.is_multiple_of(d) {
+                    sum = mul_add(totient(d) as u128, q[k / d][m / d], sum)?;
+                }
+            }
+            c[m][k] = sum / k as u128;
+        }
+    }
+    Some(c)
+}
+
+
+// Compute the number f[m][k] of multisets of components of m
+// vertices having k marks in total, for k <= m <= n, where c[i][j] is
+// the number of components of i vertices having j marks (with j <=
+// i); by the logarithmic derivative of the Euler transform, m *
+// f[m][k] is the sum of b[i][j] * f[m - i][k - j] for 1 <= i <= m and
+// j <= i, where b[i][j] is the sum of (i / d) * c[i / d][j / d] over
+// the common divisors d of i and j
+
+fn multiset_counts(n: usize, c: &[Vec<u128>]) -> Option<Vec<Vec<u128>>> {
+    let mut b = vec![vec![0; n + 1]; n + 1];
+    for i in 1..=n {
+        for j in 0..=i {
+            for d in 1..=i {
+                if i.is_multiple_of(d) && j.is_multiple_of(d) {
+                    b[i][j] = mul_add((i / d) as u128, c[i / d][j / d],
+                                      b[i][j])?;
+                }
+            }
+        }
+    }
+    let mut f = vec![vec![0; n + 1]; n + 1];
+    f[0][0] = 1;
+    for m in 1..=n {
+        for k in 0..=m {
+            let mut sum = 0;
+            for i in 1..=m {
+                for j in 0..=i.min(k) {
+                    sum = mul_add(b[i][j], f[m - i][k - j], sum)?;
+                }
+            }
+            f[m][k] = sum / m as u128;
+        }
+    }
+    Some(f)
+}
+
+
+// Compute the number f[m][k] of functional digraphs of m vertices
+// having k cycle points, for k <= m <= n, as the multisets of
+// components with their cycle points as marks
+
+pub fn func_counts(n: usize) -> Option<Vec<Vec<u128>>> {
+    multiset_counts(n, &comp_counts(n)?)
+}
+
+
+// Compute the number f[m][k] of functional digraphs of m vertices
+// having k fixed points, for k <= m <= n, as the multisets of
+// components where those with a fixed point (i.e., a cycle of
+// length 1) have one mark
+
+pub fn fixed_point_counts(n: usize) -> Option<Vec<Vec<u128>>> {
+    let c = comp_counts(n)?;
+    let mut d = vec![vec![0; n + 1]; n + 1];
+    for m in 1..=n {
+        d[m][1] = c[m][1];
+        d[m][0] = c[m][2..].iter().sum();
+    }
+    multiset_counts(n, &d)
+}
+
+
+// Compute the number f[m][k] of functional digraphs of m vertices
+// having k components, for k <= m <= n, as the multisets of
+// components where each component has one mark
+
+pub fn component_number_counts(n: usize) -> Option<Vec<Vec<u128>>> {
+    let c = comp_counts(n)?;
+    let mut d = vec![vec![0; n + 1]; n + 1];
+    for m in 1..=n {
+        d[m][1] = c[m].iter().sum();
+    }
+    multiset_counts(n, &d)
+}
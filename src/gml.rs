@@ -0,0 +1,58 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Output in the Graph Modelling Language (GML), as read by igraph,
+// networkx, Cytoscape and other graph libraries
+
+
+use std::io::{self, Write};
+use crate::Func;
+use crate::digraph6::func_adj;
+
+
+// Write functional digraph g to out as a directed GML graph on a
+// single line, with vertices named as in digraph6 format (without
+// renaming the distinguished vertex v, if any). Each node has the
+// attributes component (the index of its connected component, from
+// 0, in the order of the code) and cycle (1 for periodic vertices,
+// 0 otherwise), as well as distinguished (1 for v, 0 otherwise) if v
+// is given; each edge goes from a vertex to its image
+
+pub fn write_gml(out: &mut dyn Write, g: &Func, v: Option<usize>)
+                 -> io::Result<()> {
+    let a = func_adj(g);
+    write!(out, "graph [ directed 1")?;
+    let mut x = 0;
+    for (i, c) in g.iter().enumerate() {
+        for t in c.iter() {
+            for j in 0..t.len() {
+                write!(out, " node [ id {x} component {i} cycle {}",
+                       (j == 0) as u8)?;
+                if let Some(v) = v {
+                    write!(out, " distinguished {}", (x == v) as u8)?;
+                }
+                write!(out, " ]")?;
+                x += 1;
+            }
+        }
+    }
+    for (x, &y) in a.iter().enumerate() {
+        write!(out, " edge [ source {x} target {y} ]")?;
+    }
+    write!(out, " ]")
+}
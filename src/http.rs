@@ -0,0 +1,215 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// A minimal HTTP/1.1 server side, for the serve subcommand: parsing
+// requests (with the query string and a body of known length) and
+// writing responses, either whole or with chunked transfer encoding
+// for those streamed while they are being computed. Each connection
+// carries a single request, and is closed after the response
+
+
+use std::io::{self, BufRead, Read, Write};
+
+
+// Maximum length of the body of a request, and of its header lines
+
+const MAX_BODY: usize = 16 << 20;
+const MAX_LINE: usize = 8192;
+
+
+// Request with its method, path, decoded query parameters (in their
+// order, possibly repeated) and body
+
+#[derive(Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+
+// Error for a malformed request
+
+fn bad_request(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+
+// Read a line terminated by CRLF (or just LF), without the terminator
+
+fn read_line<R: BufRead>(input: &mut R) -> io::Result<String> {
+    let mut line = Vec::new();
+    input.take(MAX_LINE as u64 + 1).read_until(b'\n', &mut line)?;
+    if line.len() > MAX_LINE {
+        return Err(bad_request("header line too long"));
+    }
+    if line.last() != Some(&b'\n') {
+        return Err(bad_request("unexpected end of request"));
+    }
+    line.pop();
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    String::from_utf8(line).map_err(|_| bad_request("invalid header"))
+}
+
+
+// Decode the percent-encoded string s, where + also stands for a
+// space, as in the query strings of HTML forms
+
+pub fn percent_decode(s: &str) -> io::Result<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        match b {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex: Vec<u8> = iter.by_ref().take(2).collect();
+                let byte = std::str::from_utf8(&hex).ok()
+                    .filter(|h| h.len() == 2)
+                    .and_then(|h| u8::from_str_radix(h, 16).ok())
+                    .ok_or_else(|| bad_request("invalid percent encoding"))?;
+                bytes.push(byte);
+            }
+            b => bytes.push(b),
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| bad_request("invalid query"))
+}
+
+
+// Read a request, whose body is present only if it has a
+// Content-Length header
+
+pub fn read_request<R: BufRead>(input: &mut R) -> io::Result<Request> {
+    let line = read_line(input)?;
+    let mut words = line.split(' ');
+    let (Some(method), Some(target), Some(version), None) =
+        (words.next(), words.next(), words.next(), words.next()) else {
+        return Err(bad_request("invalid request line"));
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Err(bad_request("unsupported HTTP version"));
+    }
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut params = Vec::new();
+    for item in query.split('&').filter(|s| !s.is_empty()) {
+        let (key, value) = item.split_once('=').unwrap_or((item, ""));
+        params.push((percent_decode(key)?, percent_decode(value)?));
+    }
+    let mut length = 0;
+    loop {
+        let line = read_line(input)?;
+        if line.is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(bad_request("invalid header"));
+        };
+        if name.eq_ignore_ascii_case("content-length") {
+            length = value.trim().parse()
+                .map_err(|_| bad_request("invalid Content-Length"))?;
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            return Err(bad_request("unsupported Transfer-Encoding"));
+        }
+    }
+    if length > MAX_BODY {
+        return Err(bad_request("request body too large"));
+    }
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Request {
+        method: method.to_string(),
+        path: percent_decode(path)?,
+        query: params,
+        body,
+    })
+}
+
+
+// Reason phrase of the status codes used by the server
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        500 => "Internal Server Error",
+        _ => "",
+    }
+}
+
+
+// Write the response with the given status, type and body
+
+pub fn write_response(out: &mut dyn Write, status: u16, content_type: &str,
+                      body: &[u8]) -> io::Result<()> {
+    write!(out, "HTTP/1.1 {status} {}\r\nContent-Type: {content_type}\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n",
+           reason(status), body.len())?;
+    out.write_all(body)?;
+    out.flush()
+}
+
+
+// Response with chunked transfer encoding, whose body is written as
+// a chunk for each call of write (so the writer should be buffered);
+// finish writes the last, empty chunk
+
+pub struct Chunked<W: Write> {
+    out: W,
+}
+
+
+impl<W: Write> Chunked<W> {
+
+    // Write the header of a successful response of the given type
+
+    pub fn new(mut out: W, content_type: &str) -> io::Result<Chunked<W>> {
+        write!(out, "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\n\
+                     Transfer-Encoding: chunked\r\n\
+                     Connection: close\r\n\r\n")?;
+        Ok(Chunked { out })
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.write_all(b"0\r\n\r\n")?;
+        self.out.flush()
+    }
+
+}
+
+
+impl<W: Write> Write for Chunked<W> {
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !buf.is_empty() {
+            write!(self.out, "{:x}\r\n", buf.len())?;
+            self.out.write_all(buf)?;
+            self.out.write_all(b"\r\n")?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+}
@@ -0,0 +1,764 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Invariants of trees, components and functional digraphs, computed
+// directly from their isomorphism codes
+
+
+use std::fmt::{self, Display, Formatter};
+use crate::{Tree, Comp, Func, Part, TreePtr, comp_size};
+use crate::pointed::num_automorphisms;
+
+
+// Invariants of a tree, whose code lists the size of each subtree in
+// preorder (so the root is at position 0 and has size t[0])
+
+pub trait TreeInvariants {
+
+    // Length of the longest path from a leaf to the root (0 for a
+    // tree consisting of the root only)
+
+    fn height(&self) -> usize;
+
+    // Number of immediate subtrees of the root
+
+    fn num_children(&self) -> usize;
+
+    // Number of vertices having no children, excluding the root
+
+    fn num_leaves(&self) -> usize;
+
+    // Depth of each vertex (its distance from the root), in preorder
+
+    fn vertex_depths(&self) -> Vec<usize>;
+
+    // Height of the subtree of each vertex, in preorder
+
+    fn vertex_heights(&self) -> Vec<usize>;
+
+}
+
+
+// Compute the height of the subtree of t having root at position i
+
+fn subtree_height(t: &[u8], i: usize) -> usize {
+    let mut h = 0;
+    let mut j = i + 1;
+    while j < i + t[i] as usize {
+        h = h.max(1 + subtree_height(t, j));
+        j += t[j] as usize;
+    }
+    h
+}
+
+
+impl TreeInvariants for Tree {
+
+    fn height(&self) -> usize {
+        subtree_height(self, 0)
+    }
+
+    fn num_children(&self) -> usize {
+        let mut k = 0;
+        let mut j = 1;
+        while j < self.len() {
+            k += 1;
+            j += self[j] as usize;
+        }
+        k
+    }
+
+    fn num_leaves(&self) -> usize {
+        self[1..].iter().filter(|&&s| s == 1).count()
+    }
+
+    fn vertex_depths(&self) -> Vec<usize> {
+        let mut depth = vec![0; self.len()];
+        for i in 0..self.len() {
+            let mut j = i + 1;
+            while j < i + self[i] as usize {
+                depth[j] = depth[i] + 1;
+                j += self[j] as usize;
+            }
+        }
+        depth
+    }
+
+    fn vertex_heights(&self) -> Vec<usize> {
+        let mut height = vec![0; self.len()];
+        for i in (0..self.len()).rev() {
+            let mut j = i + 1;
+            while j < i + self[i] as usize {
+                height[i] = height[i].max(height[j] + 1);
+                j += self[j] as usize;
+            }
+        }
+        height
+    }
+
+}
+
+
+// Invariants of a component, i.e., a cycle with a tree rooted at each
+// of its vertices
+
+pub trait CompInvariants {
+
+    // Number of vertices
+
+    fn size(&self) -> usize;
+
+    // Length of the limit cycle
+
+    fn cycle_length(&self) -> usize;
+
+    // Number of nontrivial trees (i.e., having more than one vertex)
+    // rooted along the cycle
+
+    fn num_trees(&self) -> usize;
+
+    // Heights of the trees, in the order in which they appear along
+    // the cycle
+
+    fn tree_heights(&self) -> Vec<usize>;
+
+    // Number of trivial trees, i.e., of periodic points without
+    // incoming edges from outside the cycle
+
+    fn num_trivial_trees(&self) -> usize;
+
+    // Maximum height of the trees, i.e., the maximum number of steps
+    // needed by a vertex to reach the cycle
+
+    fn height(&self) -> usize;
+
+    // Number of vertices with in-degree 0 (i.e., not in the image of
+    // the function, the garden-of-Eden points)
+
+    fn num_leaves(&self) -> usize;
+
+    // Number of periodic points (i.e., vertices along the cycle);
+    // this is the same as the length of the cycle
+
+    fn num_periodic_points(&self) -> usize;
+
+    // Number of vertices of each in-degree, i.e., element d is the
+    // number of vertices having in-degree d (up to the maximum
+    // in-degree)
+
+    fn indegree_counts(&self) -> Vec<usize>;
+
+    // Depth of the component in the reverse search tree, i.e., the
+    // number of merges needed for obtaining it from a cycle; each
+    // merge gives children to a vertex having none, so this is the
+    // number of vertices having children in the trees
+
+    fn depth(&self) -> usize;
+
+}
+
+
+// Add to counts the in-degrees of the vertices of tree t, rooted
+// along a cycle (so that the root has an additional incoming edge
+// from its predecessor); the in-degree of a vertex of the tree is its
+// number of children
+
+fn add_indegrees(t: &[u8], counts: &mut Vec<usize>) {
+    for i in 0..t.len() {
+        let mut d = if i == 0 { 1 } else { 0 };
+        let mut j = i + 1;
+        while j < i + t[i] as usize {
+            d += 1;
+            j += t[j] as usize;
+        }
+        if d >= counts.len() {
+            counts.resize(d + 1, 0);
+        }
+        counts[d] += 1;
+    }
+}
+
+
+impl<P: TreePtr> CompInvariants for Vec<P> {
+
+    fn size(&self) -> usize {
+        comp_size(self)
+    }
+
+    fn cycle_length(&self) -> usize {
+        self.len()
+    }
+
+    fn num_trees(&self) -> usize {
+        self.iter().filter(|t| t.len() > 1).count()
+    }
+
+    fn tree_heights(&self) -> Vec<usize> {
+        self.iter().map(|t| t.height()).collect()
+    }
+
+    fn num_trivial_trees(&self) -> usize {
+        self.len() - self.num_trees()
+    }
+
+    fn height(&self) -> usize {
+        self.iter().map(|t| t.height()).max().unwrap_or(0)
+    }
+
+    fn num_leaves(&self) -> usize {
+        self.iter().map(|t| t.num_leaves()).sum()
+    }
+
+    fn num_periodic_points(&self) -> usize {
+        self.len()
+    }
+
+    fn indegree_counts(&self) -> Vec<usize> {
+        let mut counts = Vec::new();
+        for t in self {
+            add_indegrees(t, &mut counts);
+        }
+        counts
+    }
+
+    fn depth(&self) -> usize {
+        self.iter().map(|t| t.iter().filter(|&&s| s > 1).count()).sum()
+    }
+
+}
+
+
+// Invariants of a functional digraph, i.e., a list of components
+
+pub trait FuncInvariants {
+
+    // Number of vertices
+
+    fn size(&self) -> usize;
+
+    // Number of (weakly) connected components
+
+    fn num_components(&self) -> usize;
+
+    // Number of periodic points, i.e., the total length of the cycles
+
+    fn num_periodic_points(&self) -> usize;
+
+    // Number of fixed points, i.e., cycles of length 1
+
+    fn num_fixed_points(&self) -> usize;
+
+    // Number of trivial trees along all cycles, i.e., of periodic
+    // points without incoming edges from outside their cycle
+
+    fn num_trivial_trees(&self) -> usize;
+
+    // Maximum height of the trees (0 for the empty digraph)
+
+    fn height(&self) -> usize;
+
+    // Number of vertices with in-degree 0
+
+    fn num_leaves(&self) -> usize;
+
+    // Number of vertices of each in-degree, as for components
+
+    fn indegree_counts(&self) -> Vec<usize>;
+
+    // Total depth of the components in the reverse search tree, i.e.,
+    // the number of merges needed for obtaining the digraph from a
+    // permutation (a digraph consisting of cycles only)
+
+    fn depth(&self) -> usize;
+
+    // Cycle type of the permutation induced by the function f
+    // described by the digraph on its periodic points
+
+    fn cycle_type(&self) -> CycleType;
+
+    // Cycle type of the k-th iterate f^k of the function f described
+    // by the digraph: each cycle of length l of f splits into gcd(l, k)
+    // cycles of length l / gcd(l, k) of f^k, and the trees do not
+    // contain any periodic points of f^k (they only get shorter)
+
+    fn iterate_cycle_type(&self, k: usize) -> CycleType;
+
+    // Preperiod (the number of steps needed for reaching a cycle) and
+    // period (the length of that cycle) of each vertex, with the
+    // vertices numbered as in digraph6 format
+
+    fn vertex_orbits(&self) -> Vec<(usize, usize)>;
+
+    // Characteristic polynomial of the adjacency matrix of the
+    // digraph, which only depends on its cycle type and number of
+    // vertices
+
+    fn char_poly(&self) -> CharPoly;
+
+    // Sizes of the images f(V), f^2(V), ... of the set V of vertices
+    // under the iterates of the function f described by the digraph,
+    // up to the first one consisting of the periodic points only;
+    // f^k(V) contains the periodic points and the other vertices whose
+    // subtree has height at least k, so the sequence has as many items
+    // as the height of the digraph (one for a permutation)
+
+    fn image_sizes(&self) -> Vec<usize>;
+
+}
+
+
+impl FuncInvariants for Func {
+
+    fn size(&self) -> usize {
+        self.iter().map(|c| c.size()).sum()
+    }
+
+    fn num_components(&self) -> usize {
+        self.len()
+    }
+
+    fn num_periodic_points(&self) -> usize {
+        self.iter().map(|c| c.num_periodic_points()).sum()
+    }
+
+    fn num_fixed_points(&self) -> usize {
+        self.iter().filter(|c| c.cycle_length() == 1).count()
+    }
+
+    fn num_trivial_trees(&self) -> usize {
+        self.iter().map(|c| c.num_trivial_trees()).sum()
+    }
+
+    fn height(&self) -> usize {
+        self.iter().map(|c| c.height()).max().unwrap_or(0)
+    }
+
+    fn num_leaves(&self) -> usize {
+        self.iter().map(|c| c.num_leaves()).sum()
+    }
+
+    fn indegree_counts(&self) -> Vec<usize> {
+        let mut counts = Vec::new();
+        for c in self {
+            for t in c.iter() {
+                add_indegrees(t, &mut counts);
+            }
+        }
+        counts
+    }
+
+    fn depth(&self) -> usize {
+        self.iter().map(|c| c.depth()).sum()
+    }
+
+    fn cycle_type(&self) -> CycleType {
+        self.iterate_cycle_type(1)
+    }
+
+    fn iterate_cycle_type(&self, k: usize) -> CycleType {
+        let mut counts = Vec::new();
+        for c in self {
+            let l = c.cycle_length();
+            let d = gcd(l, k);
+            if l / d >= counts.len() {
+                counts.resize(l / d + 1, 0);
+            }
+            counts[l / d] += d;
+        }
+        CycleType(counts)
+    }
+
+    fn vertex_orbits(&self) -> Vec<(usize, usize)> {
+        let mut orbits = Vec::with_capacity(self.size());
+        for c in self {
+            for t in c.iter() {
+                orbits.extend(t.vertex_depths().into_iter()
+                              .map(|d| (d, c.len())));
+            }
+        }
+        orbits
+    }
+
+    fn image_sizes(&self) -> Vec<usize> {
+        let mut sizes = vec![self.num_periodic_points(); self.height().max(1)];
+        for c in self {
+            for t in c.iter() {
+                for h in t.vertex_heights().into_iter().skip(1) {
+                    for s in &mut sizes[..h] {
+                        *s += 1;
+                    }
+                }
+            }
+        }
+        sizes
+    }
+
+    fn char_poly(&self) -> CharPoly {
+        CharPoly {
+            nilpotent: self.size() - self.num_periodic_points(),
+            cycles: self.cycle_type(),
+        }
+    }
+
+}
+
+
+// Greatest common divisor of a and b
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+
+// Cycle type of a function, i.e., the number of cycles of each length
+// (the item of index l being the number of cycles of length l),
+// written as a comma-separated list of l^m for m cycles of length l
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CycleType(pub Vec<usize>);
+
+
+impl Display for CycleType {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut sep = "";
+        for (l, &m) in self.0.iter().enumerate() {
+            if m > 0 {
+                write!(f, "{sep}{l}^{m}")?;
+                sep = ",";
+            }
+        }
+        Ok(())
+    }
+}
+
+
+// Characteristic polynomial det(xI - A) of the adjacency matrix A of
+// a functional digraph: the vertices outside the cycles (nilpotent of
+// them) contribute a factor x each, since no edge leads back to them
+// from the cycles, and each cycle of length l a factor x^l - 1, so
+// that the eigenvalues are 0 and the l-th roots of unity for each
+// cycle length l. The polynomial is written in factored form without
+// spaces, e.g., x^2*(x-1)*(x^3-1)^2 (or 1 for the empty digraph)
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CharPoly {
+    pub nilpotent: usize,
+    pub cycles: CycleType,
+}
+
+
+impl Display for CharPoly {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut factors = Vec::new();
+        match self.nilpotent {
+            0 => (),
+            1 => factors.push("x".to_string()),
+            k => factors.push(format!("x^{k}")),
+        }
+        for (l, &m) in self.cycles.0.iter().enumerate() {
+            let factor = match l {
+                _ if m == 0 => continue,
+                1 => "(x-1)".to_string(),
+                _ => format!("(x^{l}-1)"),
+            };
+            factors.push(if m == 1 {
+                factor
+            } else {
+                format!("{factor}^{m}")
+            });
+        }
+        if factors.is_empty() {
+            return write!(f, "1");
+        }
+        write!(f, "{}", factors.join("*"))
+    }
+}
+
+
+// Value of an invariant of a functional digraph
+
+pub type Value = u128;
+
+
+// Value of the invariants which do not fit into a Value, which can
+// only be the number of automorphisms, for digraphs with more than 34
+// vertices (since it is at most n!); it is never the exact number of
+// automorphisms of a digraph, whose prime factors are at most its
+// number of vertices, unlike those of 2^128 - 1
+
+pub const OVERFLOW: Value = Value::MAX;
+
+
+// Value of an invariant as written in the outputs, i.e., as a number,
+// or as "overflow" for OVERFLOW
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Shown(pub Value);
+
+
+impl Display for Shown {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if self.0 == OVERFLOW {
+            f.pad("overflow")
+        } else {
+            Display::fmt(&self.0, f)
+        }
+    }
+}
+
+
+// Parse a value of an invariant as written by Shown
+
+pub fn parse_value(s: &str) -> Option<Value> {
+    match s {
+        "overflow" => Some(OVERFLOW),
+        _ => s.parse().ok(),
+    }
+}
+
+
+// Integer invariants of functional digraphs which can be selected by
+// name, e.g., for grouping digraphs according to their values, for
+// the columns of tabular outputs or for requiring a given value; each
+// invariant is a unit structure implementing this trait, and adding
+// it to INVARIANTS makes it available to all these features
+
+pub trait Invariant: Sync {
+
+    // Name of the invariant, as used on the command line
+
+    fn name(&self) -> &'static str;
+
+    // Other names accepted for the invariant
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    // Compute the value of the invariant for functional digraph g
+
+    fn compute(&self, g: &Func) -> Value;
+
+    // Check if the values of the invariant never exceed the number of
+    // vertices, so that the digraphs can be grouped by value with a
+    // generation pass for each possible value
+
+    fn is_bounded(&self) -> bool {
+        true
+    }
+
+    // Check if the invariant is expensive to compute, so that it is
+    // only included in the rows of tabular outputs on request
+
+    fn is_expensive(&self) -> bool {
+        false
+    }
+
+    // Check if component c, as well as all components obtained from
+    // it by merging, cannot appear in any digraph where the invariant
+    // has value v, for pruning the search (see Filter::prune_comp)
+
+    fn prune_comp(&self, _c: &Comp, _v: Value) -> bool {
+        false
+    }
+
+    // Check if digraphs whose components have the sizes in partition
+    // p can have value v, for skipping the other partitions
+
+    fn accept_part(&self, _p: &Part, _v: Value) -> bool {
+        true
+    }
+
+}
+
+
+impl fmt::Debug for dyn Invariant + '_ {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+
+impl Display for dyn Invariant + '_ {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+
+// Number of periodic points, i.e., the total length of the cycles of
+// a digraph with several components (not the length of each cycle,
+// as for --max-cycle-length), formerly called cycle-length
+
+pub struct PeriodicPoints;
+
+impl Invariant for PeriodicPoints {
+
+    fn name(&self) -> &'static str {
+        "periodic-points"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["cycle-length"]
+    }
+
+    fn compute(&self, g: &Func) -> Value {
+        g.num_periodic_points() as Value
+    }
+
+}
+
+
+// Merging trees never decreases their height
+
+pub struct Height;
+
+impl Invariant for Height {
+
+    fn name(&self) -> &'static str {
+        "height"
+    }
+
+    fn compute(&self, g: &Func) -> Value {
+        g.height() as Value
+    }
+
+    fn prune_comp(&self, c: &Comp, v: Value) -> bool {
+        c.height() as Value > v
+    }
+
+}
+
+
+// The leaves are the points outside the image of the function, called
+// garden-of-Eden points (GEP) in the study of cellular automata;
+// merging trees never decreases their number
+
+pub struct Leaves;
+
+impl Invariant for Leaves {
+
+    fn name(&self) -> &'static str {
+        "leaves"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["gep", "garden-of-eden"]
+    }
+
+    fn compute(&self, g: &Func) -> Value {
+        g.num_leaves() as Value
+    }
+
+    fn prune_comp(&self, c: &Comp, v: Value) -> bool {
+        c.num_leaves() as Value > v
+    }
+
+}
+
+
+// The number of components is the number of parts of the partition
+// of the sizes of the components
+
+pub struct Components;
+
+impl Invariant for Components {
+
+    fn name(&self) -> &'static str {
+        "components"
+    }
+
+    fn compute(&self, g: &Func) -> Value {
+        g.num_components() as Value
+    }
+
+    fn accept_part(&self, p: &Part, v: Value) -> bool {
+        p.len() as Value == v
+    }
+
+}
+
+
+pub struct FixedPoints;
+
+impl Invariant for FixedPoints {
+
+    fn name(&self) -> &'static str {
+        "fixed-points"
+    }
+
+    fn compute(&self, g: &Func) -> Value {
+        g.num_fixed_points() as Value
+    }
+
+}
+
+
+// The number of automorphisms is OVERFLOW when it does not fit into a
+// Value, which can only happen with more than 34 vertices
+
+pub struct Automorphisms;
+
+impl Invariant for Automorphisms {
+
+    fn name(&self) -> &'static str {
+        "aut"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["automorphisms"]
+    }
+
+    fn compute(&self, g: &Func) -> Value {
+        num_automorphisms(g).unwrap_or(OVERFLOW)
+    }
+
+    fn is_bounded(&self) -> bool {
+        false
+    }
+
+    fn is_expensive(&self) -> bool {
+        true
+    }
+
+}
+
+
+// Registry of the invariants which can be selected by name
+
+pub static INVARIANTS: [&dyn Invariant; 6] = [
+    &PeriodicPoints, &Height, &Leaves, &Components, &FixedPoints,
+    &Automorphisms,
+];
+
+
+// Find the invariant with the given name (or alias) in INVARIANTS
+
+pub fn find_invariant(s: &str) -> Result<&'static dyn Invariant, String> {
+    INVARIANTS.iter().copied()
+        .find(|inv| inv.name() == s || inv.aliases().contains(&s))
+        .ok_or_else(|| format!("unknown invariant '{s}'"))
+}
+
+
+// Invariants included by default in the rows of tabular outputs,
+// i.e., those which are not expensive to compute
+
+pub fn default_invariants() -> Vec<&'static dyn Invariant> {
+    INVARIANTS.iter().copied().filter(|inv| !inv.is_expensive()).collect()
+}
@@ -0,0 +1,168 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Isomorphism test for labelled functional digraphs, independent of
+// the isomorphism codes (e.g., for checking the generated digraphs)
+
+
+use std::collections::BTreeMap;
+use std::mem;
+
+
+// Refine the vertex colours cf of map f and cg of map g (jointly, so
+// that equal colours in the two maps have the same meaning) until the
+// colour of each vertex determines the colour of its image and the
+// multiset of colours of its preimages; isomorphisms must preserve
+// the refined colours
+
+fn refine(f: &[usize], cf: &mut Vec<usize>, g: &[usize], cg: &mut Vec<usize>) {
+    let mut classes = 0;
+    loop {
+        let mut sigs = Vec::new();
+        for (h, c) in [(f, &*cf), (g, &*cg)] {
+            let mut pre = vec![Vec::new(); h.len()];
+            for (v, &w) in h.iter().enumerate() {
+                pre[w].push(c[v]);
+            }
+            for (v, p) in pre.iter_mut().enumerate() {
+                p.sort();
+                sigs.push((c[v], c[h[v]], mem::take(p)));
+            }
+        }
+        let mut names = BTreeMap::new();
+        for s in &sigs {
+            let k = names.len();
+            names.entry(s).or_insert(k);
+        }
+        let (sf, sg) = sigs.split_at(f.len());
+        *cf = sf.iter().map(|s| names[s]).collect();
+        *cg = sg.iter().map(|s| names[s]).collect();
+        if names.len() == classes {
+            break;
+        }
+        classes = names.len();
+    }
+}
+
+
+// Partial bijection between the vertices of two maps, with the list
+// of the vertices mapped so far (for undoing assignments)
+
+struct Matching<'a> {
+    f: &'a [usize],
+    g: &'a [usize],
+    cf: Vec<usize>,
+    cg: Vec<usize>,
+    phi: Vec<Option<usize>>,
+    psi: Vec<Option<usize>>,
+    trail: Vec<usize>,
+}
+
+
+impl Matching<'_> {
+
+    // Map v to w, and then necessarily f(v) to g(w), and so on, until
+    // a vertex already mapped is reached; return false if this is not
+    // consistent with the current mapping or with the colours
+
+    fn assign(&mut self, mut v: usize, mut w: usize) -> bool {
+        loop {
+            if self.phi[v] == Some(w) {
+                return true;
+            }
+            if self.phi[v].is_some() || self.psi[w].is_some()
+                || self.cf[v] != self.cg[w] {
+                return false;
+            }
+            self.phi[v] = Some(w);
+            self.psi[w] = Some(v);
+            self.trail.push(v);
+            v = self.f[v];
+            w = self.g[w];
+        }
+    }
+
+    fn undo(&mut self, len: usize) {
+        while self.trail.len() > len {
+            let v = self.trail.pop().unwrap();
+            let w = self.phi[v].take().unwrap();
+            self.psi[w] = None;
+        }
+    }
+
+    // Extend the mapping to all vertices from v onwards, trying every
+    // vertex of the same colour for the first unmapped one
+
+    fn extend(&mut self, v: usize) -> bool {
+        let Some(v) = (v..self.f.len()).find(|&u| self.phi[u].is_none())
+        else {
+            return true;
+        };
+        for w in 0..self.g.len() {
+            if self.psi[w].is_none() && self.cf[v] == self.cg[w] {
+                let len = self.trail.len();
+                if self.assign(v, w) && self.extend(v + 1) {
+                    return true;
+                }
+                self.undo(len);
+            }
+        }
+        false
+    }
+
+}
+
+
+// Find an isomorphism phi between the functional digraphs of maps f
+// and g (i.e., a bijection with phi[f[v]] = g[phi[v]] for all v) that
+// also maps each vertex v of colour cf[v] to a vertex of colour
+// cg[phi[v]] = cf[v], if any; the search backtracks over the choices
+// allowed by the refined colours, which is fast for the digraphs
+// produced by funkdigen2, although exponential in the worst case
+
+pub fn find_colored_isomorphism(f: &[usize], cf: &[usize],
+                                g: &[usize], cg: &[usize])
+                                -> Option<Vec<usize>> {
+    if f.len() != g.len() {
+        return None;
+    }
+    let (mut cf, mut cg) = (cf.to_vec(), cg.to_vec());
+    refine(f, &mut cf, g, &mut cg);
+    let (mut hf, mut hg) = (cf.clone(), cg.clone());
+    hf.sort();
+    hg.sort();
+    if hf != hg {
+        return None;
+    }
+    let n = f.len();
+    let mut m = Matching {
+        f, g, cf, cg,
+        phi: vec![None; n],
+        psi: vec![None; n],
+        trail: Vec::new(),
+    };
+    m.extend(0).then(|| m.phi.iter().map(|w| w.unwrap()).collect())
+}
+
+
+// Find an isomorphism between the functional digraphs of maps f and
+// g, if any
+
+pub fn find_isomorphism(f: &[usize], g: &[usize]) -> Option<Vec<usize>> {
+    find_colored_isomorphism(f, &vec![0; f.len()], g, &vec![0; g.len()])
+}
@@ -0,0 +1,979 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// A generator of functional digraphs up to isomorphism. Based on
+// Antonio E. Porreca, Ekaterina Timofeeva, "Polynomial-delay
+// generation of functional digraphs up to isomorphism",
+// arXiv:2302.13832, 2023, https://doi.org/10.48550/arXiv.2302.13832
+// and on the original funkdigen, a proof-of-concept Python
+// implementation of the same algorithms, available at
+// https://github.com/aeporreca/funkdigen
+
+
+#![allow(clippy::needless_range_loop)]
+
+
+pub mod augment;
+pub mod boltzmann;
+pub mod cache;
+pub mod canon;
+pub mod code;
+pub mod digraph6;
+pub mod direct;
+pub mod dot;
+pub mod embed;
+pub mod error;
+pub mod extend;
+pub mod filter;
+pub mod format;
+pub mod gf;
+pub mod gml;
+pub mod http;
+pub mod invariants;
+pub mod iso;
+pub mod moments;
+pub mod mtx;
+pub mod orbit;
+pub mod order;
+pub mod packed;
+#[cfg(feature = "arrow")]
+pub mod parquet;
+pub mod parse;
+pub mod pointed;
+pub mod random;
+pub mod report;
+pub mod sha256;
+pub mod sqlite;
+pub mod state;
+pub mod svg;
+
+
+use std::fmt;
+use std::ops::Deref;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::cmp::Ordering::{self, Less, Equal, Greater};
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
+use crate::error::{Error, MAX_VERTICES};
+use crate::format::Pretty;
+use crate::packed::{PACKED_EXACT_LEN, packed_prefix};
+
+
+// Types for isomorphism codes for trees, components and functional
+// digraphs; Comp and Func can share substructure, which reduces the
+// number of memory allocations
+
+pub type Tree = Vec<u8>;
+pub type Comp = Vec<Rc<Tree>>;
+pub type Func = Vec<Rc<Comp>>;
+
+
+// Variants of Comp and Func using Arc instead of Rc, which are Send
+// and Sync, so that they can be shared between threads (at the cost
+// of atomic reference counting)
+
+pub type SyncComp = Vec<Arc<Tree>>;
+pub type SyncFunc = Vec<Arc<SyncComp>>;
+
+
+// Shared pointers to trees, i.e., Rc<Tree> (for Comp) or Arc<Tree>
+// (for SyncComp); the generation of the components is generic over
+// this choice, and a component using pointer type P is a Vec<P>
+
+pub trait TreePtr: Clone + Eq + Deref<Target = Tree> + From<Tree> {
+
+    // Check if a and b point to the same tree
+
+    fn ptr_eq(a: &Self, b: &Self) -> bool;
+
+}
+
+
+impl TreePtr for Rc<Tree> {
+    fn ptr_eq(a: &Self, b: &Self) -> bool {
+        Rc::ptr_eq(a, b)
+    }
+}
+
+
+impl TreePtr for Arc<Tree> {
+    fn ptr_eq(a: &Self, b: &Self) -> bool {
+        Arc::ptr_eq(a, b)
+    }
+}
+
+
+// Convert functional digraph g into a SyncFunc, and back; the
+// components and trees shared within g are still shared afterwards
+// if they are adjacent (as the copies of the same component and the
+// trivial trees of a cycle usually are)
+
+pub fn to_sync(g: &Func) -> SyncFunc {
+    convert_func(g)
+}
+
+pub fn from_sync(g: &SyncFunc) -> Func {
+    convert_func(g)
+}
+
+
+// Convert functional digraph g between pointer types, sharing the
+// adjacent copies of the same component or tree
+
+fn convert_func<P, Q, C, D>(g: &[C]) -> Vec<D>
+where P: TreePtr, Q: TreePtr, C: Deref<Target = Vec<P>>,
+      D: Clone + From<Vec<Q>> {
+    let mut h: Vec<D> = Vec::with_capacity(g.len());
+    for i in 0..g.len() {
+        if i > 0 && *g[i] == *g[i - 1] {
+            h.push(h[i - 1].clone());
+            continue;
+        }
+        let mut c: Vec<Q> = Vec::with_capacity(g[i].len());
+        for j in 0..g[i].len() {
+            if j > 0 && P::ptr_eq(&g[i][j], &g[i][j - 1]) {
+                c.push(c[j - 1].clone());
+            } else {
+                c.push(Q::from((*g[i][j]).clone()));
+            }
+        }
+        h.push(D::from(c));
+    }
+    h
+}
+
+
+// Type for partitions of an integer
+
+pub type Part = Vec<u8>;
+
+
+// Type for the callbacks receiving each generated digraph; an error
+// returned by the callback stops the generation
+
+pub type Emit<'a, E> = dyn FnMut(&Func) -> Result<(), E> + 'a;
+
+
+// Whether to use Booth's LCS algorithm (instead of the naive one) for
+// checking minimal rotations; both give the same results, so this is
+// a global setting affecting performance only
+
+static USE_LCS: AtomicBool = AtomicBool::new(false);
+
+
+// Choose the algorithm used for checking minimal rotations
+
+pub fn set_lcs(lcs: bool) {
+    USE_LCS.store(lcs, Relaxed);
+}
+
+
+// Whether to print (on stderr) each step of the search, i.e., the
+// merges tried, why they are rejected, and the backtracking steps
+
+static TRACE: AtomicBool = AtomicBool::new(false);
+
+
+// Enable or disable tracing of the search
+
+pub fn set_trace(trace: bool) {
+    TRACE.store(trace, Relaxed);
+}
+
+
+// Check if tracing is enabled
+
+#[inline]
+fn tracing() -> bool {
+    TRACE.load(Relaxed)
+}
+
+
+// Print a step of the search; this is kept out of line, so that the
+// search itself is not slowed down when tracing is disabled
+
+#[cold]
+#[inline(never)]
+fn trace(msg: fmt::Arguments) {
+    eprintln!("trace: {msg}");
+}
+
+
+// Check if slice s is sorted nondecreasingly according to the
+// comparison function cmp
+
+fn is_sorted_by<T>(s: &[T], cmp: fn(&T, &T) -> Ordering) -> bool {
+    for i in 0..s.len() - 1 {
+        if cmp(&s[i], &s[i + 1]) == Greater {
+            return false;
+        }
+    }
+    true
+}
+
+
+// Compare trees a and b lexicographically. Trees are often shared
+// (e.g., the trivial trees of a cycle), in which case the comparison
+// is immediate; otherwise the codes are compared in 64-bit chunks
+// (interpreted as big-endian integers, which preserves the
+// lexicographic order), falling back to bytes for the last ones
+
+fn cmp_trees<P: TreePtr>(a: &P, b: &P) -> Ordering {
+    if P::ptr_eq(a, b) {
+        return Equal;
+    }
+    let n = a.len().min(b.len());
+    let mut i = 0;
+    while i + 8 <= n {
+        let x = u64::from_be_bytes(a[i..i + 8].try_into().unwrap());
+        let y = u64::from_be_bytes(b[i..i + 8].try_into().unwrap());
+        if x != y {
+            return x.cmp(&y);
+        }
+        i += 8;
+    }
+    a[i..].cmp(&b[i..])
+}
+
+
+// Check if slice s is its own minimal rotation. This is a naive
+// algorithm which increases the theoretical runtime from O(n^3) but,
+// for slices of lengths corresponding to practical digraph sizes, it
+// seems to be more efficient in practice for n <= 255.
+
+pub fn naive_is_min_rotation<T: Ord>(s: &[T]) -> bool {
+    naive_is_min_rotation_by(s, T::cmp)
+}
+
+
+// Same as naive_is_min_rotation, but using the comparison function cmp
+
+pub fn naive_is_min_rotation_by<T>(s: &[T], cmp: fn(&T, &T) -> Ordering)
+                                   -> bool {
+    naive_is_min_rotation_of(s, cmp)
+}
+
+
+// Sequences which can be checked for minimal rotations: slices, and
+// the candidates of the function merge below
+
+trait Seq<T> {
+    fn len(&self) -> usize;
+    fn at(&self, i: usize) -> &T;
+}
+
+
+impl<T> Seq<T> for [T] {
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn at(&self, i: usize) -> &T {
+        &self[i]
+    }
+}
+
+
+// Same as naive_is_min_rotation_by, for any sequence
+
+fn naive_is_min_rotation_of<T, S: Seq<T> + ?Sized>(s: &S,
+                                                   cmp: fn(&T, &T)
+                                                           -> Ordering)
+                                                   -> bool {
+    for r in 1..s.len() {
+        for i in 0..s.len() {
+            match cmp(s.at(i), s.at((i + r) % s.len())) {
+                Greater => return false,
+                Less => break,
+                Equal => (),
+            }
+        }
+    }
+    true
+}
+
+
+// Check if slice s is its own minimal rotation. This is based on
+// the linear-time algorithms such as Kellogg S. Booth's LCS
+// (described in "Lexicographically least circular substrings",
+// Information Processing Letters 10(4), 1980, pages 240-242,
+// https://doi.org/10.1016/0020-0190(80)90149-0 and in the errata at
+// https://www.cs.ubc.ca/~ksbooth/PUB/LCS.shtml) which we used in the
+// paper in order to obtain the theoretical upper bound, but it is
+// empirically slower that the naive algorithm for n <= 255.
+
+pub fn lcs_is_min_rotation<T: Ord>(s: &[T]) -> bool {
+    lcs_is_min_rotation_by(s, T::cmp)
+}
+
+
+// Same as lcs_is_min_rotation, but using the comparison function cmp
+
+pub fn lcs_is_min_rotation_by<T: Eq>(s: &[T], cmp: fn(&T, &T) -> Ordering)
+                                     -> bool {
+    lcs_is_min_rotation_of(s, cmp)
+}
+
+
+// Same as lcs_is_min_rotation_by, for any sequence
+
+fn lcs_is_min_rotation_of<T: Eq, S: Seq<T> + ?Sized>(s: &S,
+                                                     cmp: fn(&T, &T)
+                                                             -> Ordering)
+                                                     -> bool {
+    let n = s.len();
+    let mut f = vec![-1; 2 * n];
+    let mut k = 0;
+    for j in 1..2 * n {
+        let mut i = f[j - k - 1];
+        while i != -1
+            && cmp(s.at(j % n), s.at((k + i as usize + 1) % n)) != Equal {
+            if cmp(s.at(j % n), s.at((k + i as usize + 1) % n)) == Less {
+                k = j - i as usize - 1;
+            }
+            i = f[i as usize];
+        }
+        if i == -1
+            && cmp(s.at(j % n), s.at((k + i as usize + 1) % n)) != Equal {
+            if cmp(s.at(j % n), s.at((k + i as usize + 1) % n)) == Less {
+                k = j;
+            }
+            f[j - k] = -1;
+        } else {
+            f[j - k] = i + 1;
+        }
+    }
+    (0..n).all(|i| s.at((k + i) % n) == s.at(i))
+}
+
+
+// Check if component c is its own minimal rotation, as in the naive
+// algorithm, but comparing the fingerprints of the trees first (their
+// packed prefixes): these are computed once and kept in a contiguous
+// array, so most comparisons do not need to follow the pointers to
+// the trees, which are only compared in full when their fingerprints
+// are equal and they have more than PACKED_EXACT_LEN vertices. This
+// is several times faster for long cycles with many equal but
+// separately allocated trees, where the naive algorithm compares the
+// same trees over and over
+
+fn fingerprint_is_min_rotation<P: TreePtr, S: Seq<P> + ?Sized>(c: &S)
+                                                                -> bool {
+    let k = c.len();
+    let mut fp = [0; 256];
+    for i in 0..k {
+        fp[i] = packed_prefix(c.at(i));
+    }
+    for r in 1..k {
+        let mut j = r;
+        for i in 0..k {
+            let ord = match fp[i].cmp(&fp[j]) {
+                Equal if c.at(i).len() > PACKED_EXACT_LEN
+                    || c.at(j).len() > PACKED_EXACT_LEN => {
+                    cmp_trees(c.at(i), c.at(j))
+                }
+                ord => ord,
+            };
+            match ord {
+                Greater => return false,
+                Less => break,
+                Equal => (),
+            }
+            j = if j + 1 == k { 0 } else { j + 1 };
+        }
+    }
+    true
+}
+
+
+// Minimum length of the cycles checked with the fingerprints: for
+// the shorter ones (i.e., most of them) computing the fingerprints
+// does not pay off
+
+const FINGERPRINT_MIN_LEN: usize = 9;
+
+
+// Check if component c is its own minimal rotation, using the
+// algorithm chosen with set_lcs
+
+fn is_min_rotation<P: TreePtr, S: Seq<P> + ?Sized>(c: &S) -> bool {
+    if USE_LCS.load(Relaxed) {
+        lcs_is_min_rotation_of(c, cmp_trees)
+    } else if c.len() >= FINGERPRINT_MIN_LEN {
+        fingerprint_is_min_rotation(c)
+    } else {
+        naive_is_min_rotation_of(c, cmp_trees)
+    }
+}
+
+
+// Compute the unmerge u of component c and the indices l, r
+// such that remerging u between l and r gives back c
+
+pub(crate) fn unmerge<P: TreePtr>(c: &[P]) -> Option<(Vec<P>, usize, usize)> {
+    let mut u = Vec::new();
+    let mut l = 0;
+    while l < c.len() && c[l].len() == 1 {
+        u.push(c[l].clone());
+        l += 1;
+    }
+    if l == c.len() {
+        return None;
+    }
+    u.push(P::from(vec![1]));
+    let t = &c[l];
+    let mut i = 1;
+    let mut r = l + 1;
+    while i < t.len() {
+        u.push(P::from(t[i..i + t[i] as usize].to_vec()));
+        i += t[i] as usize;
+        r += 1;
+    }
+    for i in l + 1..c.len() {
+        u.push(c[i].clone());
+    }
+    Some((u, l, r))
+}
+
+
+
+// Check if component c has unmerge u (this is not a general purpose
+// function, it only works in the context of the function merge below)
+
+fn has_unmerge<P: TreePtr, S: Seq<P> + ?Sized>(c: &S, u: &[P]) -> bool {
+    let mut i = 0;
+    while i < c.len() && c.at(i).len() == 1 {
+        i += 1;
+    }
+    u[i][0] == 1
+}
+
+
+// Candidate merge of trees c[l], ..., c[r - 1] of a component into
+// tree t, i.e., the sequence c[0], ..., c[l - 1], t, c[r], ..., c[k - 1],
+// which is represented by the segments of c surrounding t instead of
+// being copied, since most candidates are rejected
+
+struct Candidate<'a, P> {
+    c: &'a [P],
+    l: usize,
+    r: usize,
+    t: P,
+}
+
+
+impl<P: TreePtr> Candidate<'_, P> {
+    fn to_vec(&self) -> Vec<P> {
+        let mut m = Vec::with_capacity(Seq::len(self));
+        m.extend_from_slice(&self.c[..self.l]);
+        m.push(self.t.clone());
+        m.extend_from_slice(&self.c[self.r..]);
+        m
+    }
+}
+
+
+impl<P> Seq<P> for Candidate<'_, P> {
+    fn len(&self) -> usize {
+        self.c.len() - (self.r - self.l) + 1
+    }
+
+    fn at(&self, i: usize) -> &P {
+        if i < self.l {
+            &self.c[i]
+        } else if i == self.l {
+            &self.t
+        } else {
+            &self.c[i + self.r - self.l - 1]
+        }
+    }
+}
+
+
+// Merge trees c[l], ..., c[r - 1] if that gives a valid isomorphism
+// code for a component; c[l] must be trivial and the trees must be
+// sorted, which is checked by next_merge before calling this. The
+// merged component is only built if it is valid
+
+fn merge<P: TreePtr>(c: &[P], l: usize, r: usize) -> Option<Vec<P>> {
+    let mut t = vec![1];
+    for i in l + 1..r {
+        t.extend_from_slice(&c[i]);
+        t[0] += c[i].len() as u8;
+    }
+    let m = Candidate { c, l, r, t: P::from(t) };
+    if !is_min_rotation(&m) {
+        if tracing() {
+            trace(format_args!("  merge {l}, {r}: {} rejected, \
+                                not a minimal rotation",
+                               Pretty(&m.to_vec())));
+        }
+        return None;
+    }
+    if !has_unmerge(&m, c) {
+        if tracing() {
+            trace(format_args!("  merge {l}, {r}: {} rejected, its \
+                                parent is a different component",
+                               Pretty(&m.to_vec())));
+        }
+        return None;
+    }
+    let m = m.to_vec();
+    if tracing() {
+        trace(format_args!("  merge {l}, {r}: {} accepted", Pretty(&m)));
+    }
+    Some(m)
+}
+
+
+// Compute the next valid merge of c, if any, starting from l
+// (decreasing) and up to and exluding r (increasing); this
+// corresponds to the lexicographically minimal merge. Candidates
+// which cannot be valid are skipped without building them: c[l] must
+// be trivial, and c[l], ..., c[r - 1] must be sorted, which is
+// checked incrementally as r grows, and if they are not then they
+// are not sorted for any larger r either (r is always at least l + 2)
+
+fn next_merge<P: TreePtr>(c: &[P], l: usize, r: usize) -> Option<Vec<P>> {
+    next_merge_at(c, l, r).map(|(m, _, _)| m)
+}
+
+
+// Same as next_merge, but also return the indices l, r of the merge
+// giving the result
+
+fn next_merge_at<P: TreePtr>(c: &[P], mut l: usize, mut r: usize)
+                             -> Option<(Vec<P>, usize, usize)> {
+    if c[c.len() - 1].len() == 1 && !tracing() {
+        return next_cycle_merge(c, l, r);
+    }
+    loop {
+        if c[l].len() == 1 && r <= c.len()
+            && is_sorted_by(&c[l..r - 1], cmp_trees) {
+            while r <= c.len() && cmp_trees(&c[r - 2], &c[r - 1]) != Greater {
+                if let Some(m) = merge(c, l, r) {
+                    return Some((m, l, r));
+                }
+                r += 1;
+            }
+            if tracing() && r <= c.len() {
+                trace(format_args!("  merge {l}, {r}: skipped, \
+                                    trees not sorted"));
+            }
+        } else if tracing() {
+            if c[l].len() > 1 {
+                trace(format_args!("  merge {l}, {r}: skipped, \
+                                    tree {l} not trivial"));
+            } else if r <= c.len() {
+                trace(format_args!("  merge {l}, {r}: skipped, \
+                                    trees not sorted"));
+            }
+        }
+        if l == 0 {
+            return None;
+        }
+        l -= 1;
+        r = l + 2;
+    }
+}
+
+
+// Same as next_merge_at, when c is a cycle, i.e., all of its trees
+// are trivial (for a minimal rotation, this holds if and only if its
+// last tree is trivial). Then the trees are always sorted, and the
+// merge of c[l], ..., c[r - 1] is valid if and only if r is the
+// length of c, since the only nontrivial tree of a minimal rotation
+// must come last; thus the valid merges are built directly, for
+// decreasing l, without checking any rotation. This is where every
+// search starts, and also where it backtracks to after each subtree
+// of the cycle
+
+fn next_cycle_merge<P: TreePtr>(c: &[P], mut l: usize, r: usize)
+                                -> Option<(Vec<P>, usize, usize)> {
+    let k = c.len();
+    if r > k {
+        if l == 0 {
+            return None;
+        }
+        l -= 1;
+    }
+    let mut m = c[..l].to_vec();
+    let mut t = vec![1; k - l];
+    t[0] = (k - l) as u8;
+    m.push(P::from(t));
+    Some((m, l, k))
+}
+
+
+// Compute the next component by merging c, if possible, and otherwise
+// by unmerging and remerging, if possible
+
+pub fn next_comp<P: TreePtr>(c: &[P]) -> Option<Vec<P>> {
+    if tracing() {
+        trace(format_args!("visit {}", Pretty(c)));
+    }
+    if c.len() >= 2 {
+        if let Some(m) = next_merge(c, c.len() - 2, c.len()) {
+            return Some(m);
+        }
+    }
+    skip_comp(c)
+}
+
+
+// Compute the next component by unmerging and remerging c, if
+// possible, i.e., the next component which is not obtained from c
+// by merging (one of its descendants in the reverse search tree);
+// this makes it possible to skip whole subtrees of the search
+
+pub fn skip_comp<P: TreePtr>(c: &[P]) -> Option<Vec<P>> {
+    let mut res = unmerge(c);
+    // When called from next_comp this loop is actually executed
+    // at most twice, see Lemma 15 of the paper
+    while let Some((u, l, r)) = res {
+        if tracing() {
+            trace(format_args!("backtrack to {}", Pretty(&u)));
+        }
+        if let Some(m) = next_merge(&u, l, r + 1) {
+            return Some(m);
+        }
+        res = unmerge(&u);
+    }
+    None
+}
+
+
+// A depth-first walk of the reverse search tree of the components
+// of n vertices, in the same order as repeated calls to next_comp.
+// The path from the root (the cycle) to the current component is
+// kept on an explicit stack, whose entries are the ancestors of the
+// current component with the indices l, r of the merge leading to
+// the next component on the path; backtracking then only pops an
+// entry, instead of recomputing the unmerge of the component (which
+// allocates its subtrees again), and descending moves the component
+// onto the stack without copying it
+
+pub struct CompWalk<P: TreePtr> {
+    comp: Vec<P>,
+    stack: Vec<(Vec<P>, usize, usize)>,
+}
+
+
+impl<P: TreePtr> CompWalk<P> {
+    // Start the walk from the cycle of length n, which must be
+    // positive
+
+    pub fn new(n: usize) -> Self {
+        CompWalk { comp: cycle_of(n), stack: Vec::new() }
+    }
+
+    // Resume the walk at component c, which must be canonical (e.g.,
+    // a component obtained by a previous walk); its path from the
+    // cycle is rebuilt by unmerging it repeatedly, as in skip_comp
+
+    pub fn from_comp(c: Vec<P>) -> Self {
+        let mut stack = Vec::new();
+        let mut res = unmerge(&c);
+        while let Some((u, l, r)) = res {
+            res = unmerge(&u);
+            stack.push((u, l, r));
+        }
+        stack.reverse();
+        CompWalk { comp: c, stack }
+    }
+
+    // The current component
+
+    pub fn comp(&self) -> &Vec<P> {
+        &self.comp
+    }
+
+    // The depth of the current component in the reverse search tree,
+    // that is, the number of merges from the cycle
+
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    // Move to the next component, returning false (and leaving the
+    // current component unchanged) if there are none
+
+    pub fn advance(&mut self) -> bool {
+        let c = &self.comp;
+        if tracing() {
+            trace(format_args!("visit {}", Pretty(c)));
+        }
+        if c.len() >= 2 {
+            if let Some((m, l, r)) = next_merge_at(c, c.len() - 2, c.len()) {
+                let parent = std::mem::replace(&mut self.comp, m);
+                self.stack.push((parent, l, r));
+                return true;
+            }
+        }
+        while let Some((u, l, r)) = self.stack.pop() {
+            if tracing() {
+                trace(format_args!("backtrack to {}", Pretty(&u)));
+            }
+            if let Some((m, l, r)) = next_merge_at(&u, l, r + 1) {
+                self.comp = m;
+                self.stack.push((u, l, r));
+                return true;
+            }
+        }
+        false
+    }
+}
+
+
+// Compute the position of component c in the reverse search tree:
+// its depth, that is, the number of merges from the cycle, and the
+// indices l, r of the candidate merge of its parent giving c (None
+// for the cycle itself)
+
+pub fn search_position<P: TreePtr>(c: &[P])
+                                   -> (usize, Option<(usize, usize)>) {
+    let Some((mut u, l, r)) = unmerge(c) else {
+        return (0, None);
+    };
+    let mut depth = 1;
+    while let Some((v, _, _)) = unmerge(&u) {
+        u = v;
+        depth += 1;
+    }
+    (depth, Some((l, r)))
+}
+
+
+// Compute the number of vertices of a component
+
+pub fn comp_size<P: TreePtr>(c: &[P]) -> usize {
+    let mut n = 0;
+    for i in 0..c.len() {
+        n += c[i].len();
+    }
+    n
+}
+
+
+// Return the component consising of a cycle of length n
+
+pub fn cycle(n: usize) -> Comp {
+    cycle_of(n)
+}
+
+
+// Return the component consisting of a cycle of length n, using
+// pointer type P, e.g., cycle_of::<Arc<Tree>>(n) for a SyncComp
+
+pub fn cycle_of<P: TreePtr>(n: usize) -> Vec<P> {
+    vec![P::from(vec![1]); n]
+}
+
+
+// Generate all components of n vertices, pass each of them to emit
+// (as a single-component functional digraph) and return their count;
+// only the current component is kept in memory. Sizes larger than
+// MAX_VERTICES give an Error::TooManyVertices
+
+pub fn generate_comps<E: From<Error>>(n: usize, emit: &mut Emit<E>)
+                                      -> Result<u64, E> {
+    if n > MAX_VERTICES {
+        return Err(Error::TooManyVertices(n).into());
+    }
+    if n == 0 {
+        return Ok(0);
+    }
+    let mut walk = CompWalk::new(n);
+    let mut count = 1;
+    loop {
+        let g: Func = vec![Rc::new(walk.comp().to_vec())];
+        emit(&g)?;
+        if walk.advance() {
+            count += 1;
+        } else {
+            break;
+        }
+    }
+    Ok(count)
+}
+
+
+// Compute the sum of a partition of the integer n,
+// that is, n itself
+
+pub fn sum_part(p: &Part) -> usize {
+    p.iter().sum::<u8>() as usize
+}
+
+
+// Compute the next partition of integer n in lexicographic order,
+// if it exists. The algorithm is based on Algorithm 3.1 of Jerome
+// Kelleher, Barry O'Sullivan, "Generating all partitions: A
+// comparison of two encodings", arXiv:0909.2331, 2015,
+// https://arxiv.org/abs/0909.2331
+
+pub fn next_part(p: &Part) -> Option<Part> {
+    if p.len() <= 1 {
+        return None;
+    }
+    let n = sum_part(p);
+    let mut k = p.len() - 1;
+    let mut p = p.clone();
+    p.extend(vec![0; n - p.len()]);
+    let mut y = p[k] - 1;
+    k -= 1;
+    let x = p[k] + 1;
+    while x <= y {
+        p[k] = x;
+        y -= x;
+        k += 1;
+    }
+    p[k] = x + y;
+    Some(p[0..k + 1].to_vec())
+}
+
+
+// Compute the partition corresponding to functional digraph g,
+// that is, the list of sizes of its components
+
+pub fn part(g: &Func) -> Part {
+    let mut p = Part::new();
+    for i in 0..g.len() {
+        p.push(comp_size(&g[i]) as u8);
+    }
+    p
+}
+
+
+// Return the functional digraph consisting of n self-loops
+
+pub fn loops(n: usize) -> Func {
+    let mut g = Func::new();
+    let c = Rc::new(cycle(1));
+    for _ in 0..n {
+        g.push(c.clone());
+    }
+    g
+}
+
+
+// Compute the next functional digraph by taking the successor of the
+// rightmost component having a successor of the same size, if any;
+// otherwise, compute the next partition and restart with the first
+// component of each size (the cycle)
+
+pub fn next_func(g: &Func) -> Option<Func> {
+    let mut f = Func::new();
+    for h in (0..g.len()).rev() {
+        if let Some(c) = next_comp(&g[h]) {
+            f.extend_from_slice(&g[0..h]);
+            let n = comp_size(&c);
+            f.push(Rc::new(c));
+            for i in h + 1..g.len() {
+                let m = comp_size(&g[i]);
+                if m == n {
+                    f.push(f[h].clone());
+                } else {
+                    f.push(Rc::new(cycle(m)));
+                }
+            }
+            return Some(f);
+        }
+    }
+    let p = part(g);
+    if let Some(q) = next_part(&p) {
+        for i in 0..q.len() {
+            f.push(Rc::new(cycle(q[i] as usize)));
+        }
+        return Some(f);
+    }
+    None
+}
+
+
+// Generate all functional digraphs of n vertices, pass each of them
+// to emit and return their count; only the current digraph is kept
+// in memory. Sizes larger than MAX_VERTICES give an
+// Error::TooManyVertices
+
+pub fn generate_funcs<E: From<Error>>(n: usize, emit: &mut Emit<E>)
+                                      -> Result<u64, E> {
+    if n > MAX_VERTICES {
+        return Err(Error::TooManyVertices(n).into());
+    }
+    let mut g = loops(n);
+    let mut count = 1;
+    loop {
+        emit(&g)?;
+        if let Some(f) = next_func(&g) {
+            count += 1;
+            g = f;
+        } else {
+            break;
+        }
+    }
+    Ok(count)
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use std::rc::Rc;
+    use crate::canon::canon_tree;
+    use super::*;
+
+
+    // Code of the path with k vertices
+
+    fn path(k: u8) -> Tree {
+        (1..=k).rev().collect()
+    }
+
+
+    // Check that the fingerprints give the same answer as the naive
+    // algorithm for all sequences (of FINGERPRINT_MIN_LEN or 10
+    // trees) of single vertices and of two trees of 33 vertices whose
+    // codes [33, 32, 1, 30, ...] and [33, 16, 15, ...] agree on their
+    // first entries saturated at 15, although the first one is larger
+
+    #[test]
+    fn fingerprint_min_rotation() {
+        let leaf = Rc::new(vec![1]);
+        let a = Rc::new(canon_tree(vec![canon_tree(vec![vec![1],
+                                                        path(30)])]));
+        let b = Rc::new(canon_tree(vec![path(16), path(16)]));
+        assert_eq!(a[..3], [33, 32, 1]);
+        assert_eq!(b[..3], [33, 16, 15]);
+        assert_eq!(cmp_trees(&a, &b), Greater);
+        let trees = [leaf, a, b];
+        for len in [FINGERPRINT_MIN_LEN, 10] {
+            for mut k in 0..trees.len().pow(len as u32) {
+                let mut c = Comp::new();
+                for _ in 0..len {
+                    c.push(trees[k % trees.len()].clone());
+                    k /= trees.len();
+                }
+                assert_eq!(fingerprint_is_min_rotation(&c[..]),
+                           naive_is_min_rotation_of(&c[..], cmp_trees),
+                           "{c:?}");
+            }
+        }
+    }
+
+}
@@ -16,537 +16,784 @@
 // <https://www.gnu.org/licenses/>.
 
 
-// A generator of functional digraphs up to isomorphism. Based on
-// Antonio E. Porreca, Ekaterina Timofeeva, "Polynomial-delay
-// generation of functional digraphs up to isomorphism",
-// arXiv:2302.13832, 2023, https://doi.org/10.48550/arXiv.2302.13832
-// and on the original funkdigen, a proof-of-concept Python
-// implementation of the same algorithms, available at
-// https://github.com/aeporreca/funkdigen
+// Command-line interface of funkdigen2, a generator of functional
+// digraphs up to isomorphism
 
 
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashSet};
+use std::env;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::mem;
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{self, Child, Stdio};
 use std::rc::Rc;
-use std::cmp::Ordering::{Less, Equal, Greater};
-use std::time::Instant;
+use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::mpsc::{SyncSender, sync_channel};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use lazy_static::lazy_static;
-use clap::Parser;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap::builder::{PossibleValue, PossibleValuesParser, TypedValueParser};
+use clap::error::ErrorKind;
+use funkdigen2::{Tree, Comp, Func, Emit, comp_size, cycle, generate_comps,
+                 generate_funcs, loops, next_comp, next_func, part,
+                 search_position, set_lcs, set_trace};
+use funkdigen2::augment::{generate_comps_augment, generate_funcs_augment};
+use funkdigen2::boltzmann::{MAX_SIZE, Sampler};
+use funkdigen2::cache::{CountCache, default_cache_dir};
+use funkdigen2::filter::{Filter, IndegreeSeq, Slice, RangeGenerator, Visit,
+                         generate_comps_filtered, generate_funcs_filtered,
+                         visit_comps_filtered_from,
+                         visit_funcs_filtered_from};
+use funkdigen2::canon::{FromFunction, canon_labelling, canon_map,
+                        canon_tree_code, canonical_isomorphism};
+use funkdigen2::embed::func_embedding;
+use funkdigen2::code::Code;
+use funkdigen2::direct::{generate_comps_direct, generate_funcs_direct};
+use funkdigen2::digraph6::{func_adj, parse_digraph6, write_digraph6_code};
+use funkdigen2::dot::{DotStyle, write_dot, write_search_tree};
+use funkdigen2::error::{Error, MAX_VERTICES};
+use funkdigen2::extend::generate_completions;
+use funkdigen2::format::{Compact, Forests, Newick, Pretty, key};
+use funkdigen2::gml::write_gml;
+use funkdigen2::http::{Chunked, Request, read_request, write_response};
+use funkdigen2::invariants::{Components, CompInvariants, CycleType,
+                              FuncInvariants, INVARIANTS, Invariant,
+                              Shown, TreeInvariants, Value,
+                              default_invariants, find_invariant,
+                              parse_value};
+use funkdigen2::iso::find_colored_isomorphism;
+use funkdigen2::moments::Expectations;
+use funkdigen2::orbit::generate_labellings;
+use funkdigen2::order::FuncKey;
+#[cfg(feature = "arrow")]
+use funkdigen2::parquet::ParquetWriter;
+use funkdigen2::parse::{ParseError, is_function_table, parse_func,
+                        parse_function_table, parse_key,
+                        parse_partial_function_table, parse_tree};
+use funkdigen2::pointed::{num_labellings, pointed_reps, swap_adj};
+use funkdigen2::random::Rng;
+use funkdigen2::report::Report;
+use funkdigen2::sha256::Sha256;
+use funkdigen2::sqlite::SqliteWriter;
+use funkdigen2::mtx::{MtxWriter, write_mtx};
 
 
-// Types for isomorphism codes for trees, components and functional
-// digraphs; Comp and Func can share substructure, which reduces the
-// number of memory allocations
+// Size in bytes of the chunks of output sent to the writer thread,
+// and maximum number of chunks waiting to be written; this bounds the
+// memory used for buffering the output
 
-type Tree = Vec<u8>;
-type Comp = Vec<Rc<Tree>>;
-type Func = Vec<Rc<Comp>>;
+const OUTPUT_CHUNK_SIZE: usize = 64 * 1024;
+const OUTPUT_QUEUE_LENGTH: usize = 16;
 
 
-// Type for partitions of an integer
+// Buffered output sending full chunks to a separate thread, which
+// writes them to stdout; this way the generator can proceed while
+// the (possibly slow) terminal or pipe is being written to, and the
+// bounded channel provides backpressure when it is too slow. The
+// digraphs themselves cannot be sent, since they are not Send, so
+// they are formatted on the generating thread. If there is only one
+// CPU available, the chunks are just written directly to stdout
 
-type Part = Vec<u8>;
-
-
-// Types for adjacency vectors (i.e., adjacency lists for digraphs
-// with uniform outdegree 1) and bit strings; these are only used when
-// printing in digraph6 format
+struct Output {
+    buf: Vec<u8>,
+    sender: Option<SyncSender<Vec<u8>>>,
+    thread: Option<JoinHandle<io::Result<()>>>,
+    flush_every: Option<u64>,
+    records: u64,
+    digest: Option<Sha256>,
+    discard: bool,
+}
 
-type Adj = Vec<u8>;
-type Bits = Vec<bool>;
 
+impl Output {
 
-// Check if slice s is sorted nondecreasingly
+    // Create the output; if flush_every is Some(k), the output is
+    // also written out after every k records, instead of only when
+    // a chunk is full
 
-fn is_sorted<T: Ord>(s: &[T]) -> bool {
-    for i in 0..s.len() - 1 {
-        if s[i] > s[i + 1] {
-            return false;
+    fn new(flush_every: Option<u64>) -> Output {
+        let mut out = Output {
+            buf: Vec::with_capacity(OUTPUT_CHUNK_SIZE),
+            sender: None,
+            thread: None,
+            flush_every,
+            records: 0,
+            digest: None,
+            discard: false,
+        };
+        if thread::available_parallelism().map_or(1, |n| n.get()) > 1 {
+            let (sender, receiver) =
+                sync_channel::<Vec<u8>>(OUTPUT_QUEUE_LENGTH);
+            out.sender = Some(sender);
+            out.thread = Some(thread::spawn(move || {
+                let mut stdout = io::stdout().lock();
+                for chunk in receiver {
+                    stdout.write_all(&chunk)?;
+                    stdout.flush()?;
+                }
+                Ok(())
+            }));
         }
+        out
     }
-    true
-}
-
 
-// Check if slice s is its own minimal rotation. This is a naive
-// algorithm which increases the theoretical runtime from O(n^3) but,
-// for slices of lengths corresponding to practical digraph sizes, it
-// seems to be more efficient in practice for n <= 255.
+    // Send the current chunk to the writer thread (or write it); if
+    // the thread has stopped because of an error, return that error
 
-fn naive_is_min_rotation<T: Ord>(s: &[T]) -> bool {
-    for r in 1..s.len() {
-        for i in 0..s.len() {
-            match s[i].cmp(&s[(i + r) % s.len()]) {
-                Greater => return false,
-                Less => break,
-                Equal => (),
+    fn send(&mut self) -> io::Result<()> {
+        let chunk = mem::replace(&mut self.buf,
+                                 Vec::with_capacity(OUTPUT_CHUNK_SIZE));
+        match &self.sender {
+            Some(sender) => match sender.send(chunk) {
+                Ok(()) => Ok(()),
+                Err(_) => self.finish(),
+            },
+            None => {
+                let mut stdout = io::stdout().lock();
+                stdout.write_all(&chunk)?;
+                stdout.flush()
             }
         }
     }
-    true
-}
-
 
-// Check if slice s is its own minimal rotation. This is based on
-// the linear-time algorithms such as Kellogg S. Booth's LCS
-// (described in "Lexicographically least circular substrings",
-// Information Processing Letters 10(4), 1980, pages 240-242,
-// https://doi.org/10.1016/0020-0190(80)90149-0 and in the errata at
-// https://www.cs.ubc.ca/~ksbooth/PUB/LCS.shtml) which we used in the
-// paper in order to obtain the theoretical upper bound, but it is
-// empirically slower that the naive algorithm for n <= 255.
+    // Signal the end of a record (e.g., a digraph), sending the
+    // current chunk if the output must be written out now
 
-fn lcs_is_min_rotation<T: Ord>(s: &[T]) -> bool {
-    let n = s.len();
-    let mut f = vec![-1; 2 * n];
-    let mut k = 0;
-    for j in 1..2 * n {
-        let mut i = f[j - k - 1 as usize];
-        while i != -1 && s[j % n] != s[(k + i as usize + 1) % n] {
-            if s[j % n] < s[(k + i as usize + 1) % n] {
-                k = j - i as usize - 1;
+    fn end_record(&mut self) -> io::Result<()> {
+        if let Some(k) = self.flush_every {
+            self.records += 1;
+            if self.records.is_multiple_of(k) && !self.buf.is_empty() {
+                self.send()?;
             }
-            i = f[i as usize];
         }
-        if i == -1 && s[j % n] != s[(k + i as usize + 1) % n] {
-            if s[j % n] < s[(k + i as usize + 1) % n] {
-                k = j;
-            }
-            f[j - k] = -1;
-        } else {
-            f[j - k] = i + 1;
+        Ok(())
+    }
+
+    // Send the remaining output, wait for the writer thread (if any)
+    // to finish and return its result
+
+    fn finish(&mut self) -> io::Result<()> {
+        let res = if self.buf.is_empty() { Ok(()) } else { self.send() };
+        self.sender = None;
+        match self.thread.take() {
+            Some(thread) => thread.join().unwrap(),
+            None => res,
         }
     }
-    s[k..] == s[..n - k] && s[..k] == s[n - k..]
+
 }
 
 
-// Compute the unmerge u of component c and the indices l, r
-// such that remerging u between l and r gives back c
+impl Write for Output {
 
-fn unmerge(c: &Comp) -> Option<(Comp, usize, usize)> {
-    let mut u = Comp::new();
-    let mut l = 0;
-    while l < c.len() && c[l].len() == 1 {
-        u.push(c[l].clone());
-        l += 1;
-    }
-    if l == c.len() {
-        return None;
-    }
-    u.push(Rc::new(vec![1]));
-    let t = &c[l];
-    let mut i = 1;
-    let mut r = l + 1;
-    while i < t.len() {
-        u.push(Rc::new(t[i..i + t[i] as usize].to_vec()));
-        i += t[i] as usize;
-        r += 1;
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if let Some(digest) = &mut self.digest {
+            digest.update(data);
+        }
+        if self.discard {
+            return Ok(data.len());
+        }
+        self.buf.extend_from_slice(data);
+        if self.buf.len() >= OUTPUT_CHUNK_SIZE {
+            self.send()?;
+        }
+        Ok(data.len())
     }
-    for i in l + 1..c.len() {
-        u.push(c[i].clone());
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
     }
-    Some((u, l, r))
+
 }
 
 
+// Type for the functions printing functional digraph g to out, with
+// distinguished vertex v for pointed digraphs
+
+type Print = fn(&mut dyn Write, &Func, Option<usize>) -> io::Result<()>;
+
 
-// Check if component c has unmerge u (this is not a general purpose
-// function, it only works in the context of the function merge below)
+// Terminate the record of digraph g printed in a textual format,
+// appending the name of the distinguished vertex v, if any (as in
+// the digraph6 output of the same digraph), and the cycle type of
+// the iterate given by ARGS.iterate_cycles, if any, the
+// characteristic polynomial if ARGS.char_poly is true, the in-degree
+// sequence if ARGS.indegrees is true, the preperiod and period of
+// each vertex if ARGS.vertex_orbits is true (with vertices named as
+// in digraph6 format), the sizes of the images of the iterates if
+// ARGS.image_chain is true, and the values of the invariants given by
+// ARGS.invariants, if any; records are terminated by a newline or, if
+// ARGS.print0 is true, by a NUL character
 
-fn has_unmerge(c: &Comp, u: &Comp) -> bool {
-    let mut i = 0;
-    while i < c.len() && c[i].len() == 1 {
-        i += 1;
+fn end_line(out: &mut dyn Write, g: &Func, v: Option<usize>)
+            -> io::Result<()> {
+    if let Some(v) = v {
+        write!(out, " {v}")?;
     }
-    u[i][0] == 1
+    end_annotated(out, g, None)
 }
 
 
-// Merge trees c[l], ..., c[r - 1] if that gives a valid isomorphism
-// code for a component
+// Terminate the record of digraph g as end_line, but without
+// appending a distinguished vertex; if renamed is given, that vertex
+// was renamed as vertex 0 in the record (as in digraph6 output), and
+// the preperiods and periods follow the same naming
 
-fn merge(c: &Comp, l: usize, r: usize) -> Option<Comp> {
-    if c[l].len() != 1 || !is_sorted(&c[l..r]) {
-        return None;
+fn end_annotated(out: &mut dyn Write, g: &Func, renamed: Option<usize>)
+                 -> io::Result<()> {
+    if let Some(k) = ARGS.iterate_cycles {
+        write!(out, " {}", g.iterate_cycle_type(k as usize))?;
     }
-    let mut m = Comp::new();
-    for i in 0..l {
-        m.push(c[i].clone());
+    if ARGS.char_poly {
+        write!(out, " {}", g.char_poly())?;
     }
-    let mut t = vec![1];
-    for i in l + 1..r {
-        t.extend_from_slice(&c[i]);
-        t[0] += c[i].len() as u8;
+    if ARGS.indegrees {
+        write!(out, " {}", IndegreeSeq(g.indegree_counts()))?;
     }
-    m.push(Rc::new(t));
-    for i in r..c.len() {
-        m.push(c[i].clone());
+    if ARGS.vertex_orbits {
+        let mut orbits = g.vertex_orbits();
+        if let Some(v) = renamed {
+            orbits.swap(0, v);
+        }
+        let mut sep = " ";
+        for (preperiod, period) in orbits {
+            write!(out, "{sep}{preperiod}:{period}")?;
+            sep = ",";
+        }
     }
-    if !IS_MIN_ROTATION(&m) || !has_unmerge(&m, &c) {
-        return None;
+    if ARGS.image_chain {
+        let mut sep = " ";
+        for size in g.image_sizes() {
+            write!(out, "{sep}{size}")?;
+            sep = ",";
+        }
+    }
+    for inv in ARGS.invariants.iter().flatten() {
+        write!(out, " {}", Shown(inv.compute(g)))?;
     }
-    Some(m)
+    end_record(out)
 }
 
 
-// Compute the next valid merge of c, if any, starting from l
-// (decreasing) and up to and exluding r (increasing); this
-// corresponds to the lexicographically minimal merge
+// Terminate a record, as in end_line
 
-fn next_merge(c: &Comp, mut l: usize, mut r: usize) -> Option<Comp> {
-    loop {
-        while r <= c.len() {
-            if let Some(m) = merge(c, l, r) {
-                return Some(m);
-            }
-            r += 1;
-        }
-        if l == 0 {
-            return None;
-        }
-        l -= 1;
-        r = l + 2;
+fn end_record(out: &mut dyn Write) -> io::Result<()> {
+    if ARGS.print0 {
+        out.write_all(b"\0")
+    } else {
+        writeln!(out)
     }
 }
 
 
-// Compute the next component by merging c, if possible, and otherwise
-// by unmerging and remerging, if possible
+// Print functional digraph g to out in digraph6 format (described
+// at https://users.cecs.anu.edu.au/~bdm/data/formats.txt), renaming
+// the distinguished vertex v (if any) as vertex 0, and deleting
+// self-loops first if ARGS.loopless is true
+
+fn print_digraph6(out: &mut dyn Write, g: &Func, v: Option<usize>)
+                  -> io::Result<()> {
+    let a = match v {
+        Some(v) => swap_adj(&func_adj(g), v),
+        None => func_adj(g),
+    };
+    write_digraph6_code(out, &a, ARGS.loopless)?;
+    end_annotated(out, g, v)
+}
 
-fn next_comp(c: &Comp) -> Option<Comp> {
-    if c.len() >= 2 {
-        if let Some(m) = next_merge(c, c.len() - 2, c.len()) {
-            return Some(m);
-        }
+
+// Print functional digraph g to out as a dreadnaut command line (for
+// nauty and Traces, https://pallini.di.uniroma1.it), with vertices
+// named as in digraph6 format (but without renaming the distinguished
+// vertex v, if any, which is put in a cell of its own in the initial
+// partition instead): this reads the digraph and computes its
+// automorphism group
+
+fn print_dreadnaut(out: &mut dyn Write, g: &Func, v: Option<usize>)
+                   -> io::Result<()> {
+    let a = func_adj(g);
+    write!(out, "n={} $=0 d g", a.len())?;
+    for (x, &y) in a.iter().enumerate() {
+        write!(out, " {x}:{y};")?;
     }
-    let mut res = unmerge(&c);
-    // This loop is actually executed at most twice,
-    // see Lemma 15 of the paper
-    while let Some((u, l, r)) = res {
-        if let Some(m) = next_merge(&u, l, r + 1) {
-            return Some(m);
-        }
-        res = unmerge(&u);
+    write!(out, " .")?;
+    if let Some(v) = v {
+        write!(out, " f=[{v}]")?;
     }
-    None
+    write!(out, " x")?;
+    end_line(out, g, None)
 }
 
 
-// Compute the number of vertices of a component
+// Print functional digraph g to out as a DOT digraph, styled
+// according to the --dot options; as for GML, nothing is appended to
+// the digraph
 
-fn comp_size(c: &Comp) -> usize {
-    let mut n = 0;
-    for i in 0..c.len() {
-        n += c[i].len();
-    }
-    n
+fn print_dot(out: &mut dyn Write, g: &Func, v: Option<usize>)
+             -> io::Result<()> {
+    let style = DotStyle {
+        highlight_cycles: ARGS.dot_cycles,
+        cluster_components: ARGS.dot_clusters,
+        label_orbits: ARGS.dot_orbits,
+    };
+    write_dot(out, g, v, &style)?;
+    end_record(out)
 }
 
 
-// Return the component consising of a cycle of length n
+// Print functional digraph g to out as a GML graph, with the
+// distinguished vertex v (if any) marked by an attribute; nothing is
+// appended to the graph, so that each line can be parsed on its own
 
-fn cycle(n: usize) -> Comp {
-    let mut c = Comp::new();
-    let t = Rc::new(vec![1]);
-    for _ in 0..n {
-        c.push(t.clone());
-    }
-    c
+fn print_gml(out: &mut dyn Write, g: &Func, v: Option<usize>)
+             -> io::Result<()> {
+    write_gml(out, g, v)?;
+    end_record(out)
 }
 
 
-// Generate all components of n vertices, print them
-// and return their count
+// Print functional digraph g to out as its adjacency matrix, one row
+// of 0s and 1s per line, with vertices named as in digraph6 format
+// (including the renaming of the distinguished vertex v, if any, as
+// vertex 0); as for DOT, nothing is appended to the matrix, and the
+// record is terminated after its last row
 
-fn generate_comps(n: usize) -> u64 {
-    if n == 0 {
-        return 0;
-    }
-    let mut c = cycle(n);
-    let mut count = 1;
-    loop {
-        let g: Func = vec![Rc::new(c.clone())];
-        PRINT_FUNC(&g);
-        if let Some(d) = next_comp(&c) {
-            count += 1;
-            c = d;
-        } else {
-            break;
-        }
+fn print_adjmatrix(out: &mut dyn Write, g: &Func, v: Option<usize>)
+                   -> io::Result<()> {
+    let a = match v {
+        Some(v) => swap_adj(&func_adj(g), v),
+        None => func_adj(g),
+    };
+    let mut row = vec![b'0'; a.len()];
+    for &y in &a {
+        row[y as usize] = b'1';
+        out.write_all(&row)?;
+        writeln!(out)?;
+        row[y as usize] = b'0';
     }
-    count
+    end_record(out)
 }
 
 
-// Compute the sum of a partition of the integer n,
-// that is, n itself
+// Print functional digraph g to out as a Matrix Market file of its
+// adjacency matrix; as for DOT, nothing is appended to the file
 
-fn sum_part(p: &Part) -> usize {
-    p.iter().sum::<u8>() as usize
+fn print_mtx(out: &mut dyn Write, g: &Func, v: Option<usize>)
+             -> io::Result<()> {
+    write_mtx(out, g, v)?;
+    end_record(out)
 }
 
 
-// Compute the next partition of integer n in lexicographic order,
-// if it exists. The algorithm is based on Algorithm 3.1 of Jerome
-// Kelleher, Barry O'Sullivan, "Generating all partitions: A
-// comparison of two encodings", arXiv:0909.2331, 2015,
-// https://arxiv.org/abs/0909.2331
+// Print functional digraph g to out in internal format (list of lists
+// of lists of integers)
 
-fn next_part(p: &Part) -> Option<Part> {
-    if p.len() <= 1 {
-        return None;
-    }
-    let n = sum_part(p);
-    let mut k = p.len() - 1;
-    let mut p = p.clone();
-    p.extend(vec![0; n - p.len()]);
-    let mut y = p[k] - 1;
-    k = k - 1;
-    let x = p[k] + 1;
-    while x <= y {
-        p[k] = x;
-        y = y - x;
-        k = k + 1;
-    }
-    p[k] = x + y;
-    Some(p[0..k + 1].to_vec())
+fn print_internal(out: &mut dyn Write, g: &Func, v: Option<usize>)
+                  -> io::Result<()> {
+    write!(out, "{g:?}")?;
+    end_line(out, g, v)
 }
 
 
-// Compute the partition corresponding to functional digraph g,
-// that is, the list of sizes of its components
+// Print functional digraph g to out in nested-bracket notation
 
-fn part(g: &Func) -> Part {
-    let mut p = Part::new();
-    for i in 0..g.len() {
-        p.push(comp_size(&g[i]) as u8);
-    }
-    p
+fn print_pretty(out: &mut dyn Write, g: &Func, v: Option<usize>)
+                -> io::Result<()> {
+    write!(out, "{}", Pretty(g))?;
+    end_line(out, g, v)
 }
 
 
-// Return the functional digraph consisting of n self-loops
+// Print functional digraph g to out in Newick notation
 
-fn loops(n: usize) -> Func {
-    let mut g = Func::new();
-    let c = Rc::new(cycle(1));
-    for _ in 0..n {
-        g.push(c.clone());
-    }
-    g
+fn print_newick(out: &mut dyn Write, g: &Func, v: Option<usize>)
+                -> io::Result<()> {
+    write!(out, "{}", Newick(g))?;
+    end_line(out, g, v)
 }
 
 
-// Compute the next functional digraph by taking the successor of the
-// rightmost component having a successor of the same size, if any;
-// otherwise, compute the next partition and restart with the first
-// component of each size (the cycle)
+// Print functional digraph g to out as a permutation of forests
 
-fn next_func(g: &Func) -> Option<Func> {
-    let mut f = Func::new();
-    for h in (0..g.len()).rev() {
-        if let Some(c) = next_comp(&g[h]) {
-            f.extend_from_slice(&g[0..h]);
-            let n = comp_size(&c);
-            f.push(Rc::new(c));
-            for i in h + 1..g.len() {
-                let m = comp_size(&g[i]);
-                if m == n {
-                    f.push(f[h].clone());
-                } else {
-                    f.push(Rc::new(cycle(m)));
-                }
-            }
-            return Some(f);
-        }
-    }
-    let p = part(&g);
-    if let Some(q) = next_part(&p) {
-        for i in 0..q.len() {
-            f.push(Rc::new(cycle(q[i] as usize)));
-        }
-        return Some(f);
-    }
-    None
+fn print_forests(out: &mut dyn Write, g: &Func, v: Option<usize>)
+                 -> io::Result<()> {
+    write!(out, "{}", Forests(g))?;
+    end_line(out, g, v)
 }
 
 
-// Generate all functional digraphs of n vertices,
-// print them and return their count
+// Print functional digraph g to out in compact notation
 
-fn generate_funcs(n: usize) -> u64 {
-    let mut g = loops(n);
-    let mut count = 1;
-    loop {
-        PRINT_FUNC(&g);
-        if let Some(f) = next_func(&g) {
-            count += 1;
-            g = f;
-        } else {
-            break;
-        }
-    }
-    count
+fn print_compact(out: &mut dyn Write, g: &Func, v: Option<usize>)
+                 -> io::Result<()> {
+    write!(out, "{}", Compact(g))?;
+    end_line(out, g, v)
 }
 
 
-// Compute the adjacency vector of a tree; use b (base) as the name of
-// the root (it is 0 for an isolated tree, but of course can be > 0
-// when there are several trees)
+// Print functional digraph g to out as a base64 key
 
-fn tree_adj(t: &Tree, b: usize) -> Adj {
-    let mut a = vec![0; t.len()];
-    fill_tree_adj(t, &mut a, 0, 0, b);
-    a
+fn print_key(out: &mut dyn Write, g: &Func, v: Option<usize>)
+             -> io::Result<()> {
+    write!(out, "{}", key(g))?;
+    end_line(out, g, v)
 }
 
 
-// Fill a with the adjacency vector of tree t starting from its
-// subtree having root at position i; r is the position of the parent
-// of this subtree (if any); use b as the name of the root of t
+// Print to out, as a function table indented by two spaces, a
+// uniformly random labelled function having g as its digraph (and
+// the new name of the distinguished vertex v, if any): the vertices
+// of a representative are relabelled by a uniformly random
+// permutation, each labelled function being obtained from exactly as
+// many permutations as the automorphisms of g
 
-fn fill_tree_adj(t: &Tree, a: &mut Adj, i: usize, r: usize, b: usize) {
-    a[i] = (r + b) as u8;
-    let mut j = i + 1;
-    while j < i + t[i] as usize {
-        fill_tree_adj(t, a, j, i, b);
-        j += t[j] as usize;
+fn print_labelled_sample(out: &mut dyn Write, g: &Func, v: Option<usize>,
+                         rng: &mut Rng) -> io::Result<()> {
+    let a: Vec<usize> = func_adj(g).iter().map(|&w| w as usize).collect();
+    let (b, perm) = relabel(&a, rng);
+    write!(out, " ")?;
+    for y in b {
+        write!(out, " {y}")?;
     }
+    end_line(out, g, v.map(|v| perm[v]))
 }
 
 
-// Compute the adjacency vector of component c, using b as the name of
-// the first vertex of c
+// Do not print functional digraph _g
 
-fn comp_adj(c: &Comp, b: usize) -> Adj {
-    let mut a = Adj::new();
-    let mut j = 0;
-    for i in 0..c.len() {
-        let mut a1 = tree_adj(&c[i], b + j);
-        if i < c.len() - 1 {
-            a1[0] = (b + j + c[i].len()) as u8;
-        } else {
-            a1[0] = b as u8;
-        }
-        a.extend(a1);
-        j += c[i].len();
-    }
-    a
+fn print_nothing(_out: &mut dyn Write, _g: &Func, _v: Option<usize>)
+                 -> io::Result<()> {
+    // do nothing
+    Ok(())
 }
 
 
-// Compute the adjacency vector of functional digraph g
+// Output formats; the corresponding printing functions are chosen by
+// printer
 
-fn func_adj(g: &Func) -> Adj {
-    let mut a = Adj::new();
-    let mut b = 0;
-    for i in 0..g.len() {
-        let a1 = comp_adj(&g[i], b);
-        b += a1.len();
-        a.extend(a1);
-    }
-    a
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    #[value(help = "digraph6 format (compatible with nauty)")]
+    Digraph6,
+
+    #[value(help = "Internal representation (lists of tree codes)")]
+    Internal,
+
+    #[value(help = "Nested-bracket notation for trees and cycles")]
+    Pretty,
+
+    #[value(help = "Newick notation for trees, concatenated along \
+            the cycles")]
+    Newick,
+
+    #[value(help = "Cycle type of the permutation on the periodic \
+            points and forests hanging from them")]
+    Forests,
+
+    #[value(help = "Commands for dreadnaut (nauty), one line per \
+            digraph")]
+    Dreadnaut,
+
+    #[value(help = "DOT language of Graphviz, one digraph per record \
+            (see the --dot options)")]
+    Dot,
+
+    #[value(help = "Graph Modelling Language (e.g., for igraph), one \
+            line per digraph, with component and cycle attributes for \
+            the vertices")]
+    Gml,
+
+    #[value(help = "Adjacency matrix, one row of 0s and 1s per line, \
+            with vertices named as in digraph6 format")]
+    Adjmatrix,
+
+    #[value(help = "Matrix Market files of the sparse adjacency \
+            matrices (e.g., for scipy.io.mmread), one per record")]
+    Mtx,
+
+    #[value(help = "Compact notation without whitespace, with a dot \
+            before each component and the subtree sizes as digits")]
+    Compact,
+
+    #[value(help = "Short base64 key (e.g., for database keys or \
+            file names)")]
+    Key,
+
+    #[cfg(feature = "arrow")]
+    #[value(help = "Apache Parquet file with a row for each digraph, \
+            including its invariants")]
+    Parquet,
 }
 
 
-// Convert an adjacency vector to an adjacency matrix represented as a
-// bit vector (containing the concatenation of the rows of the
-// matrix), deleting self-loops if ARGS.loopless is true
+// Printing function for the given output format
 
-fn adj_matrix(a: &Adj) -> Bits {
-    let mut m = Bits::new();
-    for i in 0..a.len() {
-        for j in 0..a.len() {
-            m.push((!ARGS.loopless || i != j) && a[i] == j as u8);
-        }
+fn printer(format: Format) -> Print {
+    match format {
+        Format::Digraph6 => print_digraph6,
+        Format::Internal => print_internal,
+        Format::Pretty => print_pretty,
+        Format::Newick => print_newick,
+        Format::Forests => print_forests,
+        Format::Dreadnaut => print_dreadnaut,
+        Format::Dot => print_dot,
+        Format::Gml => print_gml,
+        Format::Adjmatrix => print_adjmatrix,
+        Format::Mtx => print_mtx,
+        Format::Compact => print_compact,
+        Format::Key => print_key,
+        // The digraphs are not printed one by one, but collected in
+        // row groups by a ParquetWriter
+        #[cfg(feature = "arrow")]
+        Format::Parquet => print_nothing,
     }
-    m
 }
 
 
-// Convert a bit vector into an ASCII string according to the digraph6
-// specifications
+// Algorithms for the checksum of the output
 
-fn bits_to_ascii(x: &Bits) -> String {
-    let mut s = String::new();
-    let mut i = 0;
-    while i < x.len() {
-        let mut n = 0;
-        for j in i..i + 6 {
-            if j < x.len() {
-                n = 2 * n + x[j] as u8;
-            } else {
-                n = 2 * n;
-            }
-        }
-        s.push((n + 63) as char);
-        i += 6;
-    }
-    s
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Checksum {
+    #[value(help = "SHA-256 (the same as computed by sha256sum)")]
+    Sha256,
 }
 
 
-// Returns a string representing integer n (in the range 0..255) in
-// digraph6 ASCII format, implements function N(n) of the digraph6
-// specifications
+// Generation algorithms
 
-fn int_to_ascii(mut n: usize) -> String {
-    if n < 63 {
-        ((n as u8 + 63) as char).to_string()
-    } else {
-        // 63 <= n <= 255, since args.size is u8
-        let mut b = Bits::new();
-        for _ in 0..18 {
-            b.push(n % 2 == 1);
-            n /= 2;
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Algorithm {
+    #[value(help = "Reverse search, with constant memory")]
+    Reverse,
+    #[value(help = "Direct enumeration of trees, components and \
+            multisets of components, kept in memory")]
+    Direct,
+    #[value(help = "Canonical augmentation of the components by one \
+            leaf at a time (experimental), kept in memory")]
+    Augment,
+}
+
+
+// Formats of the progress reports and of the summary at the end of
+// the generation, on stderr
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LogFormat {
+    #[value(help = "Human-readable messages and tab-separated tables")]
+    Text,
+    #[value(help = "One JSON object per line for each event")]
+    Json,
+}
+
+
+// Weights of the digraphs in the statistics
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Weight {
+    #[value(help = "Each isomorphism class counts once")]
+    Classes,
+    #[value(help = "Each class counts as its number of labelled \
+            functions")]
+    Labelled,
+}
+
+
+// Additional output of the digraphs in a textual format to a file,
+// written as FORMAT:FILE on the command line
+
+#[derive(Clone)]
+struct Sink {
+    format: Format,
+    path: PathBuf,
+}
+
+
+impl FromStr for Sink {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Sink, String> {
+        let Some((format, path)) = s.split_once(':') else {
+            return Err(format!("invalid output '{s}' (expected \
+                                FORMAT:FILE)"));
+        };
+        let format = Format::from_str(format, false)
+            .map_err(|_| format!("unknown format '{format}'"))?;
+        #[cfg(feature = "arrow")]
+        if format == Format::Parquet {
+            return Err("parquet is not available for --output".to_string());
         }
-        b.reverse();
-        bits_to_ascii(&b)
+        Ok(Sink { format, path: PathBuf::from(path) })
     }
 }
 
 
-// Print functional digraph g in digraph6 format (described at
-// https://users.cecs.anu.edu.au/~bdm/data/formats.txt), deleting
-// self-loops first if ARGS.loopless is true
+// Parser for the names of invariants
 
-fn print_digraph6(g: &Func) {
-    print!("&");
-    let a = func_adj(&g);
-    let n = a.len();
-    print!("{}", int_to_ascii(n));
-    let m = adj_matrix(&a);
-    println!("{}", bits_to_ascii(&m));
+fn invariant_parser()
+                    -> impl TypedValueParser<Value = &'static dyn Invariant> {
+    PossibleValuesParser::new(INVARIANTS.map(|inv| {
+        PossibleValue::new(inv.name()).aliases(inv.aliases())
+    }))
+        .try_map(|s| find_invariant(&s))
 }
 
 
-// Print functional digraph g in internal format (list of lists of
-// lists of integers)
+// Invariants stored in the rows of the --sqlite and parquet output:
+// those given by --invariants, or the default ones
 
-fn print_internal(g: &Func) {
-    println!("{g:?}");
+fn table_invariants() -> Vec<&'static dyn Invariant> {
+    ARGS.invariants.clone().unwrap_or_else(default_invariants)
 }
 
 
-// Do not print functional digraph _g
+// Structure for the options constraining the generated digraphs,
+// shared by the main command and the bfile subcommand
+
+#[derive(clap::Args)]
+struct Constraints {
+    #[arg(long, value_name = "H", conflicts_with_all = ["min_height",
+                                                       "max_height"],
+          help = "Only generate digraphs of height H")]
+    height: Option<usize>,
+
+    #[arg(long, value_name = "H",
+          help = "Only generate digraphs of height at least H")]
+    min_height: Option<usize>,
+
+    #[arg(long, value_name = "H", visible_alias = "max-tail",
+          help = "Only generate digraphs of height at most H (i.e., \
+          where every vertex reaches a cycle within H steps)")]
+    max_height: Option<usize>,
+
+    #[arg(long, value_name = "K", visible_alias = "gep",
+          conflicts_with_all = ["min_leaves", "max_leaves"],
+          help = "Only generate digraphs with K leaves (i.e., \
+          garden-of-Eden points, outside the image)")]
+    leaves: Option<usize>,
+
+    #[arg(long, value_name = "K", visible_alias = "min-gep",
+          help = "Only generate digraphs with at least K leaves")]
+    min_leaves: Option<usize>,
+
+    #[arg(long, value_name = "K", visible_alias = "max-gep",
+          help = "Only generate digraphs with at most K leaves")]
+    max_leaves: Option<usize>,
+
+    #[arg(long, value_name = "K", conflicts_with_all = ["min_trivial_trees",
+                                                       "max_trivial_trees"],
+          help = "Only generate digraphs with K trivial trees (i.e., \
+          periodic points without incoming edges from outside the \
+          cycle)")]
+    trivial_trees: Option<usize>,
+
+    #[arg(long, value_name = "K",
+          help = "Only generate digraphs with at least K trivial trees")]
+    min_trivial_trees: Option<usize>,
+
+    #[arg(long, value_name = "K",
+          help = "Only generate digraphs with at most K trivial trees")]
+    max_trivial_trees: Option<usize>,
+
+    #[arg(long, value_name = "SEQ",
+          help = "Only generate digraphs with the given multiset of \
+          in-degrees (e.g., 0^3,1^2,2,3)")]
+    indegree_seq: Option<IndegreeSeq>,
+
+    #[arg(long, value_name = "S",
+          help = "Only generate digraphs whose components \
+          have at least S vertices")]
+    min_component_size: Option<usize>,
+
+    #[arg(long, value_name = "S",
+          help = "Only generate digraphs whose components \
+          have at most S vertices")]
+    max_component_size: Option<usize>,
+
+    #[arg(long, value_name = "D",
+          help = "Only generate digraphs obtained from a permutation \
+          by at most D merges in the search")]
+    max_depth: Option<usize>,
+
+    #[arg(long, value_name = "L",
+          help = "Only generate digraphs whose cycles have length \
+          at most L")]
+    max_cycle_length: Option<usize>,
+
+    #[arg(long, value_name = "K", conflicts_with = "max_cycle_length",
+          help = "Only generate digraphs whose cycles all have length K")]
+    all_cycles_length: Option<usize>,
+
+    #[arg(long, help = "Only generate rigid digraphs, i.e., those whose \
+          only automorphism is the identity")]
+    rigid: bool,
+
+    #[arg(long, help = "Only generate digraphs whose components are \
+          pairwise non-isomorphic")]
+    distinct_components: bool,
+
+    #[arg(long, value_name = "TREE", value_parser = parse_forbidden_tree,
+          help = "Only generate digraphs where no vertex has the given \
+          subtree (e.g., '[[][]]'), possibly repeated")]
+    forbid_tree: Vec<Tree>,
+}
+
+
+impl Constraints {
+
+    // Filter corresponding to the constraints (without a slice)
+
+    fn filter(&self) -> Filter {
+        Filter {
+            min_height: self.height.or(self.min_height),
+            max_height: self.height.or(self.max_height),
+            min_comp_size: self.min_component_size,
+            max_comp_size: self.max_component_size,
+            min_leaves: self.leaves.or(self.min_leaves),
+            max_leaves: self.leaves.or(self.max_leaves),
+            min_trivial_trees: self.trivial_trees.or(self.min_trivial_trees),
+            max_trivial_trees: self.trivial_trees.or(self.max_trivial_trees),
+            indegrees: self.indegree_seq.clone(),
+            max_depth: self.max_depth,
+            min_cycle_length: self.all_cycles_length,
+            max_cycle_length: self.all_cycles_length
+                .or(self.max_cycle_length),
+            rigid: self.rigid,
+            distinct_comps: self.distinct_components,
+            forbidden_trees: self.forbid_tree.clone(),
+            value: None,
+            slice: None,
+        }
+    }
 
-fn print_nothing(_g: &Func) {
-    // do nothing
 }
 
 
 // Structure for the command-line arguments
 
 #[derive(Parser)]
-#[command(version, about)]
+#[command(version, about, args_conflicts_with_subcommands = true,
+          subcommand_negates_reqs = true)]
 struct Args {
-    #[arg(help = "Number of vertices")]
-    size: u8,
+    #[arg(required = true, help = "Number of vertices")]
+    size: Option<u8>,
 
     #[arg(short, long, help = "Only generate connected digraphs")]
     connected: bool,
 
-    #[arg(short, long, help = "Print internal \
-          representation instead of digraph6")]
+    #[arg(short, long, value_enum, default_value_t = Format::Digraph6,
+          help = "Output format")]
+    format: Format,
+
+    #[arg(short, long, conflicts_with = "format",
+          help = "Print internal representation instead of digraph6 \
+          (same as --format internal)")]
     internal: bool,
 
     #[arg(short, long, conflicts_with = "internal",
@@ -554,51 +801,3637 @@ struct Args {
           help = "Remove self-loops before printing (digraph6 only)")]
     loopless: bool,
 
+    #[arg(long, help = "Draw the edges of the cycles in bold red (DOT \
+          format only)")]
+    dot_cycles: bool,
+
+    #[arg(long, help = "Draw each connected component in a box of its \
+          own (DOT format only)")]
+    dot_clusters: bool,
+
+    #[arg(long, help = "Label the vertices with their preperiod and \
+          period (DOT format only)")]
+    dot_orbits: bool,
+
     #[arg(short, long, conflicts_with = "internal",
+          conflicts_with = "format",
           help = "Count digraphs without printing them")]
     quiet: bool,
 
     #[arg(short = 'b', long, help = "Use Booth's \
           LCS algorithm for minimal rotations")]
     lcs: bool,
+
+    #[arg(long, value_enum, default_value_t = Algorithm::Reverse,
+          help = "Generation algorithm (all give the same output)")]
+    algorithm: Algorithm,
+
+    #[command(flatten)]
+    constraints: Constraints,
+
+    #[arg(long, value_name = "RES/MOD",
+          help = "Only generate slice RES of MOD (numbered from 0) of \
+          the digraphs, for splitting the work among MOD processes")]
+    slice: Option<Slice>,
+
+    #[arg(short, long, value_name = "INVARIANT",
+          value_parser = invariant_parser(),
+          help = "Count digraphs by value of an invariant")]
+    group_by: Option<&'static dyn Invariant>,
+
+    #[arg(long, value_name = "INVARIANT", value_parser = invariant_parser(),
+          help = "Print a bar chart of the number of digraphs by value of \
+          an invariant at the end")]
+    histogram: Option<&'static dyn Invariant>,
+
+    #[arg(long, value_name = "INVARIANT", conflicts_with = "quiet",
+          value_parser = invariant_parser(),
+          help = "Print digraphs in sections by value of an invariant \
+          (each with a header line)")]
+    group_output: Option<&'static dyn Invariant>,
+
+    #[arg(long, conflicts_with = "group_output",
+          help = "Print the connected digraphs first, then those with \
+          two components, and so on")]
+    by_components: bool,
+
+    #[arg(short, long, help = "Generate pointed digraphs \
+          (with a distinguished vertex)")]
+    pointed: bool,
+
+    #[arg(long, help = "Print each step of the search on stderr")]
+    trace: bool,
+
+    #[arg(long, value_name = "FILE",
+          help = "Write the reverse search tree (of the components) \
+          to FILE in DOT format")]
+    emit_search_tree: Option<PathBuf>,
+
+    #[arg(short, long, value_name = "FORMAT:FILE",
+          help = "Also write the digraphs in FORMAT to a new FILE \
+          (can be given more than once)")]
+    output: Vec<Sink>,
+
+    #[arg(long, value_name = "FILE",
+          help = "Also write the digraphs, with their rank and \
+          invariants, to a new SQLite database FILE")]
+    output_sqlite: Option<PathBuf>,
+
+    #[arg(long, value_name = "DIR",
+          help = "Also write each digraph to a Matrix Market file \
+          RANK.mtx in DIR (created if needed)")]
+    output_mtx: Option<PathBuf>,
+
+    #[arg(short = '0', long, help = "Terminate each digraph with a NUL \
+          character instead of a newline")]
+    print0: bool,
+
+    #[arg(long, value_name = "N",
+          value_parser = clap::value_parser!(u64).range(1..),
+          help = "Write out the output after every N digraphs \
+          (instead of when the buffer is full)")]
+    flush_every: Option<u64>,
+
+    #[arg(short, long, conflicts_with = "flush_every",
+          help = "Write out each digraph immediately \
+          (same as --flush-every 1)")]
+    unbuffered: bool,
+
+    #[arg(long, value_enum, value_name = "ALGORITHM",
+          help = "Print a checksum of the output at the end \
+          (with -q, of the output which would have been printed)")]
+    checksum: Option<Checksum>,
+
+    #[arg(long, value_name = "K",
+          value_parser = clap::value_parser!(u64).range(1..),
+          help = "Append to each digraph the cycle type of its K-th \
+          iterate, and print their distribution at the end")]
+    iterate_cycles: Option<u64>,
+
+    #[arg(long, help = "Append to each digraph the characteristic \
+          polynomial of its adjacency matrix, in factored form")]
+    char_poly: bool,
+
+    #[arg(long, help = "Append to each digraph its sorted in-degree \
+          sequence, written as for --indegree-seq")]
+    indegrees: bool,
+
+    #[arg(long, help = "Append to each digraph the preperiod and period \
+          of each vertex (named as in digraph6 format), as a \
+          comma-separated list of PREPERIOD:PERIOD")]
+    vertex_orbits: bool,
+
+    #[arg(long, help = "Append to each digraph the sizes of the images \
+          of its iterates, down to the number of periodic points, as a \
+          comma-separated list")]
+    image_chain: bool,
+
+    #[arg(long, value_name = "INVARIANTS", value_delimiter = ',',
+          value_parser = invariant_parser(),
+          help = "Append to each digraph the values of the given \
+          invariants, separated by commas (e.g., periodic-points,aut), \
+          and only store those in the rows of --sqlite and parquet \
+          output")]
+    invariants: Option<Vec<&'static dyn Invariant>>,
+
+    #[arg(long, value_enum, value_name = "WEIGHT",
+          num_args = 0..=1, require_equals = true,
+          default_missing_value = "classes",
+          conflicts_with = "pointed",
+          help = "Print the distributions of the size of the largest \
+          component, of the number of components, of the size of the \
+          component of a random vertex and of the in-degrees of the \
+          vertices, and the number of distinct trees of each size \
+          (only their occurrences with --streaming)")]
+    stats: Option<Weight>,
+
+    #[arg(long, value_name = "K", visible_alias = "labeled-samples",
+          conflicts_with = "quiet",
+          help = "Print after each digraph the function tables of K \
+          random labelled functions having it as their digraph")]
+    labelled_samples: Option<usize>,
+
+    #[arg(long, help = "Seed for the random number generator used by \
+          --labelled-samples, --spot-check and --sample-stream (by \
+          default, taken from the system clock)")]
+    seed: Option<u64>,
+
+    #[arg(long, value_name = "K", conflicts_with_all = ["streaming",
+          "group_output", "time_limit", "resume"],
+          help = "Only output K of the digraphs generated, chosen \
+          uniformly at random (in generation order, at the end)")]
+    sample_stream: Option<usize>,
+
+    #[arg(long, value_name = "K", conflicts_with = "sample_stream",
+          value_parser = clap::value_parser!(u64).range(1..),
+          help = "Only output every K-th digraph generated, i.e., those \
+          whose rank is congruent to --offset modulo K")]
+    every: Option<u64>,
+
+    #[arg(long, value_name = "J", default_value_t = 0, requires = "every",
+          help = "Rank modulo --every of the digraphs output")]
+    offset: u64,
+
+    #[arg(long, value_name = "K", conflicts_with = "streaming",
+          help = "Check that K random pairs of the digraphs generated \
+          are not isomorphic, with an independent algorithm")]
+    spot_check: Option<usize>,
+
+    #[arg(long, help = "Report the progress of the generation on stderr \
+          every 10 seconds, as a percentage of the total number of \
+          digraphs when it is known in advance")]
+    progress: bool,
+
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration,
+          conflicts_with = "progress",
+          help = "Print a log line with the number of digraphs so far, \
+          the rate, the elapsed time and the memory used on stderr every \
+          DURATION (e.g., 60s)")]
+    log_progress: Option<Duration>,
+
+    #[arg(long, value_enum, default_value_t = LogFormat::Text,
+          help = "Format of the progress reports and of the summary \
+          at the end")]
+    log_format: LogFormat,
+
+    #[arg(long, help = "Print the peak resident memory and the sizes \
+          of the internal tables at the end")]
+    report_memory: bool,
+
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration,
+          help = "Stop the generation after DURATION (e.g., 90s, 30m, \
+          2h or 1d), printing a token for resuming it")]
+    time_limit: Option<Duration>,
+
+    #[arg(long, value_name = "TOKEN",
+          help = "Resume a generation stopped by --time-limit \
+          (with the same options), from the digraph given by TOKEN")]
+    resume: Option<String>,
+
+    #[arg(long, value_name = "CODE", conflicts_with_all = ["resume",
+          "slice", "group_output", "by_components", "sample_stream"],
+          help = "Start the generation from the digraph with the given \
+          canonical code (in any textual format), included")]
+    start_from: Option<String>,
+
+    #[arg(short, long, help = "Guarantee constant memory usage \
+          (reject options that buffer digraphs)")]
+    streaming: bool,
+
+    #[arg(long, global = true, help = "Do not read or write the cache of \
+          the tables of counts (under the user cache directory)")]
+    no_cache: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
 
-// Program options
+// Subcommands, for tasks other than generating all digraphs of a
+// given size
 
-lazy_static! {
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "Generate random digraphs, uniformly up to \
+              isomorphism")]
+    Sample(SampleArgs),
 
-    static ref ARGS: Args = Args::parse();
+    #[command(about = "Print the coefficients of the generating functions \
+              counting the digraphs")]
+    Gf(GfArgs),
 
-    static ref GENERATE: fn(usize) -> u64 = if ARGS.connected {
-        generate_comps
-    } else {
-        generate_funcs
-    };
+    #[command(about = "Write the numbers of digraphs for a range of sizes \
+              as an OEIS b-file")]
+    Bfile(BfileArgs),
 
-    static ref PRINT_FUNC: fn(&Func) = if ARGS.quiet {
-        print_nothing
-    } else if ARGS.internal {
-        print_internal
-    } else {
-        print_digraph6
-    };
+    #[command(about = "Split the generation among several worker \
+              processes, possibly on other hosts")]
+    Distribute(DistributeArgs),
 
-    static ref IS_MIN_ROTATION: fn(&Comp) -> bool = if ARGS.lcs {
-        |s| lcs_is_min_rotation(s)
-    } else {
-        |s| naive_is_min_rotation(s)
-    };
+    #[command(about = "Merge files of digraphs in generation order, \
+              removing duplicates")]
+    Merge(MergeArgs),
+
+    #[command(about = "Check that a file contains exactly the digraphs \
+              generated for a given size, in generation order")]
+    Verify(VerifyArgs),
+
+    #[command(about = "Compare two files of digraphs up to isomorphism")]
+    Diff(DiffArgs),
+
+    #[command(about = "Check whether two digraphs are isomorphic, \
+              printing an isomorphism if they are")]
+    Iso(IsoArgs),
+
+    #[command(about = "Check whether a digraph embeds into another one, \
+              component by component, printing an embedding if it does")]
+    Embed(EmbedArgs),
+
+    #[command(about = "Count the digraphs of a file in each isomorphism \
+              class")]
+    Classify(ClassifyArgs),
 
+    #[command(about = "Describe the structure of a digraph given by its \
+              code")]
+    Explain(ExplainArgs),
+
+    #[command(about = "Generate all labelled functions having the digraph \
+              with a given code")]
+    Orbit(OrbitArgs),
+
+    #[command(about = "Generate the completions of a partial function up \
+              to isomorphism")]
+    Extend(ExtendArgs),
+
+    #[command(about = "Check the generated digraphs against the \
+              canonical forms of all labelled functions, for small sizes")]
+    Selftest(SelftestArgs),
+
+    #[command(about = "Write a self-contained HTML page summarizing the \
+              digraphs of a given size")]
+    Report(ReportArgs),
+
+    #[command(about = "Print the exact expectations and variances of \
+              invariants of uniformly random labelled functions")]
+    Expect(ExpectArgs),
+
+    #[command(about = "Serve counts, generated digraphs and canonical \
+              codes over HTTP")]
+    Serve(ServeArgs),
 }
 
 
-// Main program
+// Structure for the arguments of the sample subcommand
 
-fn main() {
-    let n = ARGS.size as usize;
-    let now = Instant::now();
-    let count = GENERATE(n);
-    let time = now.elapsed();
-    eprintln!("{count} digraphs generated in {time:.2?}");
+#[derive(clap::Args)]
+struct SampleArgs {
+    #[arg(help = "Number of vertices (expected, with --approx), at most \
+          255 as for the generation")]
+    size: u8,
+
+    #[arg(short, long, help = "Only generate connected digraphs")]
+    connected: bool,
+
+    #[arg(short, long, value_enum, default_value_t = Format::Digraph6,
+          help = "Output format")]
+    format: Format,
+
+    #[arg(short = 'n', long, default_value_t = 1,
+          help = "Number of digraphs to generate")]
+    count: u64,
+
+    #[arg(short, long, help = "Accept digraphs of approximately the \
+          given size (faster for large sizes, but still at most 255)")]
+    approx: bool,
+
+    #[arg(long, value_name = "EPS", default_value_t = 0.1,
+          requires = "approx",
+          help = "Maximum relative deviation from the size with --approx")]
+    tolerance: f64,
+
+    #[arg(long, help = "Seed for the random number generator \
+          (by default, taken from the system clock)")]
+    seed: Option<u64>,
+
+    #[arg(short = 'j', long, default_value_t = 1,
+          value_parser = clap::value_parser!(u64).range(1..),
+          help = "Number of threads drawing the digraphs (the output \
+          only depends on the seed and on the number of threads)")]
+    threads: u64,
+}
+
+
+// Structure for the arguments of the gf subcommand
+
+#[derive(clap::Args)]
+struct GfArgs {
+    #[arg(help = "Maximum number of vertices")]
+    size: usize,
+
+    #[arg(short = 'y', long, help = "Also count by number of cycle points \
+          (as a polynomial in a second variable y)")]
+    cycle_points: bool,
+
+    #[arg(short = 'z', long, conflicts_with = "cycle_points",
+          help = "Count digraphs by number of fixed points instead")]
+    fixed_points: bool,
+
+    #[arg(short = 'm', long,
+          conflicts_with_all = ["cycle_points", "fixed_points"],
+          help = "Count digraphs by number of components instead")]
+    components: bool,
+
+    #[arg(long, help = "Print comma-separated values instead of \
+          tab-separated ones")]
+    csv: bool,
+}
+
+
+// Structure for the arguments of the bfile subcommand
+
+#[derive(clap::Args)]
+struct BfileArgs {
+    #[arg(help = "Last index, i.e., maximum number of vertices")]
+    last: u8,
+
+    #[arg(long, value_name = "N", default_value_t = 0,
+          help = "First index, i.e., minimum number of vertices")]
+    first: u8,
+
+    #[arg(short, long, help = "Only count connected digraphs")]
+    connected: bool,
+
+    #[arg(short, long, value_name = "FILE",
+          help = "Write the b-file to FILE instead of the standard output")]
+    output: Option<PathBuf>,
+
+    #[command(flatten)]
+    constraints: Constraints,
+}
+
+
+// Structure for the arguments of the distribute subcommand
+
+#[derive(clap::Args)]
+struct DistributeArgs {
+    #[arg(short, long, value_name = "N",
+          help = "Number of local worker processes \
+          (by default, the number of CPUs)")]
+    jobs: Option<usize>,
+
+    #[arg(long = "host", value_name = "HOST",
+          help = "Run a worker on HOST via ssh instead of locally \
+          (can be repeated, also with the same host)")]
+    hosts: Vec<String>,
+
+    #[arg(long, value_name = "COMMAND", default_value = "funkdigen2",
+          help = "Command running funkdigen2 on the remote hosts")]
+    remote_command: String,
+
+    #[arg(short, long, value_name = "DIR",
+          help = "Write the output of each worker to a file in DIR, \
+          together with a manifest (otherwise, the output is discarded)")]
+    output_dir: Option<PathBuf>,
+
+    #[arg(required = true, trailing_var_arg = true,
+          allow_hyphen_values = true, value_name = "ARGS",
+          help = "Options and number of vertices for the workers")]
+    args: Vec<String>,
+}
+
+
+// Structure for the arguments of the merge subcommand
+
+#[derive(clap::Args)]
+struct MergeArgs {
+    #[arg(short = '0', long, help = "Records are terminated by NUL \
+          instead of newline, in both input and output")]
+    print0: bool,
+
+    #[arg(long, help = "Skip the records which cannot be parsed, with \
+          a warning, instead of stopping with an error")]
+    lenient: bool,
+
+    #[arg(required = true, value_name = "FILE",
+          help = "Files to merge, each one in generation order \
+          (- for standard input)")]
+    files: Vec<PathBuf>,
+}
+
+
+// Structure for the arguments of the verify subcommand
+
+#[derive(clap::Args)]
+struct VerifyArgs {
+    #[arg(short, long, help = "Check the list of connected digraphs")]
+    connected: bool,
+
+    #[arg(short = '0', long, help = "Records are terminated by NUL \
+          instead of newline")]
+    print0: bool,
+
+    #[arg(long, help = "Skip the records which cannot be parsed, with \
+          a warning, instead of stopping with an error")]
+    lenient: bool,
+
+    #[arg(help = "Number of vertices")]
+    size: u8,
+
+    #[arg(value_name = "FILE", default_value = "-",
+          help = "File to check (- for standard input)")]
+    file: PathBuf,
+}
+
+
+// Structure for the arguments of the diff subcommand
+
+#[derive(clap::Args)]
+struct DiffArgs {
+    #[arg(short = '0', long, help = "Records are terminated by NUL \
+          instead of newline, in both input and output")]
+    print0: bool,
+
+    #[arg(long, help = "Skip the records which cannot be parsed, with \
+          a warning, instead of stopping with an error")]
+    lenient: bool,
+
+    #[arg(value_name = "FILE1", help = "First file (- for standard input)")]
+    file1: PathBuf,
+
+    #[arg(value_name = "FILE2", help = "Second file (- for standard input)")]
+    file2: PathBuf,
+}
+
+
+// Structure for the arguments of the classify subcommand
+
+#[derive(clap::Args)]
+struct ClassifyArgs {
+    #[arg(short = '0', long, help = "Records are terminated by NUL \
+          instead of newline, in both input and output")]
+    print0: bool,
+
+    #[arg(long, help = "Skip the records which cannot be parsed, with \
+          a warning, instead of stopping with an error")]
+    lenient: bool,
+
+    #[arg(short, long, value_enum, default_value_t = Format::Digraph6,
+          help = "Format of the codes of the classes")]
+    format: Format,
+
+    #[arg(value_name = "FILE", default_value = "-",
+          help = "File of digraphs or labelled functions (- for \
+          standard input)")]
+    file: PathBuf,
+}
+
+
+// Structure for the arguments of the explain subcommand
+
+#[derive(clap::Args)]
+struct ExplainArgs {
+    #[arg(value_name = "CODE",
+          help = "Code of the digraph, in any textual format (quoted \
+          if it contains spaces)")]
+    code: String,
+}
+
+
+// Structure for the arguments of the iso subcommand
+
+#[derive(clap::Args)]
+struct IsoArgs {
+    #[arg(value_name = "DIGRAPH1",
+          help = "First digraph, as a code in any textual format or as \
+          a function table (quoted if it contains spaces)")]
+    first: String,
+
+    #[arg(value_name = "DIGRAPH2",
+          help = "Second digraph, in the same way")]
+    second: String,
+}
+
+
+// Structure for the arguments of the embed subcommand
+
+#[derive(clap::Args)]
+struct EmbedArgs {
+    #[arg(value_name = "DIGRAPH1",
+          help = "Digraph to embed, as a code in any textual format or \
+          as a function table (quoted if it contains spaces)")]
+    first: String,
+
+    #[arg(value_name = "DIGRAPH2",
+          help = "Digraph to embed it into, in the same way")]
+    second: String,
+}
+
+
+// Structure for the arguments of the orbit subcommand
+
+#[derive(clap::Args)]
+struct OrbitArgs {
+    #[arg(short = '0', long, help = "Terminate each function with a NUL \
+          character instead of a newline")]
+    print0: bool,
+
+    #[arg(value_name = "CODE",
+          help = "Code of the digraph, in any textual format (quoted \
+          if it contains spaces)")]
+    code: String,
+}
+
+
+// Structure for the arguments of the extend subcommand
+
+#[derive(clap::Args)]
+struct ExtendArgs {
+    #[arg(short = '0', long, help = "Terminate each function with a NUL \
+          character instead of a newline")]
+    print0: bool,
+
+    #[arg(short, long, help = "Only generate connected completions")]
+    connected: bool,
+
+    #[arg(value_name = "TABLE",
+          help = "Images of the vertices 0, 1, ..., n - 1, with _ for \
+          those to be chosen (e.g., '1 2 _ _ _', quoted)")]
+    table: String,
+}
+
+
+// Structure for the arguments of the selftest subcommand
+
+#[derive(clap::Args)]
+struct SelftestArgs {
+    #[arg(long, value_name = "N", default_value_t = 7,
+          value_parser = clap::value_parser!(u8).range(..=9),
+          help = "Check all sizes from 0 up to N (there are N^N \
+          labelled functions of size N)")]
+    max_n: u8,
+}
+
+
+// Structure for the arguments of the report subcommand
+
+#[derive(clap::Args)]
+struct ReportArgs {
+    #[arg(help = "Number of vertices")]
+    size: u8,
+
+    #[arg(short, long, help = "Only generate connected digraphs")]
+    connected: bool,
+
+    #[arg(long, value_name = "FILE", required = true,
+          help = "Write the report as an HTML page to FILE")]
+    html: PathBuf,
+
+    #[arg(long, value_name = "K", default_value_t = 12,
+          help = "Number of digraphs drawn in the report, chosen \
+          uniformly at random")]
+    sample: usize,
+
+    #[arg(long, help = "Seed for the random number generator \
+          (by default, taken from the system clock)")]
+    seed: Option<u64>,
+}
+
+
+// Structure for the arguments of the expect subcommand
+
+#[derive(clap::Args)]
+struct ExpectArgs {
+    #[arg(value_parser = clap::value_parser!(u8).range(1..),
+          help = "Number of vertices")]
+    size: u8,
+
+    #[arg(short, long, help = "Only consider connected functions")]
+    connected: bool,
+}
+
+
+// Structure for the arguments of the serve subcommand
+
+#[derive(clap::Args)]
+struct ServeArgs {
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8080",
+          help = "Address and port to listen on")]
+    listen: String,
+
+    #[arg(short, long, value_name = "N",
+          help = "Number of requests answered at the same time \
+          (by default, the number of CPUs)")]
+    jobs: Option<usize>,
+
+    #[arg(long, value_name = "N", default_value_t = 16,
+          help = "Maximum number of vertices for /count when the \
+          digraphs must be generated (e.g., with constraints)")]
+    max_count_size: usize,
+
+    #[arg(long, value_name = "N", default_value_t = 12,
+          help = "Maximum number of vertices for /generate")]
+    max_generate_size: usize,
+}
+
+
+// Structure for the query parameters of the requests to the server,
+// parsed as the corresponding command-line options (e.g., the
+// parameter max-height=3 as --max-height=3, and rigid as --rigid)
+
+#[derive(Parser)]
+#[command(name = "query", no_binary_name = true,
+          disable_help_flag = true)]
+struct Query {
+    #[arg(long, help = "Number of vertices")]
+    n: Option<u8>,
+
+    #[arg(short, long, help = "Only consider connected digraphs")]
+    connected: bool,
+
+    #[command(flatten)]
+    constraints: Constraints,
+}
+
+
+// Program options
+
+lazy_static! {
+
+    static ref ARGS: Args = Args::parse();
+
+    static ref GENERATE: fn(usize, &Filter, Option<&Func>,
+                            &mut Visit<io::Error>) -> io::Result<u64> =
+        match (ARGS.algorithm, ARGS.connected) {
+            (Algorithm::Reverse, true) => visit_comps_filtered_from,
+            (Algorithm::Reverse, false) => visit_funcs_filtered_from,
+            (Algorithm::Direct, true) => |n, _, _, visit| {
+                generate_comps_direct(n, &mut |g| visit(g, true))
+            },
+            (Algorithm::Direct, false) => |n, _, _, visit| {
+                generate_funcs_direct(n, &mut |g| visit(g, true))
+            },
+            (Algorithm::Augment, true) => |n, _, _, visit| {
+                generate_comps_augment(n, &mut |g| visit(g, true))
+            },
+            (Algorithm::Augment, false) => |n, _, _, visit| {
+                generate_funcs_augment(n, &mut |g| visit(g, true))
+            },
+        };
+
+    static ref FILTER: Filter = Filter {
+        slice: ARGS.slice,
+        ..ARGS.constraints.filter()
+    };
+
+    static ref COUNTS: CountCache = CountCache::new(if ARGS.no_cache {
+        None
+    } else {
+        default_cache_dir()
+    });
+
+    static ref FLUSH_EVERY: Option<u64> = if ARGS.unbuffered {
+        Some(1)
+    } else {
+        ARGS.flush_every
+    };
+
+    static ref FORMAT: Format = if ARGS.internal {
+        Format::Internal
+    } else {
+        ARGS.format
+    };
+
+    static ref PRINT_FUNC: Print = if ARGS.quiet && ARGS.checksum.is_none() {
+        print_nothing
+    } else {
+        printer(*FORMAT)
+    };
+
+}
+
+
+// Exit statuses, besides 0 for success and 2 for invalid arguments
+// (as for all errors reported by clap): 1 if a check fails (e.g., a
+// discrepancy found by verify, or duplicates removed by merge), and
+// otherwise those of sysexits.h for invalid input data, internal
+// errors, I/O errors and temporary failures (a generation stopped by
+// --time-limit, which can be resumed)
+
+const EXIT_FAILURE: i32 = 1;
+const EXIT_DATAERR: i32 = 65;
+const EXIT_SOFTWARE: i32 = 70;
+const EXIT_IOERR: i32 = 74;
+const EXIT_TEMPFAIL: i32 = 75;
+
+
+// Print error e and exit with the corresponding status
+
+fn exit_with(e: &Error) -> ! {
+    eprintln!("Error: {e}");
+    process::exit(match e {
+        Error::Parse(_) | Error::InvalidEntry(_) => EXIT_DATAERR,
+        Error::TooManyVertices(_) => EXIT_DATAERR,
+        Error::Internal(_) => EXIT_SOFTWARE,
+        Error::Io(_) => EXIT_IOERR,
+    })
+}
+
+
+// Exit if writing the output failed, printing the error; a broken
+// pipe (e.g., when piping the output into head) just means that no
+// more output is needed, so in that case exit silently and
+// successfully. The errors of the library passed through the
+// callbacks as I/O errors are reported as such
+
+fn exit_on_error(res: io::Result<()>) {
+    match res {
+        Ok(()) => (),
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => process::exit(0),
+        Err(e) => {
+            if let Some(e) = e.get_ref().and_then(|e| e.downcast_ref()) {
+                exit_with(e);
+            }
+            eprintln!("Error writing output: {e}");
+            process::exit(EXIT_IOERR);
+        }
+    }
+}
+
+
+// Compute the canonical code of the digraph having adjacency vector
+// a, as obtained from a code which is not necessarily canonical
+
+fn canonical(a: &[usize]) -> Func {
+    canon_map(a).unwrap_or_else(|e| exit_with(&Error::InvalidEntry(e)))
+}
+
+
+// Content type of the JSON responses of the server
+
+const JSON: &str = "application/json";
+
+
+// Time allowed to the clients for sending their requests, and for
+// accepting each write of the response, since each of them occupies
+// one of the threads of the server
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+
+// Interval between the progress reports, and number of digraphs
+// visited by the search between two checks of the clock
+
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+const PROGRESS_CHECK_EVERY: u64 = 4096;
+
+
+// Quote s as a JSON string
+
+fn json_string(s: &str) -> String {
+    let mut q = String::from("\"");
+    for ch in s.chars() {
+        match ch {
+            '"' => q.push_str("\\\""),
+            '\\' => q.push_str("\\\\"),
+            ch if (ch as u32) < 0x20 => {
+                q.push_str(&format!("\\u{:04x}", ch as u32));
+            }
+            ch => q.push(ch),
+        }
+    }
+    q.push('"');
+    q
+}
+
+
+// Format the counts of table as a JSON object, with the keys as
+// strings
+
+fn json_counts<K: Display, V: Display>(table: &BTreeMap<K, V>) -> String {
+    let items: Vec<_> = table.iter()
+        .map(|(k, v)| format!("{}:{v}", json_string(&k.to_string())))
+        .collect();
+    format!("{{{}}}", items.join(","))
+}
+
+
+// Format the counts of the values of an invariant as a JSON object,
+// with the values written as by Shown
+
+fn json_value_counts(table: &BTreeMap<Value, u64>) -> String {
+    json_counts(&table.iter().map(|(&v, &c)| (Shown(v), c))
+                .collect::<BTreeMap<_, _>>())
+}
+
+
+// JSON value, as read by parse_json: the numbers are kept as written,
+// and the other values which are not strings or objects (null, the
+// booleans and the arrays) are only checked to be valid
+
+enum Json {
+    Number(String),
+    String(String),
+    Object(Vec<(String, Json)>),
+    Other,
+}
+
+
+impl Json {
+
+    // Value of the field of the given name, if this is an object
+    // having it
+
+    fn get(&self, name: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter()
+                .find(|(key, _)| key == name).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_u64(&self) -> Option<u64> {
+        match self {
+            Json::Number(n) => n.parse().ok(),
+            _ => None,
+        }
+    }
+
+}
+
+
+// Parser of a JSON document, at position pos of s
+
+struct JsonParser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+
+impl JsonParser<'_> {
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.s[self.pos..];
+        self.pos += rest.len()
+            - rest.trim_start_matches([' ', '\t', '\n', '\r']).len();
+    }
+
+    // Skip the character c after the whitespace, if present
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        let found = self.s[self.pos..].starts_with(c);
+        if found {
+            self.pos += c.len_utf8();
+        }
+        found
+    }
+
+    fn value(&mut self) -> Option<Json> {
+        self.skip_whitespace();
+        let rest = &self.s[self.pos..];
+        if self.eat('{') {
+            let mut fields = Vec::new();
+            if !self.eat('}') {
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    if !self.eat(':') {
+                        return None;
+                    }
+                    fields.push((key, self.value()?));
+                    if self.eat('}') {
+                        break;
+                    } else if !self.eat(',') {
+                        return None;
+                    }
+                }
+            }
+            Some(Json::Object(fields))
+        } else if self.eat('[') {
+            if !self.eat(']') {
+                loop {
+                    self.value()?;
+                    if self.eat(']') {
+                        break;
+                    } else if !self.eat(',') {
+                        return None;
+                    }
+                }
+            }
+            Some(Json::Other)
+        } else if rest.starts_with('"') {
+            self.string().map(Json::String)
+        } else if let Some(word) = ["null", "true", "false"].into_iter()
+            .find(|word| rest.starts_with(word)) {
+            self.pos += word.len();
+            Some(Json::Other)
+        } else {
+            let len = rest.len() - rest.trim_start_matches(|c: char| {
+                c.is_ascii_digit() || "+-.eE".contains(c)
+            }).len();
+            self.pos += len;
+            (len > 0).then(|| Json::Number(rest[..len].to_string()))
+        }
+    }
+
+    // Parse a string, starting with its opening quote
+
+    fn string(&mut self) -> Option<String> {
+        let mut chars = self.s[self.pos..].char_indices();
+        if chars.next()?.1 != '"' {
+            return None;
+        }
+        let mut s = String::new();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Some(s);
+                }
+                '\\' => s.push(match chars.next()?.1 {
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'u' => {
+                        let hex: String = chars.by_ref().take(4)
+                            .map(|(_, c)| c).collect();
+                        if hex.len() != 4 {
+                            return None;
+                        }
+                        char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+                    }
+                    c @ ('"' | '\\' | '/') => c,
+                    _ => return None,
+                }),
+                c => s.push(c),
+            }
+        }
+        None
+    }
+
+}
+
+
+// Parse the JSON document s (a line with a JsonEvent, for instance)
+
+fn parse_json(s: &str) -> Option<Json> {
+    let mut parser = JsonParser { s, pos: 0 };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    (parser.pos == s.len()).then_some(value)
+}
+
+
+// Event for --log-format json, i.e., a JSON object printed on a single
+// line of stderr, whose first fields are the kind of event and the
+// time (in seconds since the Unix epoch)
+
+struct JsonEvent(Vec<String>);
+
+
+impl JsonEvent {
+
+    fn new(event: &str) -> JsonEvent {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map_or(0.0, |d| d.as_secs_f64());
+        JsonEvent(vec![format!("\"event\":{}", json_string(event)),
+                       format!("\"time\":{time:.3}")])
+    }
+
+    // Add a field whose value is already formatted as JSON (e.g., a
+    // number)
+
+    fn field(&mut self, name: &str, value: impl Display) -> &mut JsonEvent {
+        self.0.push(format!("{}:{value}", json_string(name)));
+        self
+    }
+
+    // Add a field whose value is a string
+
+    fn string(&mut self, name: &str, value: &str) -> &mut JsonEvent {
+        self.field(name, json_string(value))
+    }
+
+    fn print(&self) {
+        eprintln!("{{{}}}", self.0.join(","));
+    }
+
+}
+
+
+// Progress of the generation: the starting time, the time of the
+// last report, the interval between reports, the total number of
+// digraphs to be generated, if it can be computed in advance, and
+// whether the reports are log lines (for --log-progress) instead
+
+struct Progress {
+    start: Instant,
+    last: Instant,
+    interval: Duration,
+    total: Option<u128>,
+    log: bool,
+}
+
+
+impl Progress {
+
+    fn new(start: Instant, total: Option<u128>) -> Progress {
+        Progress { start, last: start, interval: PROGRESS_INTERVAL, total,
+                   log: false }
+    }
+
+    // Create a progress logger, reporting every interval
+
+    fn logger(start: Instant, interval: Duration) -> Progress {
+        Progress { start, last: start, interval, total: None, log: true }
+    }
+
+    // Report the progress on stderr, if it is time to do so, after
+    // count digraphs have been generated and visited ones have been
+    // visited by the search (including those rejected by constraints)
+
+    fn update(&mut self, count: u64, visited: u64) {
+        if !visited.is_multiple_of(PROGRESS_CHECK_EVERY)
+            || self.last.elapsed() < self.interval {
+            return;
+        }
+        self.last = Instant::now();
+        let elapsed = self.start.elapsed();
+        let rate = count as f64 / elapsed.as_secs_f64();
+        if ARGS.log_format == LogFormat::Json {
+            let mut e = JsonEvent::new("progress");
+            e.field("elapsed", format!("{:.3}", elapsed.as_secs_f64()))
+                .field("count", count).field("visited", visited)
+                .field("rate", format!("{rate:.1}"));
+            if let Some(total) = self.total {
+                e.field("total", total);
+            }
+            if let Some(rss) = current_rss() {
+                e.field("rss", rss);
+            }
+            e.print();
+            return;
+        }
+        if self.log {
+            let memory = current_rss()
+                .map_or("unavailable".to_string(), |b| b.to_string());
+            eprintln!("progress\telapsed {:.0}s\tcount {count}\t\
+                       visited {visited}\trate {rate:.0}/s\trss {memory}",
+                      elapsed.as_secs_f64());
+            return;
+        }
+        match self.total {
+            Some(total) => {
+                let remaining = total.saturating_sub(count as u128);
+                let left = Duration::from_secs_f64(remaining as f64 / rate);
+                eprintln!("[{:.2}%] {count} of {total} digraphs in \
+                           {elapsed:.2?}, {remaining} remaining (about \
+                           {left:.2?} left)",
+                          100.0 * count as f64 / total as f64);
+            }
+            None => eprintln!("{count} digraphs in {elapsed:.2?} \
+                               ({rate:.0} per second, {visited} \
+                               visited)"),
+        }
+    }
+
+}
+
+
+// Parse a rooted tree given in either format of the trees in the
+// codes, with its subtrees in any order, as its canonical code
+
+fn parse_forbidden_tree(s: &str) -> Result<Tree, String> {
+    let t = parse_tree(s).map_err(|e| e.msg)?;
+    Ok(canon_tree_code(&t))
+}
+
+
+// Parse a duration given as a number of seconds, minutes, hours or
+// days (with suffix s, m, h or d, respectively, or no suffix for
+// seconds), possibly with a fractional part, e.g., 2h or 1.5m
+
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (num, unit) = match s.char_indices().last() {
+        Some((i, 's')) => (&s[..i], 1.0),
+        Some((i, 'm')) => (&s[..i], 60.0),
+        Some((i, 'h')) => (&s[..i], 3600.0),
+        Some((i, 'd')) => (&s[..i], 86400.0),
+        _ => (s, 1.0),
+    };
+    num.parse::<f64>().ok()
+        .and_then(|x| Duration::try_from_secs_f64(x * unit).ok())
+        .ok_or_else(|| format!("invalid duration '{s}' (expected, e.g., \
+                                90s, 30m, 2h or 1d)"))
+}
+
+
+// Token for resuming a generation stopped by --time-limit: the rank
+// of the next digraph to be output, the value of the invariant for
+// the current pass, if the output is in sections (--group-output or
+// --by-components), followed by "new" if the header of its section
+// has not been output yet (with --group-output), and the key of the
+// digraph the search continues from, separated by colons, e.g.,
+// 1234:ABhQ, 1234:2:ABhQ or 1234:2:new:ABhQ
+
+struct ResumeToken {
+    rank: u64,
+    pass: Option<usize>,
+    new: bool,
+    start: Func,
+}
+
+
+impl Display for ResumeToken {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}:", self.rank)?;
+        if let Some(value) = self.pass {
+            write!(f, "{value}:")?;
+        }
+        if self.new {
+            write!(f, "new:")?;
+        }
+        write!(f, "{}", key(&self.start))
+    }
+}
+
+
+impl FromStr for ResumeToken {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ResumeToken, String> {
+        let err = || format!("invalid resume token '{s}'");
+        let fields: Vec<&str> = s.trim().split(':').collect();
+        let (rank, pass, new, code) = match fields[..] {
+            [rank, code] => (rank, None, false, code),
+            [rank, pass, code] => (rank, Some(pass), false, code),
+            [rank, pass, "new", code] => (rank, Some(pass), true, code),
+            _ => return Err(err()),
+        };
+        Ok(ResumeToken {
+            rank: rank.parse().map_err(|_| err())?,
+            pass: pass.map(str::parse).transpose().map_err(|_| err())?,
+            new,
+            start: parse_key(code).map_err(|_| err())?,
+        })
+    }
+}
+
+
+// Value in bytes of a field of /proc/self/status, such as VmHWM (the
+// peak resident set size of the process) or VmRSS (the current one),
+// if available (so only on Linux)
+
+fn proc_status_bytes(field: &str) -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with(field))?;
+    let kb: u64 = line[field.len()..].strip_prefix(':')?.trim()
+        .strip_suffix("kB")?.trim().parse().ok()?;
+    Some(kb * 1024)
+}
+
+
+// Peak resident set size of the process in bytes, if available
+
+fn peak_rss() -> Option<u64> {
+    proc_status_bytes("VmHWM")
+}
+
+
+// Current resident set size of the process in bytes, if available
+
+fn current_rss() -> Option<u64> {
+    proc_status_bytes("VmRSS")
+}
+
+
+// Set by the handler of SIGUSR1 and cleared when the status of the
+// generation is printed
+
+static STATUS_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+
+// Install a handler for SIGUSR1 requesting a status report (only on
+// Unix systems). The handler only sets a flag, which is the only kind
+// of work which is safe in a signal handler; the status is printed by
+// the generation loop at the next digraph visited by the search
+
+#[cfg(unix)]
+fn handle_status_signal() {
+    use std::ffi::c_int;
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const SIGUSR1: c_int = 10;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    const SIGUSR1: c_int = 30;
+
+    extern "C" {
+        fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+    }
+
+    extern "C" fn request_status(_: c_int) {
+        STATUS_REQUESTED.store(true, Relaxed);
+    }
+
+    unsafe {
+        signal(SIGUSR1, request_status);
+    }
+}
+
+
+#[cfg(not(unix))]
+fn handle_status_signal() {}
+
+
+// Print on the standard error the status of the generation, whose
+// current digraph is g, after count digraphs generated and visited
+// ones (including those rejected by the constraints) since start,
+// with the rank of the next digraph to be output: the depth in the
+// reverse search tree of the last component of g (the one changing
+// first) and the candidate merge of its parent it was obtained by,
+// as l..r
+
+fn print_status(g: &Func, count: u64, visited: u64, rank: u64,
+                start: Instant) {
+    let elapsed = start.elapsed();
+    let rate = count as f64 / elapsed.as_secs_f64();
+    let (depth, candidate) = g.last().map_or((0, None), |c| {
+        search_position(c)
+    });
+    if ARGS.log_format == LogFormat::Json {
+        let mut e = JsonEvent::new("status");
+        e.field("elapsed", format!("{:.3}", elapsed.as_secs_f64()))
+            .field("count", count).field("visited", visited)
+            .field("rank", rank)
+            .string("partition", &format!("{:?}", part(g)))
+            .field("depth", depth);
+        if let Some((l, r)) = candidate {
+            e.field("candidate", format!("[{l},{r}]"));
+        }
+        e.field("rate", format!("{rate:.1}")).print();
+    } else {
+        let candidate = candidate.map_or("none".to_string(), |(l, r)| {
+            format!("{l}..{r}")
+        });
+        eprintln!("status\telapsed {}s\tcount {count}\t\
+                   visited {visited}\trank {rank}\tpartition {:?}\t\
+                   depth {depth}\tcandidate {candidate}\t\
+                   rate {rate:.0}/s", elapsed.as_secs(), part(g));
+    }
+}
+
+
+// Width in characters of the longest bar of a histogram
+
+const HISTOGRAM_WIDTH: usize = 50;
+
+
+// Print on the standard error the histogram hist of invariant inv,
+// as a bar chart with one line per value; the bars are scaled to the
+// most frequent value, and any nonzero count gets at least one #
+
+fn print_histogram(inv: &dyn Invariant, hist: &BTreeMap<Value, u64>) {
+    let max = hist.values().copied().max().unwrap_or(1);
+    let total: u64 = hist.values().sum();
+    let value_width = hist.keys().map(|&v| Shown(v).to_string().len())
+        .max().unwrap_or(1);
+    let count_width = max.to_string().len();
+    eprintln!("{inv} histogram");
+    for (&value, &count) in hist {
+        let bar = (count as u128 * HISTOGRAM_WIDTH as u128)
+            .div_ceil(max as u128) as usize;
+        let value = Shown(value);
+        eprintln!("{value:>value_width$} |{:<HISTOGRAM_WIDTH$} \
+                   {count:>count_width$} ({:.2}%)", "#".repeat(bar),
+                  100.0 * count as f64 / total as f64);
+    }
+}
+
+
+// Estimate the memory in bytes taken by the code of g, counting the
+// components and trees shared by several positions only once
+
+fn code_bytes(g: &Func) -> usize {
+    let rc = 2 * mem::size_of::<usize>();
+    let mut seen = HashSet::new();
+    let mut bytes = mem::size_of::<Func>()
+        + g.capacity() * mem::size_of::<Rc<Comp>>();
+    for c in g {
+        if seen.insert(Rc::as_ptr(c) as usize) {
+            bytes += rc + mem::size_of::<Comp>()
+                + c.capacity() * mem::size_of::<Rc<Tree>>();
+            for t in c.iter() {
+                if seen.insert(Rc::as_ptr(t) as usize) {
+                    bytes += rc + mem::size_of::<Tree>() + t.capacity();
+                }
+            }
+        }
+    }
+    bytes
+}
+
+
+// Estimate the memory in bytes taken by the entries of table t
+
+fn table_bytes<K, V>(t: &BTreeMap<K, V>) -> usize {
+    t.len() * (mem::size_of::<K>() + mem::size_of::<V>())
+}
+
+
+// Compute the number of digraphs (or connected ones) with n vertices
+// generated with the current options, if it is given by the counting
+// formulas, i.e., if no constraint is set, the digraphs are not
+// pointed and the generation is not resumed
+
+fn expected_total(n: usize) -> Option<u128> {
+    if ARGS.pointed || !FILTER.is_unconstrained() || ARGS.resume.is_some()
+        || ARGS.start_from.is_some() {
+        return None;
+    }
+    if ARGS.connected {
+        COUNTS.comp_counts(n)
+            .map(|c| if n == 0 { 0 } else { c[n].iter().sum() })
+    } else {
+        COUNTS.func_counts(n).map(|f| f[n].iter().sum())
+    }
+}
+
+
+// Generate random digraphs with a Boltzmann sampler, which draws
+// digraphs of random size with all digraphs of the same size being
+// equally likely, and rejects those of the wrong size
+
+fn sample(args: &SampleArgs) {
+    let n = args.size as usize;
+    if n == 0 && args.connected {
+        Args::command().error(ErrorKind::ValueValidation,
+                              "there are no connected digraphs \
+                               with 0 vertices")
+            .exit();
+    }
+    let (min, max) = if args.approx {
+        (((1.0 - args.tolerance) * n as f64).ceil() as usize,
+         ((1.0 + args.tolerance) * n as f64).floor() as usize)
+    } else {
+        (n, n)
+    };
+    if args.tolerance < 0.0 {
+        Args::command().error(ErrorKind::ValueValidation,
+                              "the tolerance cannot be negative")
+            .exit();
+    }
+    let seed = args.seed.unwrap_or_else(|| {
+        SystemTime::now().duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64)
+    });
+    #[cfg(feature = "arrow")]
+    if args.format == Format::Parquet && args.threads > 1 {
+        Args::command().error(ErrorKind::ArgumentConflict,
+                              "--threads cannot be used with -f parquet")
+            .exit();
+    }
+    let now = Instant::now();
+    let sampler = Sampler::new(n as f64, args.connected);
+    let print = printer(args.format);
+    let mut out = Output::new(None);
+    if args.threads > 1 {
+        let res = sample_parallel(&sampler, &mut out, print,
+                                  seed, args.threads, args.count,
+                                  (min, max.min(MAX_SIZE)));
+        exit_on_error(res.and_then(|_| out.finish()));
+        let time = now.elapsed();
+        eprintln!("{} digraphs sampled in {time:.2?} (seed {seed}, \
+                   {} threads)", args.count, args.threads);
+        return;
+    }
+    let mut rng = Rng::stream(seed, 0);
+    #[cfg(feature = "arrow")]
+    let mut parquet = (args.format == Format::Parquet)
+        .then(|| ParquetWriter::new(false, &default_invariants()));
+    let mut res = Ok(());
+    for _ in 0..args.count {
+        let g = sampler.sample(&mut rng, min, max.min(MAX_SIZE));
+        res = print(&mut out, &g, None);
+        #[cfg(feature = "arrow")]
+        if let (Ok(()), Some(w)) = (&res, &mut parquet) {
+            res = w.write(&mut out, &g, None);
+        }
+        if res.is_err() {
+            break;
+        }
+    }
+    #[cfg(feature = "arrow")]
+    if let (Ok(()), Some(w)) = (&res, parquet) {
+        res = w.finish(&mut out);
+    }
+    exit_on_error(res.and_then(|_| out.finish()));
+    let time = now.elapsed();
+    eprintln!("{} digraphs sampled in {time:.2?} (seed {seed})", args.count);
+}
+
+
+// Number of digraphs in each batch drawn by a thread of the sample
+// subcommand, and maximum number of batches waiting to be written
+
+const SAMPLE_BATCH_SIZE: u64 = 64;
+const SAMPLE_QUEUE_LENGTH: usize = 16;
+
+
+// Draw count digraphs, of size in the range (min, max), with several
+// threads: the digraphs are divided into batches, assigned to the
+// threads in round-robin order, and thread j draws the digraphs of
+// all its batches from stream j of the seed. The batches are written
+// in order as they become available, so the output is completely
+// determined by the seed and by the number of threads. The digraphs
+// are formatted by the threads, since they cannot be sent
+
+fn sample_parallel(sampler: &Sampler, out: &mut Output, print: Print,
+                   seed: u64, threads: u64, count: u64,
+                   (min, max): (usize, usize)) -> io::Result<()> {
+    let batches = count.div_ceil(SAMPLE_BATCH_SIZE);
+    thread::scope(|scope| {
+        let mut receivers = Vec::new();
+        for j in 0..threads.min(batches) {
+            let (sender, receiver) =
+                sync_channel::<io::Result<Vec<u8>>>(SAMPLE_QUEUE_LENGTH);
+            receivers.push(receiver);
+            scope.spawn(move || {
+                let mut rng = Rng::stream(seed, j);
+                for b in (j..batches).step_by(threads as usize) {
+                    let k = SAMPLE_BATCH_SIZE
+                        .min(count - b * SAMPLE_BATCH_SIZE);
+                    let mut buf = Vec::new();
+                    let res = (0..k).try_for_each(|_| {
+                        let g = sampler.sample(&mut rng, min, max);
+                        print(&mut buf, &g, None)
+                    });
+                    // A send error means that the output has stopped
+                    if sender.send(res.map(|_| buf)).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+        let res = (0..batches).try_for_each(|b| {
+            let r = &receivers[(b % threads) as usize];
+            out.write_all(&r.recv().unwrap()?)
+        });
+        drop(receivers);
+        res
+    })
+}
+
+
+// Print the number of rooted trees, components and functional
+// digraphs of each size up to args.size (the coefficients of their
+// generating functions) as a table; with args.cycle_points, print
+// the number of components and digraphs of each size n with each
+// number k of cycle points instead, with args.fixed_points the
+// number of digraphs of each size n with k fixed points, and with
+// args.components the number of those with m components
+
+fn gf(args: &GfArgs) {
+    let n = args.size;
+    let (Some(t), Some(c), Some(f), Some(z), Some(p)) =
+        (COUNTS.tree_counts(n), COUNTS.comp_counts(n),
+         COUNTS.func_counts(n), COUNTS.fixed_point_counts(n),
+         COUNTS.component_number_counts(n)) else {
+        Args::command().error(ErrorKind::ValueValidation,
+                              format!("the coefficients for {n} vertices \
+                                       do not fit in 128 bits"))
+            .exit();
+    };
+    let s = if args.csv { "," } else { "\t" };
+    let mut out = Output::new(None);
+    let res = (|| {
+        if args.cycle_points {
+            writeln!(out, "n{s}k{s}components{s}digraphs")?;
+            for m in 0..=n {
+                for k in 0..=m {
+                    writeln!(out, "{m}{s}{k}{s}{}{s}{}", c[m][k], f[m][k])?;
+                }
+            }
+        } else if args.fixed_points || args.components {
+            let (table, var) = if args.components { (p, "m") } else {
+                (z, "k")
+            };
+            writeln!(out, "n{s}{var}{s}digraphs")?;
+            for (m, row) in table.iter().enumerate() {
+                for (k, count) in row[..=m].iter().enumerate() {
+                    writeln!(out, "{m}{s}{k}{s}{count}")?;
+                }
+            }
+        } else {
+            writeln!(out, "n{s}trees{s}components{s}digraphs")?;
+            for m in 0..=n {
+                let cm: u128 = c[m].iter().sum();
+                let fm: u128 = f[m].iter().sum();
+                writeln!(out, "{m}{s}{}{s}{cm}{s}{fm}", t[m])?;
+            }
+        }
+        Ok(())
+    })();
+    exit_on_error(res.and_then(|_| out.finish()));
+}
+
+
+// Write to out an OEIS b-file (lines "n a(n)", as described at
+// https://oeis.org/wiki/B-files) with the number of digraphs (or
+// connected digraphs) with n vertices satisfying the constraints, for
+// n from args.first to args.last; the numbers are taken from counts if
+// given, otherwise the digraphs are generated (but not printed)
+
+fn write_bfile(out: &mut dyn Write, args: &BfileArgs,
+               counts: Option<&[Vec<u128>]>) -> io::Result<()> {
+    let filter = args.constraints.filter();
+    let what = if args.connected { "connected " } else { "" };
+    let constrained = if filter.is_unconstrained() {
+        ""
+    } else {
+        " satisfying the constraints"
+    };
+    let command: Vec<_> = env::args().skip(1).collect();
+    writeln!(out, "# Number of {what}functional digraphs with n \
+                   vertices{constrained}")?;
+    writeln!(out, "# Computed by funkdigen2 {} with: funkdigen2 {}",
+             env!("CARGO_PKG_VERSION"), command.join(" "))?;
+    let mut range = RangeGenerator::new(&filter);
+    for n in args.first as usize..=args.last as usize {
+        let mut emit = |_: &Func| Ok::<(), io::Error>(());
+        let count = match counts {
+            Some(c) => c[n].iter().sum(),
+            None if args.connected => {
+                generate_comps_filtered(n, &filter, &mut emit)? as u128
+            }
+            None => range.generate(n, &mut emit)? as u128,
+        };
+        writeln!(out, "{n} {count}")?;
+    }
+    Ok(())
+}
+
+
+// Write the b-file of args to the standard output or to args.output;
+// without constraints, the numbers are computed from the generating
+// functions (so they are available up to 80 vertices), otherwise the
+// digraphs of each size are generated, keeping the components of the
+// smaller sizes from one size to the next
+
+fn bfile(args: &BfileArgs) {
+    if args.first > args.last {
+        Args::command().error(ErrorKind::ValueValidation,
+                              "--first cannot be larger than the last \
+                               index")
+            .exit();
+    }
+    let last = args.last as usize;
+    let counts = if args.constraints.filter().is_unconstrained() {
+        let counts = if args.connected {
+            COUNTS.comp_counts(last)
+        } else {
+            COUNTS.func_counts(last)
+        };
+        if counts.is_none() {
+            Args::command().error(ErrorKind::ValueValidation,
+                                  format!("the numbers for {last} vertices \
+                                           do not fit in 128 bits"))
+                .exit();
+        }
+        counts
+    } else {
+        None
+    };
+    let now = Instant::now();
+    match &args.output {
+        Some(path) => {
+            let res = File::create(path).and_then(|file| {
+                let mut out = BufWriter::new(file);
+                write_bfile(&mut out, args, counts.as_deref())?;
+                out.flush()
+            });
+            if let Err(e) = res {
+                if let Some(e) = e.get_ref().and_then(|e| e.downcast_ref()) {
+                    exit_with(e);
+                }
+                eprintln!("Error writing {}: {e}", path.display());
+                process::exit(EXIT_IOERR);
+            }
+        }
+        None => {
+            let mut out = Output::new(None);
+            let res = write_bfile(&mut out, args, counts.as_deref());
+            exit_on_error(res.and_then(|_| out.finish()));
+        }
+    }
+    eprintln!("b-file for n = {} to {last} computed in {:.2?}", args.first,
+              now.elapsed());
+}
+
+
+// Interval between the progress reports of the distribute subcommand
+
+const DISTRIBUTE_REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+
+// Worker process of the distribute subcommand, generating a slice of
+// the digraphs, with the thread collecting its stderr
+
+struct Worker {
+    slice: Slice,
+    host: Option<String>,
+    file: Option<PathBuf>,
+    child: Child,
+    stderr: Option<JoinHandle<String>>,
+}
+
+
+// Quote s for a POSIX shell (for passing arguments through ssh)
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+
+// Parse a cycle type as written by its Display implementation
+
+fn parse_cycle_type(s: &str) -> Option<CycleType> {
+    let mut t = Vec::new();
+    for item in s.split(',').filter(|item| !item.is_empty()) {
+        let (l, m) = item.split_once('^')?;
+        let (l, m): (usize, usize) = (l.parse().ok()?, m.parse().ok()?);
+        if t.len() <= l {
+            t.resize(l + 1, 0);
+        }
+        t[l] += m;
+    }
+    Some(CycleType(t))
+}
+
+
+// Tables of the workers of distribute, merged by adding their counts:
+// those of --group-by and --histogram keyed by the name of their
+// invariant, and those of --iterate-cycles by the iterate, so that
+// tables with different headers are never combined
+
+#[derive(Default)]
+struct MergedTables {
+    groups: BTreeMap<String, BTreeMap<Value, u64>>,
+    histograms: BTreeMap<String, BTreeMap<Value, u64>>,
+    cycle_types: BTreeMap<u64, BTreeMap<CycleType, u64>>,
+}
+
+
+impl MergedTables {
+
+    // Add the counts of the JSON object counts, whose keys are parsed
+    // by parse, to table
+
+    fn add<K: Ord>(table: &mut BTreeMap<K, u64>, counts: &Json,
+                   parse: impl Fn(&str) -> Option<K>) -> Option<()> {
+        let Json::Object(fields) = counts else {
+            return None;
+        };
+        for (key, count) in fields {
+            *table.entry(parse(key)?).or_insert(0) += count.as_u64()?;
+        }
+        Some(())
+    }
+
+    // Add the tables of the summary of a worker (the JsonEvent printed
+    // at the end of the generation), failing if one of them cannot be
+    // read
+
+    fn add_summary(&mut self, summary: &Json) -> Option<()> {
+        if let Some(name) = summary.get("group_by") {
+            let table = self.groups.entry(name.as_str()?.to_string())
+                .or_default();
+            MergedTables::add(table, summary.get("groups")?,
+                              parse_value)?;
+        }
+        if let Some(name) = summary.get("histogram") {
+            let table = self.histograms.entry(name.as_str()?.to_string())
+                .or_default();
+            MergedTables::add(table, summary.get("histogram_counts")?,
+                              parse_value)?;
+        }
+        if let Some(k) = summary.get("iterate_cycles") {
+            let table = self.cycle_types.entry(k.as_u64()?).or_default();
+            MergedTables::add(table, summary.get("cycle_types")?,
+                              parse_cycle_type)?;
+        }
+        Some(())
+    }
+
+    // Print the merged tables on stderr, as at the end of the
+    // generation
+
+    fn print(&self) {
+        for (name, groups) in &self.groups {
+            eprintln!("{name}\tcount");
+            for (&value, count) in groups {
+                eprintln!("{}\t{count}", Shown(value));
+            }
+        }
+        for (name, histogram) in &self.histograms {
+            match find_invariant(name) {
+                Ok(inv) => print_histogram(inv, histogram),
+                Err(_) => eprintln!("Unknown invariant {name}"),
+            }
+        }
+        for (k, cycle_types) in &self.cycle_types {
+            eprintln!("f^{k} cycle type\tcount");
+            for (t, count) in cycle_types {
+                eprintln!("{t}\t{count}");
+            }
+        }
+    }
+
+}
+
+
+// Parse the stderr of a worker, run with --log-format json, returning
+// the number of digraphs generated if its summary is found and its
+// tables can be added to tables
+
+fn parse_report(report: &str, tables: &mut MergedTables) -> Option<u64> {
+    let summary = report.lines().rev().filter_map(parse_json)
+        .find(|e| e.get("event").and_then(Json::as_str) == Some("summary"))?;
+    tables.add_summary(&summary)?;
+    summary.get("count")?.as_u64()
+}
+
+
+// Options of the workers of distribute whose output cannot be merged
+// (or which would change the format of the reports read by it)
+
+const UNMERGEABLE_OPTIONS: [&str; 3] =
+    ["--stats", "--report-memory", "--log-format"];
+
+
+// Run the generation in several worker processes (instances of this
+// program, locally or via ssh), each one generating a different
+// slice of the digraphs, report their progress and merge their
+// counts; the output of each worker is written to its own file,
+// listed in a manifest together with the counts
+
+fn distribute(args: &DistributeArgs) {
+    let unmergeable = args.args.iter().find(|arg| {
+        UNMERGEABLE_OPTIONS.iter().any(|opt| {
+            arg.strip_prefix(opt)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('='))
+        })
+    });
+    if let Some(arg) = unmergeable {
+        let opt = arg.split('=').next().unwrap_or(arg);
+        Args::command().error(ErrorKind::ArgumentConflict,
+                              format!("{opt} cannot be passed to the \
+                                       workers of distribute, whose \
+                                       summaries must be merged"))
+            .exit()
+    }
+    let modulus = if args.hosts.is_empty() {
+        args.jobs.unwrap_or_else(|| {
+            thread::available_parallelism().map_or(1, |n| n.get())
+        })
+    } else {
+        args.hosts.len()
+    }.max(1);
+    if let Some(dir) = &args.output_dir {
+        if let Err(e) = fs::create_dir_all(dir) {
+            eprintln!("Error creating {}: {e}", dir.display());
+            process::exit(EXIT_IOERR);
+        }
+    }
+    let exe = env::current_exe().unwrap_or_else(|_| "funkdigen2".into());
+    let now = Instant::now();
+    let mut workers = Vec::new();
+    for res in 0..modulus {
+        let slice = Slice { res: res as u64, modulus: modulus as u64 };
+        let host = args.hosts.get(res).cloned();
+        let mut cmd = match &host {
+            Some(host) => {
+                let mut cmd = process::Command::new("ssh");
+                let mut remote = args.remote_command.clone();
+                for arg in &args.args {
+                    remote += &format!(" {}", shell_quote(arg));
+                }
+                remote += &format!(" --slice {slice} --log-format json");
+                cmd.arg(host).arg(remote);
+                cmd
+            }
+            None => {
+                let mut cmd = process::Command::new(&exe);
+                cmd.args(&args.args).arg("--slice").arg(slice.to_string())
+                    .args(["--log-format", "json"]);
+                cmd
+            }
+        };
+        let file = args.output_dir.as_ref()
+            .map(|dir| dir.join(format!("slice-{res}-of-{modulus}.out")));
+        let stdout = match &file {
+            Some(file) => match File::create(file) {
+                Ok(f) => Stdio::from(f),
+                Err(e) => {
+                    eprintln!("Error writing {}: {e}", file.display());
+                    process::exit(EXIT_IOERR);
+                }
+            },
+            None => Stdio::null(),
+        };
+        let mut child = match cmd.stdout(stdout).stderr(Stdio::piped())
+            .spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                eprintln!("Error starting worker {slice}: {e}");
+                process::exit(EXIT_FAILURE);
+            }
+        };
+        let mut pipe = child.stderr.take().unwrap();
+        let stderr = thread::spawn(move || {
+            let mut s = String::new();
+            let _ = pipe.read_to_string(&mut s);
+            s
+        });
+        workers.push(Worker { slice, host, file, child,
+                              stderr: Some(stderr) });
+    }
+    let mut total = 0;
+    let mut failed = false;
+    let mut tables = MergedTables::default();
+    let mut manifest = String::from("slice\thost\tfile\tdigraphs\tbytes\n");
+    let mut done = 0;
+    let mut last = Instant::now();
+    while done < modulus {
+        thread::sleep(Duration::from_millis(100));
+        if last.elapsed() >= DISTRIBUTE_REPORT_INTERVAL {
+            last = Instant::now();
+            let bytes: u64 = workers.iter()
+                .filter_map(|w| w.file.as_ref())
+                .filter_map(|file| fs::metadata(file).ok())
+                .map(|m| m.len())
+                .sum();
+            eprintln!("[{done}/{modulus}] {bytes} bytes written \
+                       in {:.2?}", now.elapsed());
+        }
+        for w in workers.iter_mut().filter(|w| w.stderr.is_some()) {
+            let Ok(Some(status)) = w.child.try_wait() else {
+                continue;
+            };
+            done += 1;
+            let report = w.stderr.take().unwrap().join().unwrap();
+            let count = parse_report(&report, &mut tables);
+            let host = w.host.as_deref().unwrap_or("localhost");
+            match count {
+                Some(count) if status.success() => {
+                    total += count;
+                    eprintln!("[{done}/{modulus}] slice {} on {host}: \
+                               {count} digraphs in {:.2?}",
+                              w.slice, now.elapsed());
+                }
+                _ => {
+                    failed = true;
+                    eprintln!("[{done}/{modulus}] slice {} on {host} \
+                               failed ({status}):", w.slice);
+                    eprint!("{report}");
+                }
+            }
+            let (file, bytes) = match &w.file {
+                Some(file) => (file.display().to_string(),
+                               fs::metadata(file).map_or(0, |m| m.len())),
+                None => ("-".to_string(), 0),
+            };
+            manifest += &format!("{}\t{host}\t{file}\t{}\t{bytes}\n",
+                                 w.slice, count.map_or("-".to_string(),
+                                                       |c| c.to_string()));
+        }
+    }
+    if let Some(dir) = &args.output_dir {
+        let path = dir.join("manifest.tsv");
+        if let Err(e) = fs::write(&path, manifest) {
+            eprintln!("Error writing {}: {e}", path.display());
+            process::exit(EXIT_IOERR);
+        }
+    }
+    if failed {
+        process::exit(EXIT_FAILURE);
+    }
+    let time = now.elapsed();
+    eprintln!("{total} digraphs generated in {time:.2?} \
+               by {modulus} workers");
+    tables.print();
+}
+
+
+// Parse a digraph in any of the textual formats (except for the
+// forests one) or as a function table, converting those in digraph6
+// format and the function tables into their isomorphism codes
+
+fn parse_record(s: &str) -> Result<Func, ParseError> {
+    let f = if s.starts_with('&') {
+        parse_digraph6(s)?.iter().map(|&w| w as usize).collect()
+    } else if is_function_table(s) {
+        parse_function_table(s)?
+    } else {
+        return parse_func(s);
+    };
+    // Both parsers only return valid maps, so this cannot fail
+    canon_map(&f).map_err(|e| ParseError { pos: 0, msg: e.msg })
+}
+
+
+// Parse a digraph as parse_record, but as a map on its vertices,
+// keeping the names of the vertices of the digraph6 codes and of the
+// function tables; the other codes are converted into the adjacency
+// vectors of their representatives (with vertices named as in
+// digraph6 format)
+
+fn parse_map(s: &str) -> Result<Vec<usize>, ParseError> {
+    let a = if s.starts_with('&') {
+        parse_digraph6(s)?
+    } else if is_function_table(s) {
+        return parse_function_table(s);
+    } else {
+        func_adj(&parse_func(s)?)
+    };
+    Ok(a.iter().map(|&w| w as usize).collect())
+}
+
+
+// Input file of digraphs in any of the textual formats (except for
+// pointed digraphs) or as function tables, with its current record
+// (i.e., line or NUL-terminated string), the number of that record,
+// the number of digraphs read so far and of the invalid records
+// skipped if lenient is true
+
+struct Records {
+    name: String,
+    reader: Box<dyn BufRead>,
+    record: Vec<u8>,
+    pos: u64,
+    count: u64,
+    lenient: bool,
+    skipped: u64,
+}
+
+
+impl Records {
+
+    // Open the file at path (standard input for -); exit with an error
+    // if it cannot be opened
+
+    fn open(path: &Path, lenient: bool) -> Records {
+        let name = path.display().to_string();
+        let reader: Box<dyn BufRead> = if name == "-" {
+            Box::new(io::stdin().lock())
+        } else {
+            match File::open(path) {
+                Ok(file) => Box::new(BufReader::new(file)),
+                Err(e) => {
+                    eprintln!("Error reading {name}: {e}");
+                    process::exit(EXIT_IOERR);
+                }
+            }
+        };
+        Records {
+            name, reader, record: Vec::new(), pos: 0, count: 0,
+            lenient, skipped: 0,
+        }
+    }
+
+    // Read the next nonempty record, terminated by term, returning
+    // its digraph; those in digraph6 format and the function tables
+    // are converted into their isomorphism codes. Exit with an error
+    // if the input cannot be read or parsed, reporting the line (or
+    // record, for NUL-terminated ones) and the column of the error;
+    // if lenient is true, the records which cannot be parsed are
+    // skipped with a warning instead
+
+    fn next(&mut self, term: u8) -> Option<Func> {
+        loop {
+            self.record.clear();
+            match self.reader.read_until(term, &mut self.record) {
+                Ok(0) => return None,
+                Ok(_) => self.pos += 1,
+                Err(e) => {
+                    eprintln!("Error reading {}: {e}", self.name);
+                    process::exit(EXIT_IOERR);
+                }
+            }
+            let record = String::from_utf8_lossy(&self.record);
+            let s = record.trim();
+            if s.is_empty() {
+                continue;
+            }
+            match parse_record(s) {
+                Ok(g) => {
+                    self.count += 1;
+                    return Some(g);
+                }
+                Err(e) => {
+                    // Columns are counted in characters from 1, also
+                    // including the leading whitespace
+                    let start = record.len() - record.trim_start().len();
+                    let pos = start + e.pos.min(s.len());
+                    let column = record.get(..pos)
+                        .map_or(pos, |p| p.chars().count()) + 1;
+                    let unit = if term == b'\n' { "line" } else { "record" };
+                    let at = format!("{}, {unit} {}, column {column}",
+                                     self.name, self.pos);
+                    if !self.lenient {
+                        eprintln!("Error in {at}: {}", e.msg);
+                        process::exit(EXIT_DATAERR);
+                    }
+                    eprintln!("Warning: skipping invalid record in {at}: \
+                               {}", e.msg);
+                    self.skipped += 1;
+                }
+            }
+        }
+    }
+
+}
+
+
+// Merge files of digraphs, each one in generation order (e.g., the
+// slices generated by different processes) into a single list in
+// generation order; the records are copied unchanged, and they can
+// be in any of the textual formats (except that pointed digraphs are
+// not supported). Only the current record of each file is kept in
+// memory. Any duplicates are reported and removed, and then the
+// exit status is 1
+
+fn merge(args: &MergeArgs) {
+    let now = Instant::now();
+    let term = if args.print0 { b'\0' } else { b'\n' };
+    let mut inputs: Vec<Records> = args.files.iter()
+        .map(|path| Records::open(path, args.lenient))
+        .collect();
+    let mut heap = BinaryHeap::new();
+    for (i, input) in inputs.iter_mut().enumerate() {
+        if let Some(g) = input.next(term) {
+            heap.push(Reverse((FuncKey::new(&g), i)));
+        }
+    }
+    let mut out = Output::new(None);
+    let mut last: Option<(FuncKey, usize, u64)> = None;
+    let mut count: u64 = 0;
+    let mut duplicates: u64 = 0;
+    let mut res = Ok(());
+    while let Some(Reverse((key, i))) = heap.pop() {
+        let input = &inputs[i];
+        let pos = input.pos;
+        let duplicate = match &last {
+            Some((k, j, p)) if *k == key => {
+                eprintln!("Duplicate digraph in {}, record {pos} \
+                           (also in {}, record {p})",
+                          input.name, inputs[*j].name);
+                true
+            }
+            _ => false,
+        };
+        if duplicate {
+            duplicates += 1;
+        } else {
+            count += 1;
+            res = out.write_all(input.record.trim_ascii())
+                .and_then(|_| out.write_all(&[term]));
+            if res.is_err() {
+                break;
+            }
+        }
+        let input = &mut inputs[i];
+        if let Some(next) = input.next(term).map(|g| FuncKey::new(&g)) {
+            if next < key {
+                eprintln!("Error in {}, record {}: not in generation order",
+                          input.name, input.pos);
+                process::exit(EXIT_DATAERR);
+            }
+            heap.push(Reverse((next, i)));
+        }
+        if !duplicate {
+            last = Some((key, i, pos));
+        }
+    }
+    exit_on_error(res.and_then(|_| out.finish()));
+    let time = now.elapsed();
+    eprintln!("{count} digraphs merged from {} files in {time:.2?} \
+               ({duplicates} duplicates removed)", inputs.len());
+    eprintln!("file\tcount");
+    for input in &inputs {
+        eprintln!("{}\t{}", input.name, input.count);
+    }
+    if duplicates > 0 {
+        process::exit(EXIT_FAILURE);
+    }
+}
+
+
+// Check that the file given in args contains exactly the digraphs
+// (in any textual format) generated for args.size vertices, in
+// generation order; the expected digraphs are computed one at a time
+// from their predecessors, so only the current one is kept in memory.
+// The first discrepancy found is reported, with exit status 1: a
+// digraph of the wrong size or of the wrong kind, a non-canonical
+// code, a duplicate, a digraph out of order, a missing one, or the
+// end of the file before the last digraph or after it
+
+fn verify(args: &VerifyArgs) {
+    let now = Instant::now();
+    let n = args.size as usize;
+    let term = if args.print0 { b'\0' } else { b'\n' };
+    let successor = |g: &Func| if args.connected {
+        next_comp(&g[0]).map(|c| vec![Rc::new(c)])
+    } else {
+        next_func(g)
+    };
+    let mut input = Records::open(&args.file, args.lenient);
+    let mut expected = if args.connected {
+        (n > 0).then(|| vec![Rc::new(cycle(n))])
+    } else {
+        Some(loops(n))
+    };
+    let mut prev: Option<Func> = None;
+    let fail = |input: &Records, msg: String| -> ! {
+        eprintln!("Error in {}, record {}: {msg}", input.name, input.pos);
+        process::exit(EXIT_FAILURE);
+    };
+    while let Some(g) = input.next(term) {
+        let Some(e) = expected else {
+            fail(&input, format!("unexpected digraph {} after the last one",
+                                 Pretty(&g)));
+        };
+        if g != e {
+            let key = FuncKey::new(&g);
+            let canon = canonical(&func_adj(&g).iter()
+                                  .map(|&w| w as usize)
+                                  .collect::<Vec<_>>());
+            let msg = if key.size() != n {
+                format!("digraph {} with {} vertices instead of {n}",
+                        Pretty(&g), key.size())
+            } else if args.connected && g.len() != 1 {
+                format!("disconnected digraph {}", Pretty(&g))
+            } else if canon != g {
+                format!("non-canonical code {} (instead of {})",
+                        Pretty(&g), Pretty(&canon))
+            } else if prev.as_ref() == Some(&g) {
+                format!("duplicate digraph {}", Pretty(&g))
+            } else if key < FuncKey::new(&e) {
+                format!("digraph {} out of order (expected {})",
+                        Pretty(&g), Pretty(&e))
+            } else {
+                format!("missing digraph {} (found {} instead)",
+                        Pretty(&e), Pretty(&g))
+            };
+            fail(&input, msg);
+        }
+        expected = successor(&g);
+        prev = Some(g);
+    }
+    if let Some(e) = expected {
+        input.pos += 1;
+        fail(&input, format!("end of file instead of digraph {}",
+                             Pretty(&e)));
+    }
+    let time = now.elapsed();
+    eprintln!("{} digraphs verified in {time:.2?}", input.count);
+}
+
+
+// Read the isomorphism classes of the digraphs in input, i.e., their
+// canonical codes (whatever the format of the records and even if the
+// codes are not canonical), each with its first record; also return
+// the number of duplicate records, i.e., those of a class already read
+
+fn read_classes(input: &mut Records, term: u8)
+                -> (BTreeMap<Code, Vec<u8>>, u64) {
+    let mut classes = BTreeMap::new();
+    let mut duplicates = 0;
+    while let Some(g) = input.next(term) {
+        let canon = canonical(&func_adj(&g).iter().map(|&w| w as usize)
+                              .collect::<Vec<_>>());
+        let record = input.record.trim_ascii().to_vec();
+        if classes.insert(Code(canon), record).is_some() {
+            duplicates += 1;
+        }
+    }
+    (classes, duplicates)
+}
+
+
+// Compare the files of digraphs given in args (each in any textual
+// format, and in any order) up to isomorphism, printing the records
+// of the classes present in only one of them, in generation order,
+// prefixed by < for the first file and by > for the second one, as
+// diff does; the exit status is 1 if any class is missing from either
+// file. Both sets of classes are kept in memory
+
+fn diff(args: &DiffArgs) {
+    let now = Instant::now();
+    let term = if args.print0 { b'\0' } else { b'\n' };
+    let mut input1 = Records::open(&args.file1, args.lenient);
+    let (classes1, duplicates1) = read_classes(&mut input1, term);
+    let mut input2 = Records::open(&args.file2, args.lenient);
+    let (classes2, duplicates2) = read_classes(&mut input2, term);
+    let mut only: Vec<(&Code, &[u8], &[u8])> = classes1.iter()
+        .filter(|(code, _)| !classes2.contains_key(code))
+        .map(|(code, r)| (code, b"< ".as_slice(), r.as_slice()))
+        .collect();
+    let only1 = only.len();
+    only.extend(classes2.iter()
+                .filter(|(code, _)| !classes1.contains_key(code))
+                .map(|(code, r)| (code, b"> ".as_slice(), r.as_slice())));
+    let only2 = only.len() - only1;
+    only.sort_by_key(|&(code, _, _)| code);
+    let mut out = Output::new(None);
+    let res = only.iter().try_for_each(|(_, side, record)| {
+        out.write_all(side)?;
+        out.write_all(record)?;
+        out.write_all(&[term])
+    });
+    exit_on_error(res.and_then(|_| out.finish()));
+    let time = now.elapsed();
+    eprintln!("{} classes compared in {time:.2?}: {} in both files, {only1} \
+               only in {}, {only2} only in {}",
+              classes1.len() + only2, classes1.len() - only1,
+              input1.name, input2.name);
+    for (input, duplicates) in [(&input1, duplicates1),
+                                (&input2, duplicates2)] {
+        if duplicates > 0 {
+            eprintln!("{}: {duplicates} duplicate records of the same \
+                       classes", input.name);
+        }
+    }
+    if only1 > 0 || only2 > 0 {
+        process::exit(EXIT_FAILURE);
+    }
+}
+
+
+// Print the frequency table of the isomorphism classes of the
+// digraphs in the file given in args (in any textual format, or as
+// function tables, e.g., labelled functions obtained empirically):
+// a line for each class, with its canonical code, its number of
+// records and its share of the records, separated by tabs, by
+// decreasing number of records (and in generation order for the same
+// number). The classes are kept in memory, but not the records
+
+fn classify(args: &ClassifyArgs) {
+    if matches!(args.format,
+                Format::Forests | Format::Dot | Format::Adjmatrix
+                | Format::Mtx) {
+        Args::command().error(ErrorKind::ArgumentConflict,
+                              "classify requires a format with a single \
+                               line for each digraph")
+            .exit();
+    }
+    #[cfg(feature = "arrow")]
+    if args.format == Format::Parquet {
+        Args::command().error(ErrorKind::ArgumentConflict,
+                              "classify does not support the parquet \
+                               format")
+            .exit();
+    }
+    let now = Instant::now();
+    let term = if args.print0 { b'\0' } else { b'\n' };
+    let mut input = Records::open(&args.file, args.lenient);
+    let mut classes: BTreeMap<Code, u64> = BTreeMap::new();
+    while let Some(g) = input.next(term) {
+        let canon = canonical(&func_adj(&g).iter().map(|&w| w as usize)
+                              .collect::<Vec<_>>());
+        *classes.entry(Code(canon)).or_insert(0) += 1;
+    }
+    let mut table: Vec<_> = classes.into_iter().collect();
+    table.sort_by_key(|&(_, count)| Reverse(count));
+    let print = printer(args.format);
+    let total = input.count;
+    let mut out = Output::new(None);
+    let res = table.iter().try_for_each(|(code, count)| {
+        let mut buf = Vec::new();
+        print(&mut buf, &code.0, None)?;
+        buf.pop();
+        out.write_all(&buf)?;
+        write!(out, "\t{count}\t{:.6}", *count as f64 / total as f64)?;
+        out.write_all(&[term])
+    });
+    exit_on_error(res.and_then(|_| out.finish()));
+    let time = now.elapsed();
+    eprintln!("{total} records classified in {time:.2?}: {} classes",
+              table.len());
+}
+
+
+// Check whether the two digraphs given in args (in any textual format
+// or as function tables) are isomorphic: if they are, print their
+// canonical code on stderr and an isomorphism on stdout, as the
+// function table mapping each vertex of the first digraph to the
+// corresponding vertex of the second one (with vertices named as in
+// digraph6 format for the codes), otherwise exit with status 1. The
+// isomorphism is obtained by aligning the canonical labellings of
+// the digraphs, and checked before being printed
+
+fn iso(args: &IsoArgs) {
+    let parse = |s: &str| parse_map(s.trim()).unwrap_or_else(|e| {
+        Args::command().error(ErrorKind::ValueValidation,
+                              format!("invalid digraph '{s}': {e}"))
+            .exit()
+    });
+    let (f, g) = (parse(&args.first), parse(&args.second));
+    // Both parsers only return valid maps, so this cannot fail
+    let phi = canonical_isomorphism(&f, &g)
+        .unwrap_or_else(|e| exit_with(&e.into()));
+    let Some(phi) = phi else {
+        eprintln!("the digraphs are not isomorphic");
+        process::exit(EXIT_FAILURE);
+    };
+    let mut seen = vec![false; phi.len()];
+    for &w in &phi {
+        seen[w] = true;
+    }
+    if !seen.iter().all(|&s| s)
+        || (0..f.len()).any(|v| phi[f[v]] != g[phi[v]]) {
+        exit_with(&Error::Internal("the canonical labellings do not \
+                                    give an isomorphism"));
+    }
+    eprintln!("the digraphs are isomorphic, with code {:?}",
+              canonical(&f));
+    let mut out = Output::new(None);
+    let res = phi.iter().enumerate().try_for_each(|(x, y)| {
+        write!(out, "{}{y}", if x == 0 { "" } else { " " })
+    });
+    exit_on_error(res.and_then(|_| writeln!(out))
+                  .and_then(|_| out.finish()));
+}
+
+
+// Check whether the first digraph given in args embeds into the
+// second one, component by component (as defined in the embed
+// module): if it does, print an embedding on stdout, as the function
+// table mapping each vertex of the first digraph to its image in the
+// second one (with vertices named as for iso), otherwise exit with
+// status 1. The embedding is computed between the canonical codes of
+// the digraphs, and carried over to the given vertices through their
+// canonical labellings; it is checked before being printed
+
+fn embed(args: &EmbedArgs) {
+    let parse = |s: &str| parse_map(s.trim()).unwrap_or_else(|e| {
+        Args::command().error(ErrorKind::ValueValidation,
+                              format!("invalid digraph '{s}': {e}"))
+            .exit()
+    });
+    let (f, g) = (parse(&args.first), parse(&args.second));
+    // Both parsers only return valid maps, so this cannot fail
+    let labelling = |a: &[usize]| canon_labelling(a)
+        .unwrap_or_else(|e| exit_with(&e.into()));
+    let ((cf, lf), (cg, lg)) = (labelling(&f), labelling(&g));
+    let Some(psi) = func_embedding(&cf, &cg) else {
+        eprintln!("the first digraph does not embed into the second one");
+        process::exit(EXIT_FAILURE);
+    };
+    let mut inverse = vec![0; lg.len()];
+    for (w, &x) in lg.iter().enumerate() {
+        inverse[x] = w;
+    }
+    let phi: Vec<usize> = lf.iter().map(|&x| inverse[psi[x]]).collect();
+    if (0..f.len()).any(|v| phi[f[v]] != g[phi[v]]) {
+        exit_with(&Error::Internal("the embedding does not commute with \
+                                    the functions"));
+    }
+    if phi.iter().collect::<HashSet<_>>().len() != phi.len() {
+        exit_with(&Error::Internal("the embedding is not injective"));
+    }
+    eprintln!("the first digraph embeds into the second one");
+    let mut out = Output::new(None);
+    let res = phi.iter().enumerate().try_for_each(|(x, y)| {
+        write!(out, "{}{y}", if x == 0 { "" } else { " " })
+    });
+    exit_on_error(res.and_then(|_| writeln!(out))
+                  .and_then(|_| out.finish()));
+}
+
+
+// Print a description of the digraph with the given code: its
+// components, the trees along their cycles and the function table of
+// a representative (with vertices named as in digraph6 format). Codes
+// which are well formed but not canonical are also accepted, and
+// their canonical code is given
+
+fn explain(args: &ExplainArgs) {
+    let g = parse_record(args.code.trim()).unwrap_or_else(|e| {
+        Args::command().error(ErrorKind::ValueValidation,
+                              format!("invalid code: {e}"))
+            .exit()
+    });
+    let a: Vec<usize> = func_adj(&g).iter().map(|&w| w as usize).collect();
+    let vertices = |k: usize| if k == 1 {
+        "1 vertex".to_string()
+    } else {
+        format!("{k} vertices")
+    };
+    println!("digraph {} with {} and {} component{}", Pretty(&g),
+             vertices(a.len()), g.len(), if g.len() == 1 { "" } else { "s" });
+    let canon = canonical(&a);
+    if canon != g {
+        println!("this code is not canonical, the canonical one is {}",
+                 Pretty(&canon));
+    }
+    let mut v = 0;
+    for (i, c) in g.iter().enumerate() {
+        println!("component {}: {}, cycle of length {}", i + 1,
+                 vertices(c.size()), c.cycle_length());
+        for (j, t) in c.iter().enumerate() {
+            println!("  tree {} at vertex {v}: {}, height {}, {}", j + 1,
+                     vertices(t.len()), t.height(), Pretty(&**t));
+            v += t.len();
+        }
+    }
+    println!("function table:");
+    for (x, y) in a.iter().enumerate() {
+        println!("  {x} -> {y}");
+    }
+}
+
+
+// Print the table of each labelled function (the images of the
+// vertices, separated by spaces) having the digraph with the given
+// code; the functions are generated one at a time
+
+fn orbit(args: &OrbitArgs) {
+    let g = parse_record(args.code.trim()).unwrap_or_else(|e| {
+        Args::command().error(ErrorKind::ValueValidation,
+                              format!("invalid code: {e}"))
+            .exit()
+    });
+    // The code is made canonical, since generate_labellings relies on
+    // the order of the trees and components
+    let g = canonical(&func_adj(&g).iter().map(|&w| w as usize)
+                      .collect::<Vec<_>>());
+    let now = Instant::now();
+    let mut out = Output::new(None);
+    let res = generate_labellings(&g, &mut |f| {
+        for (x, y) in f.iter().enumerate() {
+            write!(out, "{}{y}", if x == 0 { "" } else { " " })?;
+        }
+        out.write_all(if args.print0 { b"\0" } else { b"\n" })?;
+        out.end_record()
+    });
+    let count = res.as_ref().map_or(0, |&c| c);
+    exit_on_error(res.and_then(|_| out.finish()));
+    let time = now.elapsed();
+    eprintln!("{count} labelled functions generated in {time:.2?}");
+}
+
+
+// Print the table of each completion of the partial function given in
+// args (the images of the vertices, separated by spaces), up to the
+// isomorphisms fixing the vertices it mentions; the other vertices
+// are labelled in the order of the representative of the digraph
+
+fn extend(args: &ExtendArgs) {
+    let p = parse_partial_function_table(args.table.trim())
+        .unwrap_or_else(|e| {
+            Args::command().error(ErrorKind::ValueValidation,
+                                  format!("invalid table: {e}"))
+                .exit()
+        });
+    let now = Instant::now();
+    let mut out = Output::new(None);
+    let res = generate_completions(&p, args.connected, &mut |_, f| {
+        for (x, y) in f.iter().enumerate() {
+            write!(out, "{}{y}", if x == 0 { "" } else { " " })?;
+        }
+        out.write_all(if args.print0 { b"\0" } else { b"\n" })?;
+        out.end_record()
+    });
+    let count = res.as_ref().map_or(0, |&c| c);
+    exit_on_error(res.and_then(|_| out.finish()));
+    let time = now.elapsed();
+    eprintln!("{count} completions generated in {time:.2?}");
+}
+
+
+// Collect the codes passed to emit by generate (for n vertices) into
+// a set, also returning the number of duplicates found
+
+fn generated_codes(n: usize, generate: fn(usize, &mut Emit<Error>)
+                                          -> Result<u64, Error>)
+                   -> (HashSet<Code>, u64) {
+    let mut codes = HashSet::new();
+    let mut duplicates = 0;
+    let res = generate(n, &mut |g| {
+        if !codes.insert(Code(g.clone())) {
+            duplicates += 1;
+        }
+        Ok(())
+    });
+    if let Err(e) = res {
+        exit_with(&e);
+    }
+    (codes, duplicates)
+}
+
+
+// Compare the codes generated for n vertices (all digraphs, or only
+// the connected ones) with the set of canonical forms expected,
+// printing the discrepancies found on stderr; return whether there
+// are none
+
+fn compare_codes(n: usize, what: &str, expected: &HashSet<Code>,
+                 generated: &HashSet<Code>, duplicates: u64) -> bool {
+    let missing: Vec<_> = expected.difference(generated).collect();
+    let extra: Vec<_> = generated.difference(expected).collect();
+    if duplicates > 0 {
+        eprintln!("n = {n}: {duplicates} {what} generated more than once");
+    }
+    if let Some(&c) = missing.iter().min() {
+        eprintln!("n = {n}: {} {what} not generated, such as {c}",
+                  missing.len());
+    }
+    if let Some(&c) = extra.iter().min() {
+        eprintln!("n = {n}: {} {what} generated but not canonical forms \
+                   of any function, such as {c}", extra.len());
+    }
+    duplicates == 0 && missing.is_empty() && extra.is_empty()
+}
+
+
+// Cross-validate the generation algorithm for all sizes n up to
+// args.max_n: all n^n labelled functions are enumerated and made
+// canonical with Comp::from_function, independently from the reverse
+// search, and their distinct canonical forms (all of them, and the
+// connected ones) must be exactly the digraphs generated, each one
+// exactly once. A table with the counts is printed, and the exit
+// status is 1 if any check fails
+
+fn selftest(args: &SelftestArgs) {
+    let now = Instant::now();
+    let mut ok = true;
+    println!("n\tfunctions\tdigraphs\tconnected\tresult");
+    for n in 0..=args.max_n as usize {
+        let mut funcs = HashSet::new();
+        let mut comps = HashSet::new();
+        let mut f = vec![0; n];
+        let mut labelled: u64 = 0;
+        loop {
+            labelled += 1;
+            let c = Comp::from_function(&f)
+                .unwrap_or_else(|e| exit_with(&Error::InvalidEntry(e)));
+            let g: Func = c.into_iter().map(Rc::new).collect();
+            if g.len() == 1 {
+                comps.insert(Code(g.clone()));
+            }
+            funcs.insert(Code(g));
+            // Next function table, in lexicographic order
+            let Some(i) = f.iter().rposition(|&y| y + 1 < n) else {
+                break;
+            };
+            f[i] += 1;
+            f[i + 1..].fill(0);
+        }
+        let (gen_funcs, dup_funcs) = generated_codes(n, generate_funcs);
+        let (gen_comps, dup_comps) = generated_codes(n, generate_comps);
+        let res = compare_codes(n, "digraphs", &funcs, &gen_funcs,
+                                dup_funcs)
+            & compare_codes(n, "connected digraphs", &comps, &gen_comps,
+                            dup_comps);
+        println!("{n}\t{labelled}\t{}\t{}\t{}", funcs.len(), comps.len(),
+                 if res { "ok" } else { "FAILED" });
+        ok &= res;
+    }
+    eprintln!("sizes up to {} checked in {:.2?}", args.max_n, now.elapsed());
+    if !ok {
+        process::exit(EXIT_FAILURE);
+    }
+}
+
+
+// Generate all digraphs (or the connected ones) with args.size
+// vertices, writing a report on them with their count, the histogram
+// of each invariant and drawings of a random sample
+
+fn report(args: &ReportArgs) {
+    let n = args.size as usize;
+    let mut rng = Rng::new(args.seed.unwrap_or_else(|| {
+        SystemTime::now().duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64)
+    }));
+    let mut report = Report::new(n, args.connected, args.sample);
+    let now = Instant::now();
+    let generate = if args.connected { generate_comps } else {
+        generate_funcs
+    };
+    let res: Result<u64, Error> = generate(n, &mut |g| {
+        report.add(g, &mut rng);
+        Ok(())
+    });
+    if let Err(e) = res {
+        exit_with(&e);
+    }
+    report.time = now.elapsed();
+    let res = File::create(&args.html).and_then(|file| {
+        let mut out = BufWriter::new(file);
+        report.write_html(&mut out)?;
+        out.flush()
+    });
+    if let Err(e) = res {
+        eprintln!("Error writing {}: {e}", args.html.display());
+        process::exit(EXIT_IOERR);
+    }
+    eprintln!("{} digraphs generated in {:.2?}, report written to {}",
+              report.count, report.time, args.html.display());
+}
+
+
+// Generate all digraphs (or the connected ones) with args.size
+// vertices, printing the exact expectation and variance of each
+// invariant of a uniformly random labelled function (obtained by
+// weighting each digraph by its number of labellings): a line for
+// each invariant, with its name, the two exact fractions and their
+// decimal approximations, separated by tabs
+
+fn expect(args: &ExpectArgs) {
+    let n = args.size as usize;
+    let mut expectations = Expectations::new(n).unwrap_or_else(|| {
+        Args::command().error(ErrorKind::ValueValidation,
+                              "exact expectations are only supported \
+                               up to 24 vertices")
+            .exit()
+    });
+    let now = Instant::now();
+    let generate = if args.connected { generate_comps } else {
+        generate_funcs
+    };
+    let res: Result<u64, Error> = generate(n, &mut |g| {
+        expectations.add(g);
+        Ok(())
+    });
+    let count = res.unwrap_or_else(|e| exit_with(&e));
+    let time = now.elapsed();
+    let mut out = Output::new(None);
+    let res = Expectations::NAMES.iter().zip(expectations.moments())
+        .try_for_each(|(name, m)| {
+            // There is at least one function, since n > 0
+            let (mean, var) = (m.mean().unwrap(), m.variance().unwrap());
+            writeln!(out, "{name}\t{mean}\t{var}\t{:.6}\t{:.6}",
+                     mean.to_f64(), var.to_f64())
+        });
+    exit_on_error(res.and_then(|_| out.finish()));
+    eprintln!("{count} digraphs generated in {time:.2?}");
+}
+
+
+// JSON object with the single field error, for the responses to the
+// invalid requests
+
+fn json_error(msg: &str) -> Vec<u8> {
+    format!("{{\"error\":{}}}\n", json_string(msg)).into_bytes()
+}
+
+
+// Parse the query parameters of request req, whose number of
+// vertices is required; the errors are messages for the client
+
+fn parse_query(req: &Request) -> Result<(usize, bool, Filter), String> {
+    let args = req.query.iter().map(|(key, value)| {
+        let key = key.replace('_', "-");
+        if value.is_empty() {
+            format!("--{key}")
+        } else {
+            format!("--{key}={value}")
+        }
+    });
+    let q = Query::try_parse_from(args).map_err(|e| {
+        let msg = e.to_string();
+        let line = msg.lines().next().unwrap_or_default();
+        line.strip_prefix("error: ").unwrap_or(line).replace("'--", "'")
+    })?;
+    let n = q.n.ok_or("missing parameter n")? as usize;
+    if n > MAX_VERTICES {
+        return Err(Error::TooManyVertices(n).to_string());
+    }
+    Ok((n, q.connected, q.constraints.filter()))
+}
+
+
+// Answer GET /count: the number of digraphs with the given number of
+// vertices satisfying the constraints, as a JSON object; it is
+// computed from the generating functions when possible, and by
+// generating them otherwise, which is only allowed up to max_size
+// vertices
+
+fn serve_count(stream: &mut TcpStream, req: &Request, max_size: usize)
+               -> io::Result<()> {
+    let (n, connected, filter) = match parse_query(req) {
+        Ok(q) => q,
+        Err(msg) => {
+            return write_response(stream, 400, JSON, &json_error(&msg));
+        }
+    };
+    let counts = if connected {
+        COUNTS.comp_counts(n)
+    } else {
+        COUNTS.func_counts(n)
+    };
+    let count = match counts {
+        Some(c) if filter.is_unconstrained() => c[n].iter().sum(),
+        _ if n > max_size => {
+            let msg = format!("counting the digraphs by generating them \
+                               is limited to {max_size} vertices");
+            return write_response(stream, 400, JSON, &json_error(&msg));
+        }
+        _ => {
+            let mut emit = |_: &Func| Ok(());
+            let res = if connected {
+                generate_comps_filtered(n, &filter, &mut emit)
+            } else {
+                generate_funcs_filtered(n, &filter, &mut emit)
+            };
+            match res {
+                Ok(count) => count as u128,
+                Err(e) => {
+                    let e: Error = e;
+                    return write_response(stream, 500, JSON,
+                                          &json_error(&e.to_string()));
+                }
+            }
+        }
+    };
+    let body = format!("{{\"n\":{n},\"connected\":{connected},\
+                        \"count\":{count}}}\n");
+    write_response(stream, 200, JSON, body.as_bytes())
+}
+
+
+// Write functional digraph g as a JSON object on a line, with its
+// digraph6 code and its nested-bracket notation
+
+fn write_json_digraph(out: &mut dyn Write, g: &Func) -> io::Result<()> {
+    let mut d6 = Vec::new();
+    write_digraph6_code(&mut d6, &func_adj(g), false)?;
+    writeln!(out, "{{\"digraph6\":{},\"pretty\":{}}}",
+             json_string(&String::from_utf8_lossy(&d6)),
+             json_string(&Pretty(g).to_string()))
+}
+
+
+// Answer GET /generate: the digraphs with the given number of
+// vertices satisfying the constraints, in generation order, as
+// newline-delimited JSON streamed while they are generated; only up
+// to max_size vertices
+
+fn serve_generate(stream: &mut TcpStream, req: &Request, max_size: usize)
+                  -> io::Result<()> {
+    let (n, connected, filter) = match parse_query(req) {
+        Ok(q) => q,
+        Err(msg) => {
+            return write_response(stream, 400, JSON, &json_error(&msg));
+        }
+    };
+    if n > max_size {
+        let msg = format!("generation is limited to {max_size} vertices");
+        return write_response(stream, 400, JSON, &json_error(&msg));
+    }
+    let chunked = Chunked::new(&mut *stream, "application/x-ndjson")?;
+    let mut out = BufWriter::new(chunked);
+    let mut emit = |g: &Func| write_json_digraph(&mut out, g);
+    if connected {
+        generate_comps_filtered(n, &filter, &mut emit)?;
+    } else {
+        generate_funcs_filtered(n, &filter, &mut emit)?;
+    }
+    out.into_inner().map_err(|e| e.into_error())?.finish()
+}
+
+
+// Answer POST /canonicalize: the body contains a digraph on each
+// nonempty line, in any of the textual formats or as a function table
+// (as for verify), and the response has a JSON object on a line for
+// each of them, with its canonical code, or with the error (and the
+// column where it occurs) if the line cannot be parsed
+
+fn serve_canonicalize(stream: &mut TcpStream, req: &Request)
+                      -> io::Result<()> {
+    let mut body = Vec::new();
+    let text = String::from_utf8_lossy(&req.body);
+    for (i, line) in text.lines().enumerate() {
+        let s = line.trim();
+        if s.is_empty() {
+            continue;
+        }
+        let res = parse_record(s).and_then(|g| {
+            let a: Vec<_> = func_adj(&g).iter().map(|&w| w as usize)
+                .collect();
+            canon_map(&a).map_err(|e| ParseError { pos: 0, msg: e.msg })
+        });
+        match res {
+            Ok(g) => write_json_digraph(&mut body, &g)?,
+            Err(e) => writeln!(body, "{{\"line\":{},\"column\":{},\
+                                      \"error\":{}}}", i + 1,
+                               e.pos.min(s.len()) + 1,
+                               json_string(&e.msg))?,
+        }
+    }
+    write_response(stream, 200, "application/x-ndjson", &body)
+}
+
+
+// Answer a request on stream, within the limits of args
+
+fn handle_request(stream: &mut TcpStream, args: &ServeArgs)
+                  -> io::Result<()> {
+    let req = match read_request(&mut BufReader::new(&mut *stream)) {
+        Ok(req) => req,
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            let msg = json_error(&e.to_string());
+            return write_response(stream, 400, JSON, &msg);
+        }
+        Err(e) => return Err(e),
+    };
+    let method = match req.path.as_str() {
+        "/count" | "/generate" => "GET",
+        "/canonicalize" => "POST",
+        _ => {
+            let msg = json_error(&format!("no such endpoint {}", req.path));
+            return write_response(stream, 404, JSON, &msg);
+        }
+    };
+    if req.method != method {
+        let msg = json_error(&format!("{} requires {method}", req.path));
+        return write_response(stream, 405, JSON, &msg);
+    }
+    match req.path.as_str() {
+        "/count" => serve_count(stream, &req, args.max_count_size),
+        "/generate" => serve_generate(stream, &req, args.max_generate_size),
+        _ => serve_canonicalize(stream, &req),
+    }
+}
+
+
+// Accept the connections on listener and answer their requests, one
+// at a time, until the listener fails
+
+fn serve_connections(listener: &TcpListener, args: &ServeArgs) {
+    for stream in listener.incoming() {
+        let res = stream.and_then(|mut stream| {
+            stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+            stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+            let res = handle_request(&mut stream, args);
+            let _ = stream.shutdown(Shutdown::Both);
+            res
+        });
+        match res {
+            Ok(()) => (),
+            Err(e) if matches!(e.kind(), io::ErrorKind::BrokenPipe
+                               | io::ErrorKind::ConnectionReset) => (),
+            Err(e) => eprintln!("Error serving a request: {e}"),
+        }
+    }
+}
+
+
+// Listen on args.listen for HTTP requests, answering up to args.jobs
+// of them at the same time, each one on its own thread: GET /count
+// and GET /generate with the number of vertices n and the
+// constraints as query parameters, and POST /canonicalize with a
+// digraph on each line of the body. The errors on a connection are
+// reported (except for a client disconnecting, e.g., during the
+// generation), and the thread moves on to the next one
+
+fn serve(args: &ServeArgs) {
+    let listener = TcpListener::bind(&args.listen).unwrap_or_else(|e| {
+        eprintln!("Error listening on {}: {e}", args.listen);
+        process::exit(EXIT_IOERR);
+    });
+    if let Ok(addr) = listener.local_addr() {
+        eprintln!("Listening on http://{addr}");
+    }
+    let jobs = args.jobs.unwrap_or_else(|| {
+        thread::available_parallelism().map_or(1, |n| n.get())
+    }).max(1);
+    thread::scope(|scope| {
+        for _ in 1..jobs {
+            match listener.try_clone() {
+                Ok(listener) => {
+                    scope.spawn(move || serve_connections(&listener, args));
+                }
+                Err(e) => eprintln!("Error starting a thread: {e}"),
+            }
+        }
+        serve_connections(&listener, args);
+    });
+}
+
+
+// Random relabelling of the functional digraph with adjacency vector
+// a, together with the new name of each vertex
+
+fn relabel(a: &[usize], rng: &mut Rng) -> (Vec<usize>, Vec<usize>) {
+    let n = a.len();
+    let mut perm: Vec<usize> = (0..n).collect();
+    for i in (1..n).rev() {
+        perm.swap(i, rng.below(i as u64 + 1) as usize);
+    }
+    let mut b = vec![0; n];
+    for v in 0..n {
+        b[perm[v]] = perm[a[v]];
+    }
+    (b, perm)
+}
+
+
+// Distributions of the random-mapping statistics of the digraphs,
+// each one weighted by weight: the size of the largest component,
+// the number of components, the size of the component containing a
+// random vertex and the in-degree of a random vertex (for the last
+// two, counting each vertex of each digraph, so that the total is n
+// times that of the others), as well as the total size of the image
+// of each iterate f^k, for k from 1 to n - 1 (the maximum height).
+// The number of occurrences of the trees rooted along the cycles of
+// each size is also counted (without weights), together with the
+// distinct trees themselves, which are collected in trees unless it
+// is None (as with --streaming, since their number grows
+// exponentially with the number of vertices)
+
+struct Stats {
+    weight: Weight,
+    largest: BTreeMap<usize, u128>,
+    components: BTreeMap<usize, u128>,
+    point: BTreeMap<usize, u128>,
+    indegrees: BTreeMap<usize, u128>,
+    images: Vec<u128>,
+    trees: Option<HashSet<Rc<Tree>>>,
+    tree_counts: BTreeMap<usize, u64>,
+}
+
+
+impl Stats {
+
+    fn new(weight: Weight, distinct_trees: bool) -> Stats {
+        Stats {
+            weight,
+            largest: BTreeMap::new(),
+            components: BTreeMap::new(),
+            point: BTreeMap::new(),
+            indegrees: BTreeMap::new(),
+            images: Vec::new(),
+            trees: distinct_trees.then(HashSet::new),
+            tree_counts: BTreeMap::new(),
+        }
+    }
+
+    // Add functional digraph g to the distributions; the weights are
+    // assumed to fit into a u128, which is checked in advance
+
+    fn add(&mut self, g: &Func) {
+        let w = match self.weight {
+            Weight::Classes => 1,
+            Weight::Labelled => num_labellings(g).unwrap(),
+        };
+        let largest = g.iter().map(|c| c.size()).max().unwrap_or(0);
+        *self.largest.entry(largest).or_insert(0) += w;
+        *self.components.entry(g.len()).or_insert(0) += w;
+        for (d, &m) in g.indegree_counts().iter().enumerate() {
+            if m > 0 {
+                *self.indegrees.entry(d).or_insert(0) += w * m as u128;
+            }
+        }
+        let sizes = g.image_sizes();
+        let k = sizes.len().max(g.size().saturating_sub(1));
+        if self.images.len() < k {
+            self.images.resize(k, 0);
+        }
+        for (i, image) in self.images.iter_mut().enumerate() {
+            let size = sizes.get(i).or(sizes.last()).unwrap();
+            *image += w * *size as u128;
+        }
+        for c in g {
+            *self.point.entry(c.size()).or_insert(0) += w * c.size() as u128;
+            for t in c.iter() {
+                *self.tree_counts.entry(t.len()).or_insert(0) += 1;
+                if let Some(trees) = &mut self.trees {
+                    if !trees.contains(t) {
+                        trees.insert(t.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    // Estimate the memory in bytes taken by the distributions and
+    // by the distinct trees
+
+    fn bytes(&self) -> usize {
+        let rc = 2 * mem::size_of::<usize>() + mem::size_of::<Tree>();
+        table_bytes(&self.largest) + table_bytes(&self.components)
+            + table_bytes(&self.point) + table_bytes(&self.indegrees)
+            + table_bytes(&self.tree_counts)
+            + self.images.capacity() * mem::size_of::<u128>()
+            + self.trees.as_ref().map_or(0, |trees| {
+                trees.iter().map(|t| rc + t.capacity()).sum::<usize>()
+                    + trees.capacity() * mem::size_of::<Rc<Tree>>()
+            })
+    }
+
+    fn print(&self) {
+        let tables = [("largest-component", &self.largest),
+                      ("components", &self.components),
+                      ("point-component", &self.point),
+                      ("indegree", &self.indegrees)];
+        for (name, table) in tables {
+            eprintln!("{name}\tcount");
+            for (value, count) in table {
+                eprintln!("{value}\t{count}");
+            }
+        }
+        eprintln!("iterate\timage-size");
+        for (k, size) in self.images.iter().enumerate() {
+            eprintln!("{}\t{size}", k + 1);
+        }
+        let Some(trees) = &self.trees else {
+            eprintln!("tree-size\toccurrences");
+            for (size, count) in &self.tree_counts {
+                eprintln!("{size}\t{count}");
+            }
+            return;
+        };
+        let mut distinct = BTreeMap::new();
+        for t in trees {
+            *distinct.entry(t.len()).or_insert(0) += 1;
+        }
+        eprintln!("tree-size\tdistinct\toccurrences");
+        for (size, count) in &self.tree_counts {
+            eprintln!("{size}\t{}\t{count}", distinct[size]);
+        }
+    }
+
+}
+
+
+// Check that the (pointed) digraphs in sample are pairwise not
+// isomorphic, after shuffling them into pairs and relabelling them at
+// random, with an isomorphism test independent of the codes; each
+// digraph is also checked to be isomorphic to another relabelling of
+// itself, so that the test cannot pass by always failing. Exit with
+// an error if any check fails
+
+fn spot_check(sample: &mut [(Func, Option<usize>)], rng: &mut Rng) {
+    for i in (1..sample.len()).rev() {
+        sample.swap(i, rng.below(i as u64 + 1) as usize);
+    }
+    let labelled = |(g, v): &(Func, Option<usize>), rng: &mut Rng| {
+        let a: Vec<usize> = func_adj(g).iter().map(|&w| w as usize)
+            .collect();
+        let (b, perm) = relabel(&a, rng);
+        let mut colors = vec![0; a.len()];
+        if let Some(v) = v {
+            colors[perm[*v]] = 1;
+        }
+        (b, colors)
+    };
+    for pair in sample.chunks_exact(2) {
+        let (f, cf) = labelled(&pair[0], rng);
+        let (g, cg) = labelled(&pair[1], rng);
+        let (h, ch) = labelled(&pair[0], rng);
+        let fail = |msg: &str| -> ! {
+            eprintln!("Spot check failed: {msg} {} and {}",
+                      Pretty(&pair[0].0), Pretty(&pair[1].0));
+            process::exit(EXIT_FAILURE);
+        };
+        if find_colored_isomorphism(&f, &cf, &g, &cg).is_some() {
+            fail("isomorphism found between");
+        }
+        match find_colored_isomorphism(&f, &cf, &h, &ch) {
+            Some(phi) if (0..f.len()).all(|v| phi[f[v]] == h[phi[v]]
+                                          && cf[v] == ch[phi[v]]) => (),
+            _ => fail("no isomorphism found between relabellings of the \
+                       first digraph of"),
+        }
+    }
+}
+
+
+// Main program; the output is written by a separate thread through
+// a bounded buffer, so memory usage does not grow with the number of
+// digraphs
+
+fn main() {
+    match &ARGS.command {
+        Some(Command::Sample(args)) => return sample(args),
+        Some(Command::Gf(args)) => return gf(args),
+        Some(Command::Bfile(args)) => return bfile(args),
+        Some(Command::Distribute(args)) => return distribute(args),
+        Some(Command::Merge(args)) => return merge(args),
+        Some(Command::Verify(args)) => return verify(args),
+        Some(Command::Diff(args)) => return diff(args),
+        Some(Command::Iso(args)) => return iso(args),
+        Some(Command::Embed(args)) => return embed(args),
+        Some(Command::Classify(args)) => return classify(args),
+        Some(Command::Explain(args)) => return explain(args),
+        Some(Command::Orbit(args)) => return orbit(args),
+        Some(Command::Extend(args)) => return extend(args),
+        Some(Command::Selftest(args)) => return selftest(args),
+        Some(Command::Report(args)) => return report(args),
+        Some(Command::Expect(args)) => return expect(args),
+        Some(Command::Serve(args)) => return serve(args),
+        None => (),
+    }
+    if ARGS.loopless && *FORMAT != Format::Digraph6 {
+        Args::command().error(ErrorKind::ArgumentConflict,
+                              "--loopless requires --format digraph6")
+            .exit();
+    }
+    // The direct and augmentation algorithms keep all the trees or
+    // components in memory, against the guarantee of --streaming
+    if ARGS.streaming && ARGS.algorithm != Algorithm::Reverse {
+        Args::command().error(ErrorKind::ArgumentConflict,
+                              "--algorithm direct or augment cannot be \
+                               used with --streaming")
+            .exit();
+    }
+    let Some(n) = ARGS.size.map(usize::from) else {
+        Args::command().error(ErrorKind::MissingRequiredArgument,
+                              "the number of vertices is required")
+            .exit();
+    };
+    if let Some(path) = &ARGS.emit_search_tree {
+        let sizes: Vec<usize> = if ARGS.connected {
+            vec![n]
+        } else {
+            (1..=n).collect()
+        };
+        let res = File::create(path).and_then(|file| {
+            let mut w = BufWriter::new(file);
+            write_search_tree(&mut w, &sizes)?;
+            w.flush()
+        });
+        if let Err(e) = res {
+            eprintln!("Error writing {}: {e}", path.display());
+            process::exit(EXIT_IOERR);
+        }
+    }
+    #[cfg(feature = "arrow")]
+    if ARGS.labelled_samples.is_some() && *FORMAT == Format::Parquet {
+        Args::command().error(ErrorKind::ArgumentConflict,
+                              "--labelled-samples cannot be used with \
+                               --format parquet")
+            .exit();
+    }
+    #[cfg(feature = "arrow")]
+    if ARGS.group_output.is_some() && *FORMAT == Format::Parquet {
+        Args::command().error(ErrorKind::ArgumentConflict,
+                              "--group-output cannot be used with \
+                               --format parquet")
+            .exit();
+    }
+    // The rows of each row group are kept in memory until it is full
+    #[cfg(feature = "arrow")]
+    if ARGS.streaming && *FORMAT == Format::Parquet {
+        Args::command().error(ErrorKind::ArgumentConflict,
+                              "--format parquet cannot be used with \
+                               --streaming")
+            .exit();
+    }
+    // The largest total of the statistics weighted by labelled
+    // functions is n * n^n, for the sizes of the components of the
+    // vertices of all n^n functions
+    if ARGS.stats == Some(Weight::Labelled)
+        && (n as u128).checked_pow(n as u32 + 1).is_none() {
+        Args::command().error(ErrorKind::ValueValidation,
+                              format!("--stats=labelled is not supported \
+                                       for digraphs of {n} vertices"))
+            .exit();
+    }
+    if ARGS.every.is_some_and(|k| ARGS.offset >= k) {
+        Args::command().error(ErrorKind::ValueValidation,
+                              "--offset must be less than --every")
+            .exit();
+    }
+    if ARGS.group_output.is_some_and(|inv| !inv.is_bounded()) {
+        Args::command().error(ErrorKind::ArgumentConflict,
+                              "--group-output requires an invariant whose \
+                               values are at most the number of vertices")
+            .exit();
+    }
+    let passes = if ARGS.by_components {
+        Some(&Components as &dyn Invariant)
+    } else {
+        ARGS.group_output
+    };
+    let resume = ARGS.resume.as_ref().map(|s| {
+        let token = ResumeToken::from_str(s).unwrap_or_else(|e| {
+            Args::command().error(ErrorKind::ValueValidation, e).exit()
+        });
+        let g = &token.start;
+        let size: usize = g.iter().map(|c| comp_size(c)).sum();
+        let a: Vec<usize> = func_adj(g).iter().map(|&v| v as usize)
+            .collect();
+        if size != n || (ARGS.connected && g.len() != 1)
+            || token.pass.is_some() != passes.is_some()
+            || token.pass.is_some_and(|value| value > n)
+            || canonical(&a) != *g {
+            Args::command().error(ErrorKind::ValueValidation,
+                                  format!("the resume token '{s}' does \
+                                           not match the options"))
+                .exit();
+        }
+        token
+    });
+    // The search is entered at the digraph given by --start-from, so
+    // it must be canonical and accepted by the constraints (otherwise
+    // the pruning could skip some of the following digraphs)
+    let start_from = ARGS.start_from.as_ref().map(|s| {
+        let g = parse_record(s.trim()).unwrap_or_else(|e| {
+            Args::command().error(ErrorKind::ValueValidation,
+                                  format!("invalid code: {e}"))
+                .exit()
+        });
+        let size: usize = g.iter().map(|c| comp_size(c)).sum();
+        let a: Vec<usize> = func_adj(&g).iter().map(|&v| v as usize)
+            .collect();
+        let msg = if size != n {
+            Some(format!("the digraph '{s}' does not have {n} vertices"))
+        } else if ARGS.connected && g.len() != 1 {
+            Some(format!("the digraph '{s}' is not connected"))
+        } else if canonical(&a) != g {
+            Some(format!("'{s}' is not a canonical code, the canonical \
+                          one is {}", Pretty(&canonical(&a))))
+        } else if !FILTER.accepts(&g) {
+            Some(format!("the digraph '{s}' does not satisfy the \
+                          constraints"))
+        } else {
+            None
+        };
+        if let Some(msg) = msg {
+            Args::command().error(ErrorKind::ValueValidation, msg).exit();
+        }
+        g
+    });
+    // The direct and augmentation algorithms do not prune the search,
+    // so they ignore the filters (and the starting digraph)
+    if ARGS.algorithm != Algorithm::Reverse
+        && (!FILTER.is_unconstrained() || passes.is_some()
+            || ARGS.resume.is_some() || start_from.is_some()) {
+        Args::command().error(ErrorKind::ArgumentConflict,
+                              "--algorithm direct or augment cannot be \
+                               used with constraints, --slice, \
+                               --group-output, --by-components, \
+                               --resume or --start-from")
+            .exit();
+    }
+    set_lcs(ARGS.lcs);
+    set_trace(ARGS.trace);
+    let now = Instant::now();
+    let mut out = Output::new(*FLUSH_EVERY);
+    if ARGS.checksum.is_some() {
+        out.digest = Some(Sha256::new());
+        out.discard = ARGS.quiet;
+    }
+    #[cfg(feature = "arrow")]
+    let mut parquet = (*FORMAT == Format::Parquet && !ARGS.quiet)
+        .then(|| ParquetWriter::new(ARGS.pointed, &table_invariants()));
+    let mut rng = Rng::new(ARGS.seed.unwrap_or_else(|| {
+        SystemTime::now().duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64)
+    }));
+    let spot = 2 * ARGS.spot_check.unwrap_or(0);
+    let mut sample = Vec::with_capacity(spot);
+    let mut sinks: Vec<_> = ARGS.output.iter().map(|sink| {
+        match File::create(&sink.path) {
+            Ok(file) => (printer(sink.format), BufWriter::new(file)),
+            Err(e) => {
+                eprintln!("Error writing {}: {e}", sink.path.display());
+                process::exit(EXIT_IOERR);
+            }
+        }
+    }).collect();
+    let mut sqlite = ARGS.output_sqlite.as_ref().map(|path| {
+        SqliteWriter::create(path, ARGS.pointed, &table_invariants())
+            .unwrap_or_else(|e| {
+                eprintln!("Error writing {}: {e}", path.display());
+                process::exit(EXIT_IOERR);
+            })
+    });
+    let mut mtx = ARGS.output_mtx.as_ref().map(|dir| {
+        MtxWriter::create(dir).unwrap_or_else(|e| {
+            eprintln!("Error writing {}: {e}", dir.display());
+            process::exit(EXIT_IOERR);
+        })
+    });
+    // Write digraph g (with distinguished vertex v, if any) to all
+    // outputs
+    let mut write_digraph = |out: &mut Output, g: &Func, v: Option<usize>,
+                             rng: &mut Rng| -> io::Result<()> {
+        PRINT_FUNC(out, g, v)?;
+        for _ in 0..ARGS.labelled_samples.unwrap_or(0) {
+            print_labelled_sample(out, g, v, rng)?;
+        }
+        #[cfg(feature = "arrow")]
+        if let Some(w) = &mut parquet {
+            w.write(out, g, v)?;
+        }
+        if let Some(db) = &mut sqlite {
+            db.insert(g, v)?;
+        }
+        if let Some(w) = &mut mtx {
+            w.write(g, v)?;
+        }
+        for (print, file) in &mut sinks {
+            print(file, g, v)?;
+        }
+        Ok(())
+    };
+    let mut stream = Vec::with_capacity(ARGS.sample_stream.unwrap_or(0));
+    let mut count: u64 = 0;
+    let mut groups = BTreeMap::new();
+    let mut histogram = BTreeMap::new();
+    let mut cycle_types = BTreeMap::new();
+    let mut stats = ARGS.stats.map(|w| Stats::new(w, !ARGS.streaming));
+    handle_status_signal();
+    let mut progress = match ARGS.log_progress {
+        Some(interval) => Some(Progress::logger(now, interval)),
+        None => ARGS.progress.then(|| Progress::new(now, expected_total(n))),
+    };
+    let mut largest_code = 0;
+    let mut section = resume.as_ref().filter(|t| !t.new)
+        .and_then(|t| t.pass).map(|p| p as Value);
+    let offset = resume.as_ref().map_or(0, |t| t.rank);
+    let deadline = ARGS.time_limit.and_then(|t| now.checked_add(t));
+    let mut visited: u64 = 0;
+    let mut stopped = None;
+    // Process each digraph g visited by the search, which is output
+    // only if accepted by the constraints; the time limit is checked
+    // for all of them, so that it also holds when few are accepted
+    let mut process = |g: &Func, accepted: bool| {
+        visited += 1;
+        // The generation is stopped before g, which is then the first
+        // digraph of the resumed generation (even if it is rejected,
+        // since the search can start from any digraph it visits)
+        if let Some(d) = deadline {
+            if visited.is_multiple_of(PROGRESS_CHECK_EVERY)
+                && Instant::now() >= d {
+                stopped = Some(g.clone());
+                return Err(io::Error::new(io::ErrorKind::TimedOut,
+                                          "time limit reached"));
+            }
+        }
+        // The flag is read before being cleared, since a plain load
+        // is cheaper than a swap for each digraph
+        if STATUS_REQUESTED.load(Relaxed) {
+            STATUS_REQUESTED.store(false, Relaxed);
+            print_status(g, count, visited, offset + count, now);
+        }
+        if let Some(p) = &mut progress {
+            p.update(count, visited);
+        }
+        if !accepted {
+            return Ok(());
+        }
+        if let Some(inv) = ARGS.group_output {
+            let value = inv.compute(g);
+            if section != Some(value) {
+                write!(out, "# {inv} = {value}")?;
+                end_record(&mut out)?;
+                section = Some(value);
+            }
+        }
+        let mut emit = |v| {
+            count += 1;
+            // The size of the code is only sampled from time to time,
+            // since computing it takes longer than generating g
+            if ARGS.report_memory
+                && (count == 1 || count.is_multiple_of(PROGRESS_CHECK_EVERY)) {
+                largest_code = largest_code.max(code_bytes(g));
+            }
+            if let Some(inv) = ARGS.group_by {
+                *groups.entry(inv.compute(g)).or_insert(0) += 1;
+            }
+            if let Some(inv) = ARGS.histogram {
+                *histogram.entry(inv.compute(g)).or_insert(0) += 1;
+            }
+            if let Some(k) = ARGS.iterate_cycles {
+                let t = g.iterate_cycle_type(k as usize);
+                *cycle_types.entry(t).or_insert(0) += 1;
+            }
+            if let Some(s) = &mut stats {
+                s.add(g);
+            }
+            // Reservoir sampling of the digraphs to output, if only
+            // some of them are, together with their ranks
+            if let Some(k) = ARGS.sample_stream {
+                if stream.len() < k {
+                    stream.push((count, g.clone(), v));
+                } else if k > 0 {
+                    let i = rng.below(count) as usize;
+                    if i < k {
+                        stream[i] = (count, g.clone(), v);
+                    }
+                }
+            } else if ARGS.every.is_none_or(|k| {
+                (offset + count - 1) % k == ARGS.offset
+            }) {
+                write_digraph(&mut out, g, v, &mut rng)?;
+            }
+            // Reservoir sampling of the digraphs for the spot check
+            if sample.len() < spot {
+                sample.push((g.clone(), v));
+            } else if spot > 0 {
+                let i = rng.below(count) as usize;
+                if i < spot {
+                    sample[i] = (g.clone(), v);
+                }
+            }
+            out.end_record()
+        };
+        if ARGS.pointed {
+            for v in pointed_reps(g) {
+                emit(Some(v))?;
+            }
+            Ok(())
+        } else {
+            emit(None)
+        }
+    };
+    let (first_value, mut start) = match resume {
+        Some(t) => (t.pass.unwrap_or(0), Some(t.start)),
+        None => (0, start_from),
+    };
+    let mut pass = None;
+    let res = match passes {
+        // The values of the invariants are not contiguous in the
+        // generation order, so a pass is made for each value (which
+        // is at most n for all invariants), instead of keeping the
+        // digraphs in memory; for the number of components, each pass
+        // only considers the partitions of n with that many parts
+        Some(inv) => (first_value..=n).try_for_each(|value| {
+            pass = Some(value);
+            let filter = Filter {
+                value: Some((inv, value as Value)),
+                ..FILTER.clone()
+            };
+            GENERATE(n, &filter, start.take().as_ref(), &mut process)
+                .map(|_| ())
+        }),
+        None => GENERATE(n, &FILTER, start.as_ref(), &mut process)
+            .map(|_| ()),
+    };
+    let res = match res {
+        Err(e) if stopped.is_some()
+            && e.kind() == io::ErrorKind::TimedOut => Ok(()),
+        res => res,
+    };
+    let res = res.and_then(|_| {
+        stream.sort_by_key(|&(rank, _, _)| rank);
+        stream.iter().try_for_each(|(_, g, v)| {
+            write_digraph(&mut out, g, *v, &mut rng)?;
+            out.end_record()
+        })
+    });
+    #[cfg(feature = "arrow")]
+    let res = res.and_then(|_| match parquet {
+        Some(w) => w.finish(&mut out),
+        None => Ok(()),
+    });
+    let res = res.and_then(|_| sqlite.map_or(Ok(()), SqliteWriter::finish));
+    let res = res.and_then(|_| {
+        sinks.iter_mut().try_for_each(|(_, file)| file.flush())
+    });
+    exit_on_error(res.and_then(|_| out.finish()));
+    let time = now.elapsed();
+    let token = stopped.as_ref().map(|g| {
+        ResumeToken {
+            rank: offset + count,
+            pass,
+            new: ARGS.group_output.is_some()
+                && section != pass.map(|p| p as Value),
+            start: g.clone(),
+        }
+    });
+    let digest = match (ARGS.checksum, out.digest.take()) {
+        (Some(Checksum::Sha256), Some(digest)) => Some(digest.hex_digest()),
+        _ => None,
+    };
+    if ARGS.spot_check.is_some() {
+        spot_check(&mut sample, &mut rng);
+    }
+    if ARGS.log_format == LogFormat::Json {
+        let mut e = JsonEvent::new("summary");
+        e.field("size", n).field("connected", ARGS.connected)
+            .field("count", count)
+            .field("elapsed", format!("{:.6}", time.as_secs_f64()));
+        if let Some(t) = &token {
+            e.field("last_rank", (offset + count).checked_sub(1)
+                    .map_or("null".to_string(), |r| r.to_string()))
+                .string("resume_token", &t.to_string());
+        }
+        if let Some(digest) = &digest {
+            e.string("sha256", digest);
+        }
+        if ARGS.spot_check.is_some() {
+            e.field("spot_check_pairs", sample.len() / 2);
+        }
+        if let Some(inv) = ARGS.group_by {
+            e.string("group_by", inv.name())
+                .field("groups", json_value_counts(&groups));
+        }
+        if let Some(inv) = ARGS.histogram {
+            e.string("histogram", inv.name())
+                .field("histogram_counts", json_value_counts(&histogram));
+        }
+        if let Some(k) = ARGS.iterate_cycles {
+            e.field("iterate_cycles", k)
+                .field("cycle_types", json_counts(&cycle_types));
+        }
+        e.print();
+    } else {
+        eprintln!("{count} digraphs generated in {time:.2?}");
+        if let Some(t) = &token {
+            match (offset + count).checked_sub(1) {
+                Some(rank) => {
+                    eprintln!("Time limit reached, last rank {rank}")
+                }
+                None => eprintln!("Time limit reached, no digraphs output"),
+            }
+            eprintln!("resume token {t}");
+        }
+        if let Some(digest) = &digest {
+            eprintln!("sha256 {digest}");
+        }
+        if ARGS.spot_check.is_some() {
+            eprintln!("{} random pairs of digraphs checked to be \
+                       non-isomorphic", sample.len() / 2);
+        }
+        if let Some(inv) = ARGS.group_by {
+            eprintln!("{inv}\tcount");
+            for (&value, count) in &groups {
+                eprintln!("{}\t{count}", Shown(value));
+            }
+        }
+        if let Some(inv) = ARGS.histogram {
+            print_histogram(inv, &histogram);
+        }
+        if let Some(k) = ARGS.iterate_cycles {
+            eprintln!("f^{k} cycle type\tcount");
+            for (t, count) in &cycle_types {
+                eprintln!("{t}\t{count}");
+            }
+        }
+    }
+    if let Some(s) = &stats {
+        s.print();
+    }
+    if ARGS.report_memory {
+        eprintln!("memory\tbytes");
+        match peak_rss() {
+            Some(rss) => eprintln!("peak-rss\t{rss}"),
+            None => eprintln!("peak-rss\tunavailable"),
+        }
+        eprintln!("largest-code\t{largest_code}");
+        eprintln!("output-buffers\t{}",
+                  (OUTPUT_QUEUE_LENGTH + 1) * OUTPUT_CHUNK_SIZE);
+        let sample_bytes: usize = sample.iter()
+            .map(|(g, _)| code_bytes(g)).sum();
+        eprintln!("spot-check-sample\t{sample_bytes}");
+        let stream_bytes: usize = stream.iter()
+            .map(|(_, g, _)| code_bytes(g)).sum();
+        eprintln!("sample-stream\t{stream_bytes}");
+        eprintln!("group-tables\t{}", table_bytes(&groups)
+                  + table_bytes(&histogram)
+                  + cycle_types.keys().map(|t: &CycleType| {
+                      mem::size_of::<CycleType>() + mem::size_of::<u64>()
+                          + t.0.capacity() * mem::size_of::<usize>()
+                  }).sum::<usize>());
+        eprintln!("stats-tables\t{}", stats.map_or(0, |s| s.bytes()));
+    }
+    if stopped.is_some() {
+        process::exit(EXIT_TEMPFAIL);
+    }
 }
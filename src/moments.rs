@@ -0,0 +1,343 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Exact expectations and variances of invariants under the uniform
+// distribution on the labelled functions of a given size, computed by
+// weighting each digraph by its number of labellings; the sums fit in
+// 128 bits, and the variances are computed with 256-bit integers
+
+
+use std::fmt::{self, Display, Formatter};
+use crate::Func;
+use crate::invariants::FuncInvariants;
+use crate::pointed::num_labellings;
+
+
+// Unsigned 256-bit integer, as four 64-bit limbs with the most
+// significant first (so that the derived order is the numeric one)
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Wide([u64; 4]);
+
+
+impl Wide {
+
+    const ZERO: Wide = Wide([0; 4]);
+
+    fn from_u128(x: u128) -> Wide {
+        Wide([0, 0, (x >> 64) as u64, x as u64])
+    }
+
+    // Full product of two 128-bit integers
+
+    fn mul(x: u128, y: u128) -> Wide {
+        let a = [(x >> 64) as u64, x as u64];
+        let b = [(y >> 64) as u64, y as u64];
+        let mut r = [0; 4];
+        for i in (0..2).rev() {
+            let mut carry = 0;
+            for j in (0..2).rev() {
+                let t = a[i] as u128 * b[j] as u128
+                    + r[i + j + 1] as u128 + carry;
+                r[i + j + 1] = t as u64;
+                carry = t >> 64;
+            }
+            r[i] = carry as u64;
+        }
+        Wide(r)
+    }
+
+    // Difference self - other, assuming self >= other
+
+    fn sub(self, other: Wide) -> Wide {
+        let mut r = [0; 4];
+        let mut borrow = false;
+        for i in (0..4).rev() {
+            let (d, b1) = self.0[i].overflowing_sub(other.0[i]);
+            let (d, b2) = d.overflowing_sub(borrow as u64);
+            r[i] = d;
+            borrow = b1 || b2;
+        }
+        Wide(r)
+    }
+
+    fn shl(self, k: u32) -> Wide {
+        let mut r = self;
+        for _ in 0..k {
+            for i in 0..4 {
+                let next = if i < 3 { r.0[i + 1] >> 63 } else { 0 };
+                r.0[i] = r.0[i] << 1 | next;
+            }
+        }
+        r
+    }
+
+    fn shr(self, k: u32) -> Wide {
+        let mut r = self;
+        for _ in 0..k {
+            for i in (0..4).rev() {
+                let prev = if i > 0 { r.0[i - 1] << 63 } else { 0 };
+                r.0[i] = r.0[i] >> 1 | prev;
+            }
+        }
+        r
+    }
+
+    fn trailing_zeros(self) -> u32 {
+        let mut k = 0;
+        for &limb in self.0.iter().rev() {
+            if limb != 0 {
+                return k + limb.trailing_zeros();
+            }
+            k += 64;
+        }
+        k
+    }
+
+    fn bits(self) -> u32 {
+        256 - self.0.iter().position(|&limb| limb != 0)
+            .map_or(256, |i| 64 * i as u32 + self.0[i].leading_zeros())
+    }
+
+    // Greatest common divisor, by the binary algorithm
+
+    fn gcd(mut a: Wide, mut b: Wide) -> Wide {
+        if a == Wide::ZERO {
+            return b;
+        }
+        if b == Wide::ZERO {
+            return a;
+        }
+        let k = a.trailing_zeros().min(b.trailing_zeros());
+        a = a.shr(a.trailing_zeros());
+        while b != Wide::ZERO {
+            b = b.shr(b.trailing_zeros());
+            if a > b {
+                (a, b) = (b, a);
+            }
+            b = b.sub(a);
+        }
+        a.shl(k)
+    }
+
+    // Quotient of self divided by d (nonzero), by long division
+
+    fn div(self, d: Wide) -> Wide {
+        let mut q = Wide::ZERO;
+        let mut r = self;
+        if r < d {
+            return q;
+        }
+        let shift = r.bits() - d.bits();
+        for k in (0..=shift).rev() {
+            let s = d.shl(k);
+            if r >= s {
+                r = r.sub(s);
+                q.0[3 - k as usize / 64] |= 1 << (k % 64);
+            }
+        }
+        q
+    }
+
+    // Quotient and remainder of self divided by a 64-bit integer
+
+    fn div_rem_small(self, d: u64) -> (Wide, u64) {
+        let mut q = [0; 4];
+        let mut rem: u128 = 0;
+        for i in 0..4 {
+            let t = rem << 64 | self.0[i] as u128;
+            q[i] = (t / d as u128) as u64;
+            rem = t % d as u128;
+        }
+        (Wide(q), rem as u64)
+    }
+
+    fn to_f64(self) -> f64 {
+        self.0.iter().fold(0.0, |x, &limb| x * 2f64.powi(64) + limb as f64)
+    }
+
+}
+
+
+impl Display for Wide {
+
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut digits = Vec::new();
+        let mut x = *self;
+        loop {
+            let (q, r) = x.div_rem_small(10);
+            digits.push(b'0' + r as u8);
+            x = q;
+            if x == Wide::ZERO {
+                break;
+            }
+        }
+        digits.reverse();
+        f.write_str(std::str::from_utf8(&digits).unwrap())
+    }
+
+}
+
+
+// Nonnegative rational number in lowest terms, displayed as p/q (or
+// just p when q = 1)
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Ratio {
+    num: Wide,
+    den: Wide,
+}
+
+
+impl Ratio {
+
+    fn new(num: Wide, den: Wide) -> Ratio {
+        let d = Wide::gcd(num, den);
+        Ratio { num: num.div(d), den: den.div(d) }
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.num.to_f64() / self.den.to_f64()
+    }
+
+}
+
+
+impl Display for Ratio {
+
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if self.den == Wide::from_u128(1) {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+
+}
+
+
+// Sums of the weights, of the weighted values and of the weighted
+// squares of the values of an integer random variable
+
+#[derive(Clone, Copy, Default)]
+pub struct Moments {
+    pub total: u128,
+    pub sum: u128,
+    pub sum_squares: u128,
+}
+
+
+impl Moments {
+
+    // Add value x observed with weight w
+
+    fn add(&mut self, x: usize, w: u128) {
+        let x = x as u128;
+        self.total += w;
+        self.sum += w * x;
+        self.sum_squares += w * x * x;
+    }
+
+    // The mean and variance are None if nothing has been observed
+    // (e.g., the invariants of the vertices of the empty function)
+
+    pub fn mean(&self) -> Option<Ratio> {
+        (self.total > 0).then(|| Ratio::new(Wide::from_u128(self.sum),
+                                            Wide::from_u128(self.total)))
+    }
+
+    // The variance is the sum of the squares times the total, minus
+    // the square of the sum, divided by the square of the total
+
+    pub fn variance(&self) -> Option<Ratio> {
+        let num = Wide::mul(self.total, self.sum_squares)
+            .sub(Wide::mul(self.sum, self.sum));
+        (self.total > 0)
+            .then(|| Ratio::new(num, Wide::mul(self.total, self.total)))
+    }
+
+}
+
+
+// Moments of the invariants of random labelled functions of a given
+// size: the tail length (number of steps needed for reaching a cycle)
+// and the length of that cycle for a random vertex, the number of
+// periodic points, the size of the image and the number of components
+
+pub struct Expectations {
+    pub size: usize,
+    pub tail_length: Moments,
+    pub cycle_length: Moments,
+    pub periodic_points: Moments,
+    pub image_size: Moments,
+    pub components: Moments,
+}
+
+
+impl Expectations {
+
+    // Names of the invariants, in the order of moments
+
+    pub const NAMES: [&'static str; 5] = [
+        "tail-length", "cycle-length", "periodic-points", "image-size",
+        "components",
+    ];
+
+    // Create empty moments for the functions with size vertices, or
+    // None if the sums might not fit in 128 bits: the total weight of
+    // the vertices is at most n^(n + 1), and the values at most n, so
+    // n^(n + 3) must fit (which happens for n up to 24)
+
+    pub fn new(size: usize) -> Option<Expectations> {
+        let mut bound: u128 = 1;
+        for _ in 0..size + 3 {
+            bound = bound.checked_mul(size as u128)?;
+        }
+        Some(Expectations {
+            size,
+            tail_length: Moments::default(),
+            cycle_length: Moments::default(),
+            periodic_points: Moments::default(),
+            image_size: Moments::default(),
+            components: Moments::default(),
+        })
+    }
+
+    // Add digraph g, weighted by its number of labellings; the
+    // invariants of the vertices are weighted by the vertex too, so
+    // that they refer to a uniformly random vertex
+
+    pub fn add(&mut self, g: &Func) {
+        let w = num_labellings(g).unwrap();
+        for (tail, cycle) in g.vertex_orbits() {
+            self.tail_length.add(tail, w);
+            self.cycle_length.add(cycle, w);
+        }
+        self.periodic_points.add(g.num_periodic_points(), w);
+        self.image_size.add(self.size - g.num_leaves(), w);
+        self.components.add(g.num_components(), w);
+    }
+
+    // The moments, in the order of NAMES
+
+    pub fn moments(&self) -> [&Moments; 5] {
+        [&self.tail_length, &self.cycle_length, &self.periodic_points,
+         &self.image_size, &self.components]
+    }
+
+}
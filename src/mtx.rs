@@ -0,0 +1,87 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Output of the adjacency matrices in the Matrix Market exchange
+// format (https://math.nist.gov/MatrixMarket/formats.html), as read
+// by scipy.io.mmread, MATLAB's mmread.m and other sparse linear
+// algebra software
+
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use crate::Func;
+use crate::digraph6::func_adj;
+use crate::pointed::swap_adj;
+
+
+// Write the adjacency matrix of functional digraph g to out as a
+// Matrix Market file, i.e., a sparse matrix in coordinate format with
+// a pattern entry (row, column) for each edge, both numbered from 1;
+// the vertices are named as in digraph6 format, the distinguished
+// vertex v (if any) being renamed as vertex 0 (i.e., row 1), and the
+// internal code of g is written in a comment line
+
+pub fn write_mtx(out: &mut dyn Write, g: &Func, v: Option<usize>)
+                 -> io::Result<()> {
+    let a = match v {
+        Some(v) => swap_adj(&func_adj(g), v),
+        None => func_adj(g),
+    };
+    writeln!(out, "%%MatrixMarket matrix coordinate pattern general")?;
+    writeln!(out, "% funkdigen2 {g:?}")?;
+    writeln!(out, "{n} {n} {n}", n = a.len())?;
+    for (x, &y) in a.iter().enumerate() {
+        writeln!(out, "{} {}", x + 1, y as usize + 1)?;
+    }
+    Ok(())
+}
+
+
+// Writer of functional digraphs as Matrix Market files in a
+// directory, one file per digraph named after its rank (its position
+// among the written digraphs, starting from 0), e.g., 0.mtx, 1.mtx
+
+pub struct MtxWriter {
+    dir: PathBuf,
+    rows: u64,
+}
+
+
+impl MtxWriter {
+
+    // Create a writer to directory dir, which is created if needed
+
+    pub fn create(dir: &Path) -> io::Result<MtxWriter> {
+        fs::create_dir_all(dir)?;
+        Ok(MtxWriter { dir: dir.to_path_buf(), rows: 0 })
+    }
+
+    // Write functional digraph g (with distinguished vertex v, if
+    // any) to the file of the next rank
+
+    pub fn write(&mut self, g: &Func, v: Option<usize>) -> io::Result<()> {
+        let path = self.dir.join(format!("{}.mtx", self.rows));
+        let mut out = BufWriter::new(File::create(path)?);
+        write_mtx(&mut out, g, v)?;
+        out.flush()?;
+        self.rows += 1;
+        Ok(())
+    }
+
+}
@@ -0,0 +1,152 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Enumeration of the labelled functions having a given functional
+// digraph, i.e., of the orbit of a representative under relabelling
+
+
+use crate::{Tree, Func, comp_size};
+use crate::digraph6::func_adj;
+use crate::pointed::rotation_period;
+
+
+// Add to deps, for each vertex of the subtree of t having root at
+// position i (named b + i), the blocks whose labels bound its own:
+// each subtree isomorphic to its previous sibling must only contain
+// labels larger than the minimum label of the latter
+
+fn add_tree_deps(t: &Tree, i: usize, b: usize,
+                 deps: &mut [Vec<(usize, usize)>]) {
+    let mut prev: Option<(usize, usize)> = None;
+    let mut j = i + 1;
+    while j < i + t[i] as usize {
+        let k = j + t[j] as usize;
+        if let Some((p, q)) = prev {
+            if t[p..q] == t[j..k] {
+                for v in b + j..b + k {
+                    deps[v].push((b + p, b + q));
+                }
+            }
+        }
+        add_tree_deps(t, j, b, deps);
+        prev = Some((j, k));
+        j = k;
+    }
+}
+
+
+// Generate all labelled functions on vertices 0..n whose functional
+// digraph is g, pass the table of each of them (the image of each
+// vertex) to emit, and return their number, i.e., n! divided by the
+// number of automorphisms of g. The vertices of the representative
+// of g computed by func_adj are labelled in all ways such that, for
+// each block of vertices (a subtree or a component) isomorphic to the
+// previous one in the same run of isomorphic siblings, all labels are
+// larger than the minimum one of the previous block, and the roots
+// of the trees along a cycle which are equivalent under rotations to
+// the first one have larger labels than it. This chooses exactly one
+// labelling for each function, and the labellings are built by
+// backtracking one vertex at a time, checking these bounds as soon
+// as possible; only O(n) memory is used
+
+pub fn generate_labellings<E>(g: &Func,
+                              emit: &mut dyn FnMut(&[usize]) -> Result<(), E>)
+                              -> Result<u64, E> {
+    let a: Vec<usize> = func_adj(g).iter().map(|&w| w as usize).collect();
+    let n = a.len();
+    let mut deps = vec![Vec::new(); n];
+    let mut b = 0;
+    for i in 0..g.len() {
+        let c = &g[i];
+        let m = comp_size(c);
+        if i > 0 && g[i] == g[i - 1] {
+            for v in b..b + m {
+                deps[v].push((b - m, b));
+            }
+        }
+        let p = rotation_period(c);
+        let mut r = b;
+        for j in 0..c.len() {
+            if j > 0 && j % p == 0 {
+                deps[r].push((b, b + 1));
+            }
+            add_tree_deps(&c[j], 0, r, &mut deps);
+            r += c[j].len();
+        }
+        b += m;
+    }
+    let mut state = Labelling {
+        a: &a,
+        deps: &deps,
+        label: vec![0; n],
+        used: vec![false; n],
+        f: vec![0; n],
+        count: 0,
+    };
+    state.extend(0, emit)?;
+    Ok(state.count)
+}
+
+
+// State of the backtracking in generate_labellings: the label of each
+// vertex of the representative with adjacency vector a assigned so
+// far, which labels are used, and the function built at the end
+
+struct Labelling<'a> {
+    a: &'a [usize],
+    deps: &'a [Vec<(usize, usize)>],
+    label: Vec<usize>,
+    used: Vec<bool>,
+    f: Vec<usize>,
+    count: u64,
+}
+
+
+impl Labelling<'_> {
+
+    // Label the vertices from v onwards in all possible ways
+
+    fn extend<E>(&mut self, v: usize,
+                 emit: &mut dyn FnMut(&[usize]) -> Result<(), E>)
+                 -> Result<(), E> {
+        let n = self.a.len();
+        if v == n {
+            for x in 0..n {
+                self.f[self.label[x]] = self.label[self.a[x]];
+            }
+            self.count += 1;
+            return emit(&self.f);
+        }
+        // The smallest label allowed for v
+        let mut low = 0;
+        for &(p, q) in &self.deps[v] {
+            let min = self.label[p..q].iter().min().unwrap();
+            low = low.max(min + 1);
+        }
+        for l in low..n {
+            if !self.used[l] {
+                self.used[l] = true;
+                self.label[v] = l;
+                self.extend(v + 1, emit)?;
+                self.used[l] = false;
+            }
+        }
+        Ok(())
+    }
+
+}
@@ -0,0 +1,145 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Total order on isomorphism codes consistent with the order in which
+// they are generated
+
+
+use std::cmp::{Ordering, Reverse};
+use crate::{Comp, Func, Part, comp_size, part, unmerge};
+
+
+// Compute the path from the cycle of the same size as component c up
+// to c in the reverse search tree, as the list of the indices l, r of
+// each merge. The children of a component are generated by
+// decreasing l and then by increasing r, so the keys returned can be
+// compared lexicographically
+
+pub(crate) fn merge_path(c: &Comp) -> Vec<(Reverse<usize>, usize)> {
+    let mut path = Vec::new();
+    let mut res = unmerge(c);
+    while let Some((u, l, r)) = res {
+        path.push((Reverse(l), r));
+        res = unmerge(&u);
+    }
+    path.reverse();
+    path
+}
+
+
+// Compare components a and b according to the order in which they are
+// generated: components with fewer vertices come first and, among
+// those with the same number of vertices, the reverse search tree is
+// visited in preorder, so that a component comes before all of its
+// descendants, and these before its next sibling
+
+pub fn cmp_comps(a: &Comp, b: &Comp) -> Ordering {
+    comp_size(a).cmp(&comp_size(b))
+        .then_with(|| merge_path(a).cmp(&merge_path(b)))
+}
+
+
+// Compare functional digraphs g and h according to the order in which
+// they are generated: digraphs with fewer vertices come first, then
+// partitions (the sizes of the components) are compared
+// lexicographically and, for the same partition, the lists of
+// components are compared lexicographically according to cmp_comps
+
+pub fn cmp_funcs(g: &Func, h: &Func) -> Ordering {
+    let (p, q) = (part(g), part(h));
+    p.iter().map(|&k| k as usize).sum::<usize>()
+        .cmp(&q.iter().map(|&k| k as usize).sum())
+        .then_with(|| p.cmp(&q))
+        .then_with(|| {
+            for i in 0..g.len() {
+                match cmp_comps(&g[i], &h[i]) {
+                    Ordering::Equal => (),
+                    ord => return ord,
+                }
+            }
+            Ordering::Equal
+        })
+}
+
+
+// Key of a functional digraph whose natural order is the same as
+// cmp_funcs: its size, its partition and the merge paths of its
+// components. Computing the key once is faster when the same digraph
+// is compared many times, e.g., when merging sorted lists of digraphs
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FuncKey(usize, Part, Vec<Vec<(Reverse<usize>, usize)>>);
+
+
+impl FuncKey {
+
+    pub fn new(g: &Func) -> FuncKey {
+        let p = part(g);
+        FuncKey(p.iter().map(|&k| k as usize).sum(), p,
+                g.iter().map(|c| merge_path(c)).collect())
+    }
+
+    // Number of vertices of the digraph
+
+    pub fn size(&self) -> usize {
+        self.0
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use crate::{Emit, Func, generate_comps, generate_funcs};
+    use crate::code::Code;
+    use crate::error::Error;
+
+
+    // Check that the digraphs generated by generate for all sizes up to
+    // 10, in increasing order of size, are strictly increasing as Codes
+
+    fn check_increasing(generate: fn(usize, &mut Emit<Error>)
+                                     -> Result<u64, Error>) {
+        let mut last: Option<Code> = None;
+        for n in 0..=10 {
+            let res = generate(n, &mut |g: &Func| {
+                let code = Code(g.clone());
+                if let Some(prev) = &last {
+                    assert!(*prev < code, "{prev} generated before {code}");
+                }
+                last = Some(code);
+                Ok(())
+            });
+            assert!(res.is_ok());
+        }
+    }
+
+
+    #[test]
+    fn funcs_in_order() {
+        check_increasing(generate_funcs);
+    }
+
+
+    #[test]
+    fn comps_in_order() {
+        check_increasing(generate_comps);
+    }
+
+}
@@ -0,0 +1,66 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Bit-packed prefixes of the tree codes, for comparing trees one
+// machine word at a time.
+// Packing whole codes into nibbles is only possible for trees of at
+// most 15 vertices, since the first entry of a code is the number of
+// vertices, and packing trees as balanced-parentheses bitstrings does
+// not preserve the order of the codes (already for 7 vertices,
+// [7, 3, 2, 1, 3, 2, 1] has a smaller code but a larger bitstring
+// than [7, 6, 1, 1, 1, 1, 1]), so it cannot replace the codes without
+// changing the generation order. Instead, the packed prefix of a code
+// keeps its first entry as a byte and the next 14 entries as nibbles,
+// up to the first one which is at least 15: this describes trees of
+// at most 15 vertices exactly, and still gives the order of the
+// others in most cases
+
+
+use crate::Tree;
+
+
+// Number of vertices up to which trees are determined by their
+// packed prefixes
+
+pub const PACKED_EXACT_LEN: usize = 15;
+
+
+// Compute the packed prefix of tree t: its number of vertices in the
+// most significant byte, followed by the next 14 entries of its code
+// (or as many as there are), each one in a nibble, and padded with
+// zeros. An entry of at least 15 is written as 15 and ends the
+// prefix, since the entries after it cannot be compared with those
+// of another tree unless the two entries are known to be equal. Two
+// prefixes thus first differ at an entry which is exact in both, or
+// exact in one and at least 15 in the other, and the codes never
+// contain zeros, so if the packed prefix of a is smaller than that of
+// b then a < b. Trees of at most PACKED_EXACT_LEN vertices (whose
+// entries after the first are all below 15) have equal packed
+// prefixes only if they are equal; the others must be compared in
+// full when their packed prefixes are equal
+
+pub fn packed_prefix(t: &Tree) -> u64 {
+    let mut x = (t[0] as u64) << 56;
+    for i in 1..t.len().min(PACKED_EXACT_LEN) {
+        x |= (t[i].min(15) as u64) << (56 - 4 * i);
+        if t[i] >= 15 {
+            break;
+        }
+    }
+    x
+}
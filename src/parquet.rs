@@ -0,0 +1,701 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Output in the Apache Parquet columnar format
+// (https://parquet.apache.org), available with the arrow feature
+
+
+use std::io::{self, Write};
+use std::mem;
+use crate::Func;
+use crate::digraph6::{func_adj, write_digraph6_code};
+use crate::invariants::{FuncInvariants, Invariant, Value};
+use crate::pointed::swap_adj;
+
+
+// Maximum number of rows of a row group, which are kept in memory
+// until the row group is written
+
+const ROW_GROUP_SIZE: usize = 1 << 17;
+
+
+// Magic number at the beginning and at the end of a Parquet file
+
+const MAGIC: &[u8; 4] = b"PAR1";
+
+
+// Type codes of the Thrift compact protocol, used for the metadata
+
+const T_I32: u8 = 5;
+const T_I64: u8 = 6;
+const T_BINARY: u8 = 8;
+const T_LIST: u8 = 9;
+const T_STRUCT: u8 = 12;
+
+
+// Physical types, repetition type and converted type of the Parquet
+// columns used here
+
+const INT64: i32 = 2;
+const BYTE_ARRAY: i32 = 6;
+const REQUIRED: i32 = 0;
+const OPTIONAL: i32 = 1;
+const UTF8: i32 = 0;
+
+
+// Encodings of the Parquet pages used here
+
+const PLAIN: i32 = 0;
+const RLE: i32 = 3;
+
+
+// Encoder for the Thrift compact protocol, keeping track of the last
+// field identifier of each open struct (field identifiers are encoded
+// as differences from the previous one)
+
+#[derive(Default)]
+struct Thrift {
+    buf: Vec<u8>,
+    last: i16,
+    stack: Vec<i16>,
+}
+
+
+impl Thrift {
+
+    fn varint(&mut self, mut x: u64) {
+        while x >= 0x80 {
+            self.buf.push((x & 0x7f) as u8 | 0x80);
+            x >>= 7;
+        }
+        self.buf.push(x as u8);
+    }
+
+    fn zigzag(&mut self, x: i64) {
+        self.varint(((x << 1) ^ (x >> 63)) as u64);
+    }
+
+    fn field(&mut self, id: i16, ty: u8) {
+        let delta = id - self.last;
+        if (1..=15).contains(&delta) {
+            self.buf.push((delta as u8) << 4 | ty);
+        } else {
+            self.buf.push(ty);
+            self.zigzag(id as i64);
+        }
+        self.last = id;
+    }
+
+    fn i32(&mut self, id: i16, x: i32) {
+        self.field(id, T_I32);
+        self.zigzag(x as i64);
+    }
+
+    fn i64(&mut self, id: i16, x: i64) {
+        self.field(id, T_I64);
+        self.zigzag(x);
+    }
+
+    fn binary(&mut self, id: i16, s: &[u8]) {
+        self.field(id, T_BINARY);
+        self.varint(s.len() as u64);
+        self.buf.extend_from_slice(s);
+    }
+
+    // Begin a list of n elements of type ty; the elements are then
+    // encoded with the *_elem methods, or with begin, followed by
+    // their fields and end, for structs
+
+    fn list(&mut self, id: i16, ty: u8, n: usize) {
+        self.field(id, T_LIST);
+        if n < 15 {
+            self.buf.push((n as u8) << 4 | ty);
+        } else {
+            self.buf.push(0xf0 | ty);
+            self.varint(n as u64);
+        }
+    }
+
+    fn i32_elem(&mut self, x: i32) {
+        self.zigzag(x as i64);
+    }
+
+    fn binary_elem(&mut self, s: &[u8]) {
+        self.varint(s.len() as u64);
+        self.buf.extend_from_slice(s);
+    }
+
+    fn begin(&mut self) {
+        self.stack.push(self.last);
+        self.last = 0;
+    }
+
+    fn struct_field(&mut self, id: i16) {
+        self.field(id, T_STRUCT);
+        self.begin();
+    }
+
+    fn end(&mut self) {
+        self.buf.push(0);
+        self.last = self.stack.pop().unwrap_or(0);
+    }
+
+}
+
+
+// Buffered values of an integer column, plain-encoded, and their
+// definition levels (whether each row has a value or a null) if the
+// column is optional
+
+#[derive(Clone, Default)]
+struct IntColumn {
+    data: Vec<u8>,
+    levels: Option<Vec<bool>>,
+}
+
+
+// Encode levels as the definition levels of a data page, with a bit
+// width of 1: a single bit-packed run (of groups of 8 levels, the
+// last one padded with zeros) preceded by its length in bytes
+
+fn encode_levels(levels: &[bool]) -> Vec<u8> {
+    let mut run = Thrift::default();
+    run.varint((levels.len().div_ceil(8) as u64) << 1 | 1);
+    for group in levels.chunks(8) {
+        run.buf.push(group.iter().rev().fold(0, |b, &l| b << 1 | l as u8));
+    }
+    let mut buf = (run.buf.len() as u32).to_le_bytes().to_vec();
+    buf.extend(run.buf);
+    buf
+}
+
+
+// Metadata of a column chunk already written
+
+struct Chunk {
+    ty: i32,
+    offset: u64,
+    size: u64,
+}
+
+
+// Metadata of a row group already written
+
+struct RowGroup {
+    rows: usize,
+    chunks: Vec<Chunk>,
+}
+
+
+// Writer of functional digraphs as the rows of a Parquet file, with
+// a column for the digraph6 code of the digraph (as a string), one
+// for its distinguished vertex if pointed is true (renamed as vertex
+// 0 in the digraph6 code, as in the digraph6 output), one for its
+// size and one for each of the given invariants (with its name in
+// snake case, e.g., periodic_points), as 64-bit integers; those of
+// the unbounded invariants are optional, with a null for the values
+// which do not fit into an i64 (such as large numbers of
+// automorphisms). The pages are plain-encoded and uncompressed; the
+// rows are buffered in memory and written ROW_GROUP_SIZE at a time,
+// and the metadata is written by finish
+
+pub struct ParquetWriter {
+    pointed: bool,
+    invariants: Vec<&'static dyn Invariant>,
+    codes: Vec<u8>,
+    ints: Vec<IntColumn>,
+    rows: usize,
+    offset: u64,
+    row_groups: Vec<RowGroup>,
+}
+
+
+impl ParquetWriter {
+
+    pub fn new(pointed: bool, invariants: &[&'static dyn Invariant])
+               -> ParquetWriter {
+        let mut ints = vec![IntColumn::default(); 1 + pointed as usize];
+        ints.extend(invariants.iter().map(|inv| IntColumn {
+            data: Vec::new(),
+            levels: (!inv.is_bounded()).then(Vec::new),
+        }));
+        ParquetWriter {
+            pointed,
+            invariants: invariants.to_vec(),
+            codes: Vec::new(),
+            ints,
+            rows: 0,
+            offset: 0,
+            row_groups: Vec::new(),
+        }
+    }
+
+    // Names of the columns, in order
+
+    fn column_names(&self) -> Vec<String> {
+        let mut names = vec!["digraph".to_string()];
+        if self.pointed {
+            names.push("vertex".to_string());
+        }
+        names.push("size".to_string());
+        for inv in &self.invariants {
+            names.push(inv.name().replace('-', "_"));
+        }
+        names
+    }
+
+    // Write the magic number to out, if nothing was written yet
+
+    fn start(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        if self.offset == 0 {
+            out.write_all(MAGIC)?;
+            self.offset = MAGIC.len() as u64;
+        }
+        Ok(())
+    }
+
+    // Add the row of functional digraph g, with distinguished vertex
+    // v if the writer is pointed, writing the buffered rows to out if
+    // they fill a row group
+
+    pub fn write(&mut self, out: &mut dyn Write, g: &Func, v: Option<usize>)
+                 -> io::Result<()> {
+        let mut a = func_adj(g);
+        if let Some(v) = v {
+            a = swap_adj(&a, v);
+        }
+        let mut code = Vec::new();
+        write_digraph6_code(&mut code, &a, false)?;
+        self.codes.extend_from_slice(&(code.len() as u32).to_le_bytes());
+        self.codes.extend_from_slice(&code);
+        let mut values = Vec::new();
+        if self.pointed {
+            values.push(v.unwrap_or(0) as Value);
+        }
+        values.push(g.size() as Value);
+        values.extend(self.invariants.iter().map(|inv| inv.compute(g)));
+        // The values of the required columns are at most the number of
+        // vertices, so they always fit
+        for (col, x) in self.ints.iter_mut().zip(values) {
+            let x = i64::try_from(x).ok();
+            if let Some(levels) = &mut col.levels {
+                levels.push(x.is_some());
+            }
+            if let Some(x) = x {
+                col.data.extend_from_slice(&x.to_le_bytes());
+            }
+        }
+        self.rows += 1;
+        if self.rows == ROW_GROUP_SIZE {
+            self.write_row_group(out)?;
+        }
+        Ok(())
+    }
+
+    // Write a column chunk made of a single data page, containing the
+    // given plain-encoded values of type ty, preceded by their encoded
+    // definition levels, if the column is optional
+
+    fn write_chunk(&mut self, out: &mut dyn Write, ty: i32, levels: &[u8],
+                   data: &[u8]) -> io::Result<Chunk> {
+        let len = levels.len() + data.len();
+        let mut header = Thrift::default();
+        header.i32(1, 0);
+        header.i32(2, len as i32);
+        header.i32(3, len as i32);
+        header.struct_field(5);
+        header.i32(1, self.rows as i32);
+        header.i32(2, PLAIN);
+        header.i32(3, RLE);
+        header.i32(4, RLE);
+        header.end();
+        header.end();
+        out.write_all(&header.buf)?;
+        out.write_all(levels)?;
+        out.write_all(data)?;
+        let chunk = Chunk {
+            ty,
+            offset: self.offset,
+            size: (header.buf.len() + len) as u64,
+        };
+        self.offset += chunk.size;
+        Ok(chunk)
+    }
+
+    // Write the buffered rows to out as a row group
+
+    fn write_row_group(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        self.start(out)?;
+        let codes = mem::take(&mut self.codes);
+        let mut chunks = vec![self.write_chunk(out, BYTE_ARRAY, &[],
+                                               &codes)?];
+        for i in 0..self.ints.len() {
+            let data = mem::take(&mut self.ints[i].data);
+            let levels = self.ints[i].levels.as_mut().map(mem::take)
+                .map_or(Vec::new(), |l| encode_levels(&l));
+            chunks.push(self.write_chunk(out, INT64, &levels, &data)?);
+        }
+        self.row_groups.push(RowGroup { rows: self.rows, chunks });
+        self.rows = 0;
+        Ok(())
+    }
+
+    // Write the remaining rows and the metadata (schema and row
+    // groups) to out, completing the file
+
+    pub fn finish(mut self, out: &mut dyn Write) -> io::Result<()> {
+        if self.rows > 0 {
+            self.write_row_group(out)?;
+        }
+        self.start(out)?;
+        let names = self.column_names();
+        let total: usize = self.row_groups.iter().map(|r| r.rows).sum();
+        let mut meta = Thrift::default();
+        meta.i32(1, 1);
+        meta.list(2, T_STRUCT, names.len() + 1);
+        meta.begin();
+        meta.binary(4, b"schema");
+        meta.i32(5, names.len() as i32);
+        meta.end();
+        for (i, name) in names.iter().enumerate() {
+            let optional = i > 0 && self.ints[i - 1].levels.is_some();
+            meta.begin();
+            meta.i32(1, if i == 0 { BYTE_ARRAY } else { INT64 });
+            meta.i32(3, if optional { OPTIONAL } else { REQUIRED });
+            meta.binary(4, name.as_bytes());
+            if i == 0 {
+                meta.i32(6, UTF8);
+            }
+            meta.end();
+        }
+        meta.i64(3, total as i64);
+        meta.list(4, T_STRUCT, self.row_groups.len());
+        for r in &self.row_groups {
+            meta.begin();
+            meta.list(1, T_STRUCT, r.chunks.len());
+            for (c, name) in r.chunks.iter().zip(&names) {
+                meta.begin();
+                meta.i64(2, c.offset as i64);
+                meta.struct_field(3);
+                meta.i32(1, c.ty);
+                meta.list(2, T_I32, 1);
+                meta.i32_elem(PLAIN);
+                meta.list(3, T_BINARY, 1);
+                meta.binary_elem(name.as_bytes());
+                meta.i32(4, 0);
+                meta.i64(5, r.rows as i64);
+                meta.i64(6, c.size as i64);
+                meta.i64(7, c.size as i64);
+                meta.i64(9, c.offset as i64);
+                meta.end();
+                meta.end();
+            }
+            let size: u64 = r.chunks.iter().map(|c| c.size).sum();
+            meta.i64(2, size as i64);
+            meta.i64(3, r.rows as i64);
+            meta.end();
+        }
+        meta.binary(6, concat!("funkdigen2 version ",
+                               env!("CARGO_PKG_VERSION")).as_bytes());
+        meta.end();
+        out.write_all(&meta.buf)?;
+        out.write_all(&(meta.buf.len() as u32).to_le_bytes())?;
+        out.write_all(MAGIC)
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+
+    use crate::{Func, generate_funcs};
+    use crate::canon::canon_labelling;
+    use crate::digraph6::{func_adj, write_digraph6_code};
+    use crate::error::Error;
+    use crate::invariants::find_invariant;
+    use super::*;
+
+
+    // Value of the Thrift compact protocol, with the fields of the
+    // structs by identifier
+
+    #[derive(Debug)]
+    enum Thing {
+        Int(i64),
+        Binary(Vec<u8>),
+        List(Vec<Thing>),
+        Struct(Vec<(i16, Thing)>),
+    }
+
+
+    impl Thing {
+
+        fn field(&self, id: i16) -> &Thing {
+            let Thing::Struct(fields) = self else {
+                panic!("not a struct: {self:?}");
+            };
+            fields.iter().find(|(i, _)| *i == id).map(|(_, v)| v)
+                .unwrap_or_else(|| panic!("no field {id} in {self:?}"))
+        }
+
+        fn int(&self) -> i64 {
+            match self {
+                Thing::Int(x) => *x,
+                _ => panic!("not an integer: {self:?}"),
+            }
+        }
+
+        fn list(&self) -> &[Thing] {
+            match self {
+                Thing::List(items) => items,
+                _ => panic!("not a list: {self:?}"),
+            }
+        }
+
+    }
+
+
+    // Decoder of the Thrift compact protocol, independent from the
+    // encoder, reading buf from position pos
+
+    struct Reader<'a> {
+        buf: &'a [u8],
+        pos: usize,
+    }
+
+
+    impl Reader<'_> {
+
+        fn byte(&mut self) -> u8 {
+            self.pos += 1;
+            self.buf[self.pos - 1]
+        }
+
+        fn varint(&mut self) -> u64 {
+            let (mut x, mut shift) = (0, 0);
+            loop {
+                let b = self.byte();
+                x |= ((b & 0x7f) as u64) << shift;
+                if b < 0x80 {
+                    return x;
+                }
+                shift += 7;
+            }
+        }
+
+        fn zigzag(&mut self) -> i64 {
+            let x = self.varint();
+            (x >> 1) as i64 ^ -((x & 1) as i64)
+        }
+
+        fn value(&mut self, ty: u8) -> Thing {
+            match ty {
+                T_I32 | T_I64 => Thing::Int(self.zigzag()),
+                T_BINARY => {
+                    let len = self.varint() as usize;
+                    self.pos += len;
+                    Thing::Binary(self.buf[self.pos - len..self.pos].to_vec())
+                }
+                T_LIST => {
+                    let b = self.byte();
+                    let n = match b >> 4 {
+                        15 => self.varint() as usize,
+                        n => n as usize,
+                    };
+                    Thing::List((0..n).map(|_| self.value(b & 0xf))
+                                .collect())
+                }
+                T_STRUCT => {
+                    let mut fields = Vec::new();
+                    let mut last = 0;
+                    loop {
+                        let b = self.byte();
+                        if b == 0 {
+                            return Thing::Struct(fields);
+                        }
+                        last = match b >> 4 {
+                            0 => self.zigzag() as i16,
+                            delta => last + delta as i16,
+                        };
+                        fields.push((last, self.value(b & 0xf)));
+                    }
+                }
+                _ => panic!("unexpected type {ty}"),
+            }
+        }
+
+    }
+
+
+    // Decode the definition levels (with a bit width of 1) of rows
+    // values at the beginning of data, given as a length followed by
+    // RLE and bit-packed runs, returning them with the rest of data
+
+    fn read_levels(data: &[u8], rows: usize) -> (Vec<bool>, &[u8]) {
+        let len = u32::from_le_bytes(data[..4].try_into().unwrap()) as usize;
+        let mut r = Reader { buf: &data[4..4 + len], pos: 0 };
+        let mut levels = Vec::new();
+        while r.pos < len {
+            let header = r.varint() as usize;
+            if header & 1 == 0 {
+                let l = r.byte() != 0;
+                levels.extend((0..header >> 1).map(|_| l));
+            } else {
+                for _ in 0..header >> 1 {
+                    let b = r.byte();
+                    levels.extend((0..8).map(|k| b >> k & 1 != 0));
+                }
+            }
+        }
+        assert!(levels.len() >= rows);
+        levels.truncate(rows);
+        (levels, &data[4 + len..])
+    }
+
+
+    // Contents of a Parquet file: the names of the columns, whether
+    // each one is optional, the digraph6 codes and the values of the
+    // integer columns (None for nulls)
+
+    struct Contents {
+        names: Vec<String>,
+        optional: Vec<bool>,
+        codes: Vec<Vec<u8>>,
+        ints: Vec<Vec<Option<i64>>>,
+    }
+
+
+    // Read back a file, from its metadata and its pages
+
+    fn read(file: &[u8]) -> Contents {
+        assert!(file.starts_with(MAGIC) && file.ends_with(MAGIC));
+        let len = file.len();
+        let meta_len = u32::from_le_bytes(file[len - 8..len - 4]
+                                          .try_into().unwrap()) as usize;
+        let meta = Reader { buf: file, pos: len - 8 - meta_len }
+            .value(T_STRUCT);
+        let columns = &meta.field(2).list()[1..];
+        let names: Vec<_> = columns.iter()
+            .map(|s| match s.field(4) {
+                Thing::Binary(b) => String::from_utf8(b.clone()).unwrap(),
+                s => panic!("not a name: {s:?}"),
+            })
+            .collect();
+        let optional: Vec<_> = columns.iter()
+            .map(|s| s.field(3).int() == OPTIONAL as i64)
+            .collect();
+        let mut codes = Vec::new();
+        let mut ints = vec![Vec::new(); names.len() - 1];
+        for group in meta.field(4).list() {
+            let rows = group.field(3).int() as usize;
+            for (i, chunk) in group.field(1).list().iter().enumerate() {
+                let mut r = Reader {
+                    buf: file,
+                    pos: chunk.field(3).field(9).int() as usize,
+                };
+                let header = r.value(T_STRUCT);
+                assert_eq!(header.field(5).field(1).int() as usize, rows);
+                let size = header.field(3).int() as usize;
+                let mut data = &file[r.pos..r.pos + size];
+                let levels = if optional[i] {
+                    let (levels, rest) = read_levels(data, rows);
+                    data = rest;
+                    levels
+                } else {
+                    vec![true; rows]
+                };
+                for l in levels {
+                    if i == 0 {
+                        let (l, rest) = data.split_at(4);
+                        let l = u32::from_le_bytes(l.try_into().unwrap());
+                        let (code, rest) = rest.split_at(l as usize);
+                        codes.push(code.to_vec());
+                        data = rest;
+                    } else if l {
+                        let (x, rest) = data.split_at(8);
+                        ints[i - 1].push(Some(i64::from_le_bytes(
+                            x.try_into().unwrap())));
+                        data = rest;
+                    } else {
+                        ints[i - 1].push(None);
+                    }
+                }
+                assert!(data.is_empty());
+            }
+        }
+        assert_eq!(meta.field(3).int() as usize, codes.len());
+        Contents { names, optional, codes, ints }
+    }
+
+
+    // Write all digraphs with 7 vertices, with three invariants, and
+    // read them back
+
+    #[test]
+    fn round_trip() {
+        let invariants = [find_invariant("height").unwrap(),
+                          find_invariant("periodic-points").unwrap(),
+                          find_invariant("aut").unwrap()];
+        let mut writer = ParquetWriter::new(false, &invariants);
+        let mut file = Vec::new();
+        let mut funcs: Vec<Func> = Vec::new();
+        let res: Result<u64, Error> = generate_funcs(7, &mut |g| {
+            funcs.push(g.clone());
+            Ok(writer.write(&mut file, g, None)?)
+        });
+        assert_eq!(res.ok(), Some(343));
+        writer.finish(&mut file).unwrap();
+        let c = read(&file);
+        assert_eq!(c.names, ["digraph", "size", "height", "periodic_points",
+                             "aut"]);
+        assert_eq!(c.optional, [false, false, false, false, true]);
+        assert_eq!(c.codes.len(), funcs.len());
+        for (k, g) in funcs.iter().enumerate() {
+            let mut code = Vec::new();
+            write_digraph6_code(&mut code, &func_adj(g), false).unwrap();
+            assert_eq!(c.codes[k], code);
+            assert_eq!(c.ints[0][k], Some(7));
+            for (j, inv) in invariants.iter().enumerate() {
+                assert_eq!(c.ints[j + 1][k], Some(inv.compute(g) as i64));
+            }
+        }
+    }
+
+
+    // Check that the numbers of automorphisms of the identities on 20,
+    // 21 and 36 points (20!, which fits into an i64, 21!, which does
+    // not, and 36!, which overflows) are written as 20! and two nulls
+
+    #[test]
+    fn large_values() {
+        let mut writer = ParquetWriter::new(false,
+                                            &[find_invariant("aut").unwrap()]);
+        let mut file = Vec::new();
+        for n in [20, 21, 36] {
+            let id: Vec<usize> = (0..n).collect();
+            let g = canon_labelling(&id).unwrap().0;
+            writer.write(&mut file, &g, None).unwrap();
+        }
+        writer.finish(&mut file).unwrap();
+        let c = read(&file);
+        let factorial: i64 = (1..=20).product();
+        assert_eq!(c.ints[1], [Some(factorial), None, None]);
+    }
+
+}
@@ -0,0 +1,564 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Parsing of the textual representations of isomorphism codes
+// printed by funkdigen2, i.e., the internal one (lists of lists of
+// lists of integers), the nested-bracket one, the Newick one and
+// the keys, and of function tables
+
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::rc::Rc;
+use crate::{Tree, Comp, Func};
+use crate::format::{COMPACT_DIGITS, KEY_ALPHABET};
+
+
+// Error found while parsing, with the position (in bytes) of the
+// offending character in the input string
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub pos: usize,
+    pub msg: String,
+}
+
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.msg, self.pos)
+    }
+}
+
+
+impl Error for ParseError {}
+
+
+// State of the parser: the input string and the current position
+
+struct Parser<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+
+impl Parser<'_> {
+
+    fn error<T>(&self, msg: &str) -> Result<T, ParseError> {
+        Err(ParseError { pos: self.pos, msg: msg.to_string() })
+    }
+
+    // Skip whitespace and return the next character, if any
+
+    fn peek(&mut self) -> Option<u8> {
+        while self.pos < self.s.len()
+            && self.s[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        self.s.get(self.pos).copied()
+    }
+
+    // Consume character c, skipping whitespace before it
+
+    fn expect(&mut self, c: u8) -> Result<(), ParseError> {
+        if self.peek() != Some(c) {
+            return self.error(&format!("expected '{}'", c as char));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    // Consume character c if it is the next one, and report whether
+    // this happened
+
+    fn accept(&mut self, c: u8) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    // Parse an integer in the range 1..255
+
+    fn int(&mut self) -> Result<u8, ParseError> {
+        self.peek();
+        let start = self.pos;
+        while self.pos < self.s.len() && self.s[self.pos].is_ascii_digit() {
+            self.pos += 1;
+        }
+        let digits = std::str::from_utf8(&self.s[start..self.pos]).unwrap();
+        match digits.parse::<u8>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => {
+                self.pos = start;
+                self.error("expected an integer between 1 and 255")
+            }
+        }
+    }
+
+    // Parse a tree in internal format, i.e., a list of subtree sizes
+    // in preorder, checking that the sizes are consistent
+
+    fn internal_tree(&mut self) -> Result<Tree, ParseError> {
+        self.peek();
+        let start = self.pos;
+        self.expect(b'[')?;
+        let mut t = vec![self.int()?];
+        while self.accept(b',') {
+            t.push(self.int()?);
+        }
+        self.expect(b']')?;
+        if !is_valid_tree(&t) {
+            self.pos = start;
+            return self.error("inconsistent subtree sizes");
+        }
+        Ok(t)
+    }
+
+    // Parse a list of items in internal format, enclosed in square
+    // brackets and separated by commas; the list may be empty only
+    // if allow_empty is true
+
+    fn internal_list<T>(&mut self, allow_empty: bool,
+                        item: fn(&mut Self) -> Result<T, ParseError>)
+                        -> Result<Vec<T>, ParseError> {
+        self.expect(b'[')?;
+        let mut v = Vec::new();
+        if allow_empty && self.accept(b']') {
+            return Ok(v);
+        }
+        v.push(item(self)?);
+        while self.accept(b',') {
+            v.push(item(self)?);
+        }
+        self.expect(b']')?;
+        Ok(v)
+    }
+
+    fn internal_comp(&mut self) -> Result<Comp, ParseError> {
+        let c = self.internal_list(false, Self::internal_tree)?;
+        Ok(c.into_iter().map(Rc::new).collect())
+    }
+
+    fn internal_func(&mut self) -> Result<Func, ParseError> {
+        let g = self.internal_list(true, Self::internal_comp)?;
+        Ok(g.into_iter().map(Rc::new).collect())
+    }
+
+    // Parse a subtree in nested-bracket notation and append its
+    // code to t
+
+    fn pretty_subtree(&mut self, t: &mut Tree) -> Result<(), ParseError> {
+        self.peek();
+        let start = self.pos;
+        self.expect(b'[')?;
+        let i = t.len();
+        t.push(1);
+        if t.len() > 255 {
+            self.pos = start;
+            return self.error("tree with more than 255 vertices");
+        }
+        while self.peek() == Some(b'[') {
+            self.pretty_subtree(t)?;
+        }
+        self.expect(b']')?;
+        t[i] = (t.len() - i) as u8;
+        Ok(())
+    }
+
+    fn pretty_comp(&mut self) -> Result<Comp, ParseError> {
+        self.expect(b'(')?;
+        let mut c = Comp::new();
+        loop {
+            let mut t = Tree::new();
+            self.pretty_subtree(&mut t)?;
+            c.push(Rc::new(t));
+            if self.peek() != Some(b'[') {
+                break;
+            }
+        }
+        self.expect(b')')?;
+        Ok(c)
+    }
+
+    fn pretty_func(&mut self) -> Result<Func, ParseError> {
+        let mut g = Func::new();
+        while self.peek() == Some(b'(') {
+            g.push(Rc::new(self.pretty_comp()?));
+        }
+        Ok(g)
+    }
+
+    // Parse a subtree in Newick notation (without the final
+    // semicolon) and append its code to t
+
+    fn newick_subtree(&mut self, t: &mut Tree) -> Result<(), ParseError> {
+        let start = self.pos;
+        let i = t.len();
+        t.push(1);
+        if t.len() > 255 {
+            self.pos = start;
+            return self.error("tree with more than 255 vertices");
+        }
+        if self.accept(b'(') {
+            self.newick_subtree(t)?;
+            while self.accept(b',') {
+                self.newick_subtree(t)?;
+            }
+            self.expect(b')')?;
+        }
+        t[i] = (t.len() - i) as u8;
+        Ok(())
+    }
+
+    // Parse a component in Newick notation, i.e., a sequence of trees
+    // each followed by a semicolon and ending with whitespace (or at
+    // the end of the input)
+
+    fn newick_comp(&mut self) -> Result<Comp, ParseError> {
+        let mut c = Comp::new();
+        loop {
+            let mut t = Tree::new();
+            self.newick_subtree(&mut t)?;
+            self.expect(b';')?;
+            c.push(Rc::new(t));
+            if self.s.get(self.pos).is_none_or(|b| b.is_ascii_whitespace()) {
+                return Ok(c);
+            }
+        }
+    }
+
+    fn newick_func(&mut self) -> Result<Func, ParseError> {
+        let mut g = Func::new();
+        while self.peek().is_some() {
+            g.push(Rc::new(self.newick_comp()?));
+        }
+        Ok(g)
+    }
+
+    // Check if the next item is a tree in internal format, i.e., an
+    // opening square bracket followed by an integer
+
+    fn at_internal_tree(&mut self) -> bool {
+        let pos = self.pos;
+        let res = self.accept(b'[')
+            && self.peek().is_some_and(|c| c.is_ascii_digit());
+        self.pos = pos;
+        res
+    }
+
+    // Check that the whole input has been consumed
+
+    fn end(&mut self) -> Result<(), ParseError> {
+        if self.peek().is_some() {
+            return self.error("unexpected character");
+        }
+        Ok(())
+    }
+
+}
+
+
+// Check if the subtree sizes of tree code t are consistent, i.e., if
+// the subtrees of each vertex exactly fill its own subtree
+
+fn is_valid_tree(t: &Tree) -> bool {
+    fn valid_subtree(t: &Tree, i: usize) -> bool {
+        let end = i + t[i] as usize;
+        if end > t.len() {
+            return false;
+        }
+        let mut j = i + 1;
+        while j < end {
+            if !valid_subtree(t, j) {
+                return false;
+            }
+            j += t[j] as usize;
+        }
+        j == end
+    }
+    valid_subtree(t, 0) && t[0] as usize == t.len()
+}
+
+
+// Parse a functional digraph in internal format (as printed with
+// --format internal), nested-bracket notation (--format pretty),
+// Newick notation (--format newick), compact notation (--format
+// compact) or as a key (--format key); the format is recognized
+// automatically.
+// The code is only checked to be well formed, not to be canonical
+
+pub fn parse_func(s: &str) -> Result<Func, ParseError> {
+    let mut p = Parser { s: s.as_bytes(), pos: 0 };
+    let g = match p.peek() {
+        _ if s.contains(';') => p.newick_func()?,
+        Some(b'[') => p.internal_func()?,
+        Some(b'.') => return parse_compact(s.trim()),
+        Some(c) if KEY_ALPHABET.contains(&c) => return parse_key(s.trim()),
+        _ => p.pretty_func()?,
+    };
+    p.end()?;
+    let n: usize = g.iter().flat_map(|c| c.iter()).map(|t| t.len()).sum();
+    if n > 255 {
+        p.pos = 0;
+        return p.error("digraph with more than 255 vertices");
+    }
+    Ok(g)
+}
+
+
+// Parse a functional digraph in compact notation (as printed with
+// --format compact); the code is only checked to be well formed
+
+pub fn parse_compact(s: &str) -> Result<Func, ParseError> {
+    let error = |pos, msg: &str| Err(ParseError { pos, msg: msg.to_string() });
+    let digit = |pos: usize| {
+        s.as_bytes().get(pos)
+            .and_then(|c| COMPACT_DIGITS.iter().position(|d| d == c))
+            .map(|d| d as u8)
+    };
+    let b = s.as_bytes();
+    let mut g = Func::new();
+    let mut n = 0;
+    let mut i = 0;
+    while i < b.len() {
+        if b[i] != b'.' {
+            return error(i, "expected '.'");
+        }
+        i += 1;
+        let mut c = Comp::new();
+        while i < b.len() && b[i] != b'.' {
+            // Read a tree, whose size is given by its first digit
+            let start = i;
+            let mut t = Tree::new();
+            loop {
+                let size = if b[i] == b'_' {
+                    let size = digit(i + 1).zip(digit(i + 2))
+                        .map(|(h, l)| 62 * h as usize + l as usize);
+                    match size {
+                        Some(size) if (62..256).contains(&size) => {
+                            i += 3;
+                            size as u8
+                        }
+                        _ => return error(i, "invalid size"),
+                    }
+                } else {
+                    match digit(i) {
+                        Some(d) if d > 0 => {
+                            i += 1;
+                            d
+                        }
+                        _ => return error(i, "invalid size"),
+                    }
+                };
+                t.push(size);
+                if t.len() >= t[0] as usize {
+                    break;
+                }
+                if i >= b.len() || b[i] == b'.' {
+                    return error(i, "truncated tree");
+                }
+            }
+            if !is_valid_tree(&t) {
+                return error(start, "inconsistent subtree sizes");
+            }
+            n += t.len();
+            c.push(Rc::new(t));
+        }
+        if c.is_empty() {
+            return error(i, "empty component");
+        }
+        g.push(Rc::new(c));
+    }
+    if n > 255 {
+        return error(0, "digraph with more than 255 vertices");
+    }
+    Ok(g)
+}
+
+
+// Parse a component, in either format accepted by parse_func
+
+pub fn parse_comp(s: &str) -> Result<Comp, ParseError> {
+    let mut p = Parser { s: s.as_bytes(), pos: 0 };
+    let c = if p.peek() == Some(b'[') {
+        p.internal_comp()?
+    } else {
+        p.pretty_comp()?
+    };
+    p.end()?;
+    Ok(c)
+}
+
+
+// Parse a tree, in either format accepted by parse_func
+
+pub fn parse_tree(s: &str) -> Result<Tree, ParseError> {
+    let mut p = Parser { s: s.as_bytes(), pos: 0 };
+    let t = if p.at_internal_tree() {
+        p.internal_tree()?
+    } else {
+        let mut t = Tree::new();
+        p.pretty_subtree(&mut t)?;
+        t
+    };
+    p.end()?;
+    Ok(t)
+}
+
+
+// Parse the key of a functional digraph (as printed with --format
+// key), i.e., the base64 encoding of its packed code
+
+pub fn parse_key(s: &str) -> Result<Func, ParseError> {
+    let error = |pos, msg: &str| Err(ParseError { pos, msg: msg.to_string() });
+    if s.len() % 4 == 1 {
+        return error(s.len(), "truncated key");
+    }
+    let mut b = Vec::new();
+    let mut x = 0;
+    for (i, c) in s.bytes().enumerate() {
+        match KEY_ALPHABET.iter().position(|&d| d == c) {
+            Some(d) => x = x << 6 | d as u32,
+            None => return error(i, "invalid character in key"),
+        }
+        if i % 4 == 3 || i == s.len() - 1 {
+            let k = i % 4;
+            x <<= 6 * (3 - k);
+            for j in 0..k {
+                b.push((x >> (16 - 8 * j)) as u8);
+            }
+            x = 0;
+        }
+    }
+    // Positions in b are converted back into positions in s
+    let pos = |i: usize| i * 4 / 3;
+    let mut g = Func::new();
+    let mut i = 0;
+    let mut n = 0;
+    while i < b.len() {
+        let k = b[i] as usize;
+        if k == 0 {
+            return error(pos(i), "component without trees");
+        }
+        i += 1;
+        let mut c = Comp::new();
+        for _ in 0..k {
+            if i >= b.len() || b[i] == 0 || i + b[i] as usize > b.len() {
+                return error(pos(i), "truncated key");
+            }
+            let t = b[i..i + b[i] as usize].to_vec();
+            if !is_valid_tree(&t) {
+                return error(pos(i), "inconsistent subtree sizes");
+            }
+            n += t.len();
+            i += t.len();
+            c.push(Rc::new(t));
+        }
+        g.push(Rc::new(c));
+    }
+    if n > 255 {
+        return error(0, "digraph with more than 255 vertices");
+    }
+    Ok(g)
+}
+
+
+// Check whether s looks like a function table (as printed by the
+// orbit subcommand and by --labelled-samples), i.e., the images of
+// the vertices 0, 1, ..., n - 1 separated by whitespace: it begins
+// with a digit, unlike the codes, and unlike the keys it contains
+// whitespace (or it is 0, which has length 1 mod 4)
+
+pub fn is_function_table(s: &str) -> bool {
+    let s = s.trim();
+    s.starts_with(|c: char| c.is_ascii_digit())
+        && (s.contains(|c: char| c.is_ascii_whitespace()) || s == "0")
+}
+
+
+// Parse a function table, returning the images of the vertices; each
+// of them must be a decimal number smaller than the number of
+// vertices, which must be between 1 and 255
+
+pub fn parse_function_table(s: &str) -> Result<Vec<usize>, ParseError> {
+    // Without partial, all images are given
+    Ok(parse_table(s, false)?.into_iter().map(Option::unwrap).collect())
+}
+
+
+// Parse a partial function table, where the images of some vertices
+// are left unspecified by writing _ instead (e.g., 1 _ 0 _), returning
+// the image of each vertex, if given
+
+pub fn parse_partial_function_table(s: &str)
+                                    -> Result<Vec<Option<usize>>, ParseError> {
+    parse_table(s, true)
+}
+
+
+// Parse a function table, also allowing the unspecified images _ if
+// partial is true
+
+fn parse_table(s: &str, partial: bool)
+               -> Result<Vec<Option<usize>>, ParseError> {
+    let error = |pos, msg: String| Err(ParseError { pos, msg });
+    let mut f = Vec::new();
+    let mut starts = Vec::new();
+    let b = s.as_bytes();
+    let mut i = 0;
+    while i < b.len() {
+        if b[i].is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < b.len() && !b[i].is_ascii_whitespace() {
+            if !(b[i].is_ascii_digit() || partial && b[i] == b'_') {
+                return error(i, "invalid character in function table"
+                             .to_string());
+            }
+            i += 1;
+        }
+        match &s[start..i] {
+            "_" => f.push(None),
+            y => match y.parse::<usize>() {
+                Ok(y) if y < 256 => f.push(Some(y)),
+                _ => return error(start, format!("image {y} out of range")),
+            },
+        }
+        starts.push(start);
+    }
+    if f.is_empty() {
+        return error(0, "empty function table".to_string());
+    }
+    if f.len() > 255 {
+        return error(starts[255], "function with more than 255 vertices"
+                     .to_string());
+    }
+    for (x, &y) in f.iter().enumerate() {
+        if let Some(y) = y.filter(|&y| y >= f.len()) {
+            return error(starts[x], format!("image {y} of vertex {x} out \
+                                             of range (the function has \
+                                             {} vertices)", f.len()));
+        }
+    }
+    Ok(f)
+}
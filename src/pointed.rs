@@ -0,0 +1,175 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Automorphisms of functional digraphs, used for counting their
+// labellings, and orbits of vertices under automorphisms, used for
+// generating pointed functional digraphs (with a distinguished vertex)
+
+
+use crate::{Tree, Comp, Func, comp_size};
+use crate::digraph6::Adj;
+
+
+// Compute the smallest p such that rotating component c by p
+// positions gives back c itself; cycle positions i and i + p are then
+// equivalent under the automorphisms of c
+
+pub fn rotation_period(c: &Comp) -> usize {
+    let k = c.len();
+    for p in 1..k {
+        if k.is_multiple_of(p) && c[p..] == c[..k - p]
+            && c[..p] == c[k - p..] {
+            return p;
+        }
+    }
+    k
+}
+
+
+// Push to reps one representative for each orbit of the vertices of
+// the subtree of t having root at position i under the automorphisms
+// of t, which just swap isomorphic sibling subtrees; use b as the
+// name of the root of t, as in tree_adj, and choose the smallest name
+// in each orbit
+
+fn push_tree_reps(t: &Tree, i: usize, b: usize, reps: &mut Vec<usize>) {
+    reps.push(b + i);
+    let mut prev: &[u8] = &[];
+    let mut j = i + 1;
+    while j < i + t[i] as usize {
+        let s = &t[j..j + t[j] as usize];
+        if s != prev {
+            push_tree_reps(t, j, b, reps);
+        }
+        prev = s;
+        j += t[j] as usize;
+    }
+}
+
+
+// Compute one representative (the one with the smallest name, with
+// vertices named as in func_adj) for each orbit of the vertices of
+// functional digraph g under its automorphisms; these correspond to
+// the isomorphism classes of pointed digraphs (g, v). Isomorphic
+// components are adjacent in g, so only the first one of each run
+// needs to be considered
+
+pub fn pointed_reps(g: &Func) -> Vec<usize> {
+    let mut reps = Vec::new();
+    let mut b = 0;
+    for i in 0..g.len() {
+        if i == 0 || g[i] != g[i - 1] {
+            let c = &g[i];
+            let mut j = 0;
+            for k in 0..rotation_period(c) {
+                push_tree_reps(&c[k], 0, b + j, &mut reps);
+                j += c[k].len();
+            }
+        }
+        b += comp_size(&g[i]);
+    }
+    reps
+}
+
+
+// Compute the number of automorphisms of the subtree of t having
+// root at position i, i.e., the product of the numbers of
+// automorphisms of its immediate subtrees and of the factorials of
+// the multiplicities of isomorphic subtrees (which are adjacent)
+
+fn tree_automorphisms(t: &Tree, i: usize) -> Option<u128> {
+    let mut a: u128 = 1;
+    let mut prev: &[u8] = &[];
+    let mut run = 0;
+    let mut j = i + 1;
+    while j < i + t[i] as usize {
+        let s = &t[j..j + t[j] as usize];
+        run = if s == prev { run + 1 } else { 1 };
+        a = a.checked_mul(run)?.checked_mul(tree_automorphisms(t, j)?)?;
+        prev = s;
+        j += t[j] as usize;
+    }
+    Some(a)
+}
+
+
+// Check if tree t is rigid, i.e., its only automorphism is the
+// identity, which means that no vertex has two isomorphic immediate
+// subtrees
+
+pub fn is_rigid_tree(t: &Tree) -> bool {
+    tree_automorphisms(t, 0) == Some(1)
+}
+
+
+// Compute the number of automorphisms of functional digraph g, if it
+// fits into a u128: the automorphisms of a component c rotate it by
+// a multiple of rotation_period(c) and then act on each tree, and
+// those of g also permute its isomorphic components
+
+pub fn num_automorphisms(g: &Func) -> Option<u128> {
+    let mut a: u128 = 1;
+    let mut run = 0;
+    for i in 0..g.len() {
+        run = if i > 0 && g[i] == g[i - 1] { run + 1 } else { 1 };
+        let c = &g[i];
+        a = a.checked_mul(run)?
+            .checked_mul((c.len() / rotation_period(c)) as u128)?;
+        for t in c.iter() {
+            a = a.checked_mul(tree_automorphisms(t, 0)?)?;
+        }
+    }
+    Some(a)
+}
+
+
+// Check if functional digraph g is rigid (or asymmetric), i.e., its
+// only automorphism is the identity: its trees are rigid, its
+// components are not invariant under any nontrivial rotation and no
+// two of them are isomorphic
+
+pub fn is_rigid(g: &Func) -> bool {
+    num_automorphisms(g) == Some(1)
+}
+
+
+// Compute the number of labelled functions (on vertices 0, ..., n -
+// 1) having functional digraph g, i.e., n! divided by the number of
+// automorphisms of g, if n! fits into a u128
+
+pub fn num_labellings(g: &Func) -> Option<u128> {
+    let n: usize = g.iter().map(|c| comp_size(c)).sum();
+    let mut f: u128 = 1;
+    for k in 2..=n as u128 {
+        f = f.checked_mul(k)?;
+    }
+    Some(f / num_automorphisms(g)?)
+}
+
+
+// Rename vertex v of adjacency vector a as 0 and vice versa, so that
+// the distinguished vertex of a pointed digraph comes first
+
+pub fn swap_adj(a: &Adj, v: usize) -> Adj {
+    let swap = |x: usize| if x == 0 { v } else if x == v { 0 } else { x };
+    let mut s = vec![0; a.len()];
+    for x in 0..a.len() {
+        s[swap(x)] = swap(a[x] as usize) as u8;
+    }
+    s
+}
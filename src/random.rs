@@ -0,0 +1,89 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// A small pseudorandom number generator, so that random generation
+// is reproducible from a seed without depending on external crates
+
+
+// Increment of the state of the generator (an odd number, so that
+// the states of a sequence are all distinct)
+
+const GAMMA: u64 = 0x9e3779b97f4a7c15;
+
+
+// SplitMix64 generator (Guy L. Steele, Doug Lea, Christine H. Flood,
+// "Fast splittable pseudorandom number generators", OOPSLA 2014);
+// it is not cryptographically secure, but it is fast and its output
+// passes the usual statistical tests
+
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+
+impl Rng {
+
+    // Create a generator from a seed; the same seed always gives the
+    // same sequence of numbers
+
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    // Create the generator for stream number j of a seed, for
+    // drawing numbers independently in parallel; since the i-th
+    // number of a SplitMix64 sequence only depends on the counter
+    // seed + i * GAMMA, stream j just starts at counter j * 2^40, so
+    // that the streams do not overlap unless more than 2^40 numbers
+    // are drawn from one of them. Stream 0 is the same as Rng::new
+
+    pub fn stream(seed: u64, j: u64) -> Rng {
+        Rng { state: seed.wrapping_add((j << 40).wrapping_mul(GAMMA)) }
+    }
+
+    // Return the next 64-bit number of the sequence
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(GAMMA);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // Return a number uniformly distributed in [0, 1)
+
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // Return a number uniformly distributed in [0, n), for n > 0; the
+    // slightly biased numbers at the top of the range are rejected
+
+    pub fn below(&mut self, n: u64) -> u64 {
+        let zone = u64::MAX - u64::MAX % n;
+        loop {
+            let x = self.next_u64();
+            if x < zone {
+                return x % n;
+            }
+        }
+    }
+
+}
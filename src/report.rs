@@ -0,0 +1,164 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Self-contained HTML reports on the digraphs of a given size, for
+// sharing the results of a run without installing the program
+
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::time::Duration;
+use crate::Func;
+use crate::format::Pretty;
+use crate::invariants::{INVARIANTS, Invariant, Shown, Value};
+use crate::random::Rng;
+use crate::svg::write_svg;
+
+
+// Style sheet of the report, embedded in the page
+
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em auto; max-width: 60em; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { padding: 0.2em 0.8em; text-align: right; }
+th { border-bottom: 1px solid #888; }
+table.summary th { border: none; text-align: left; }
+td.bar { width: 20em; text-align: left; }
+td.bar div { background: #4a7ab5; height: 0.9em; }
+div.sample { display: flex; flex-wrap: wrap; gap: 1em; }
+figure { margin: 0; border: 1px solid #ccc; padding: 0.5em; }
+figure svg { max-width: 100%; height: auto; }
+figcaption { font-family: monospace; overflow-wrap: anywhere; }
+";
+
+
+// Summary of the digraphs of a given size, collected one digraph at a
+// time: their number, the distribution of each invariant and a
+// uniform sample (by reservoir sampling) of at most sample_size
+// digraphs
+
+pub struct Report {
+    pub size: usize,
+    pub connected: bool,
+    pub count: u64,
+    pub time: Duration,
+    pub histograms: Vec<(&'static dyn Invariant, BTreeMap<Value, u64>)>,
+    pub sample: Vec<Func>,
+    pub sample_size: usize,
+}
+
+
+impl Report {
+
+    // Create an empty report on the digraphs (or the connected ones)
+    // with size vertices
+
+    pub fn new(size: usize, connected: bool, sample_size: usize)
+               -> Report {
+        Report {
+            size,
+            connected,
+            count: 0,
+            time: Duration::ZERO,
+            histograms: INVARIANTS.iter()
+                .map(|&inv| (inv, BTreeMap::new())).collect(),
+            sample: Vec::with_capacity(sample_size),
+            sample_size,
+        }
+    }
+
+    // Add digraph g to the report, using rng for the sample
+
+    pub fn add(&mut self, g: &Func, rng: &mut Rng) {
+        self.count += 1;
+        for (inv, hist) in &mut self.histograms {
+            *hist.entry(inv.compute(g)).or_insert(0) += 1;
+        }
+        if self.sample.len() < self.sample_size {
+            self.sample.push(g.clone());
+        } else if self.sample_size > 0 {
+            let i = rng.below(self.count) as usize;
+            if i < self.sample_size {
+                self.sample[i] = g.clone();
+            }
+        }
+    }
+
+    // Write the report to out as an HTML page, with no references to
+    // external resources (the drawings of the sample are embedded as
+    // SVG)
+
+    pub fn write_html(&self, out: &mut dyn Write) -> io::Result<()> {
+        let what = if self.connected {
+            "Connected functional digraphs"
+        } else {
+            "Functional digraphs"
+        };
+        let title = format!("{what} with {} vertices", self.size);
+        writeln!(out, "<!DOCTYPE html>\n<html lang=\"en\">\n<head>")?;
+        writeln!(out, "<meta charset=\"utf-8\">")?;
+        writeln!(out, "<title>{title}</title>")?;
+        writeln!(out, "<style>\n{STYLE}</style>\n</head>\n<body>")?;
+        writeln!(out, "<h1>{title}</h1>")?;
+        writeln!(out, "<table class=\"summary\">")?;
+        writeln!(out, "<tr><th>Vertices</th><td>{}</td></tr>", self.size)?;
+        writeln!(out, "<tr><th>Digraphs</th><td>{}</td></tr>",
+                 self.count)?;
+        writeln!(out, "<tr><th>Generation time</th><td>{:.2?}</td></tr>",
+                 self.time)?;
+        writeln!(out, "<tr><th>Generated by</th><td>funkdigen2 {}</td></tr>",
+                 env!("CARGO_PKG_VERSION"))?;
+        writeln!(out, "</table>")?;
+        writeln!(out, "<h2>Invariants</h2>")?;
+        for (inv, hist) in &self.histograms {
+            self.write_histogram(out, *inv, hist)?;
+        }
+        writeln!(out, "<h2>Sample</h2>")?;
+        writeln!(out, "<p>{} of the digraphs, chosen uniformly at random; \
+                       the periodic vertices are filled.</p>",
+                 self.sample.len())?;
+        writeln!(out, "<div class=\"sample\">")?;
+        for g in &self.sample {
+            writeln!(out, "<figure>")?;
+            write_svg(out, g)?;
+            writeln!(out, "<figcaption>{}</figcaption>\n</figure>",
+                     Pretty(g))?;
+        }
+        writeln!(out, "</div>\n</body>\n</html>")
+    }
+
+    // Write the histogram hist of invariant inv as a table, with the
+    // bars scaled to the most frequent value
+
+    fn write_histogram(&self, out: &mut dyn Write, inv: &dyn Invariant,
+                       hist: &BTreeMap<Value, u64>) -> io::Result<()> {
+        let max = hist.values().copied().max().unwrap_or(1);
+        writeln!(out, "<h3>{inv}</h3>\n<table>")?;
+        writeln!(out, "<tr><th>value</th><th>digraphs</th><th>%</th>\
+                       <th></th></tr>")?;
+        for (&value, &count) in hist {
+            writeln!(out, "<tr><td>{}</td><td>{count}</td>\
+                           <td>{:.2}</td><td class=\"bar\">\
+                           <div style=\"width: {:.1}%\"></div></td></tr>",
+                     Shown(value), 100.0 * count as f64 / self.count as f64,
+                     100.0 * count as f64 / max as f64)?;
+        }
+        writeln!(out, "</table>")
+    }
+
+}
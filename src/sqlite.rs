@@ -0,0 +1,416 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Output as an SQLite database (https://www.sqlite.org/fileformat.html)
+
+
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::mem;
+use std::path::Path;
+use crate::Func;
+use crate::digraph6::{func_adj, write_digraph6_code};
+use crate::invariants::{FuncInvariants, Invariant, OVERFLOW};
+use crate::pointed::swap_adj;
+
+
+// Size in bytes of the pages of the database; the rows of digraphs
+// with up to 255 vertices always fit into a single page
+
+const PAGE_SIZE: usize = 16384;
+
+
+// Maximum payload of a row stored in a leaf page (larger ones would
+// require overflow pages)
+
+const MAX_PAYLOAD: usize = PAGE_SIZE - 35;
+
+
+// Name of the table of digraphs
+
+const TABLE: &str = "digraphs";
+
+
+// Types of B-tree pages
+
+const TABLE_INTERIOR: u8 = 5;
+const TABLE_LEAF: u8 = 13;
+
+
+// Append x to buf as an SQLite variable-length integer (big-endian,
+// 7 bits per byte except for the ninth byte, which has 8 bits)
+
+fn put_varint(buf: &mut Vec<u8>, x: u64) {
+    if x >> 56 != 0 {
+        for i in 0..8 {
+            buf.push((x >> (57 - 7 * i)) as u8 | 0x80);
+        }
+        buf.push(x as u8);
+        return;
+    }
+    let len = varint_len(x);
+    for i in (0..len).rev() {
+        let byte = (x >> (7 * i)) as u8 & 0x7f;
+        buf.push(if i > 0 { byte | 0x80 } else { byte });
+    }
+}
+
+
+// Number of bytes of x as a variable-length integer
+
+fn varint_len(x: u64) -> usize {
+    if x >> 56 != 0 {
+        9
+    } else {
+        (64 - x.leading_zeros() as usize).div_ceil(7).max(1)
+    }
+}
+
+
+// Values of the columns of a row; the integers which do not fit into
+// an i64 (such as large numbers of automorphisms) are stored exactly
+// as their decimal text
+
+enum Value<'a> {
+    Null,
+    Int(i64),
+    Large(u128),
+    Text(&'a [u8]),
+}
+
+
+// Encode values as an SQLite record: a header with the serial type
+// of each value (including its own size), followed by the values
+
+fn record(values: &[Value]) -> Vec<u8> {
+    let mut types = Vec::new();
+    let mut body = Vec::new();
+    for value in values {
+        match *value {
+            Value::Null => put_varint(&mut types, 0),
+            Value::Int(0) => put_varint(&mut types, 8),
+            Value::Int(1) => put_varint(&mut types, 9),
+            Value::Int(x) => {
+                let (ty, len) = if i8::try_from(x).is_ok() {
+                    (1, 1)
+                } else if i16::try_from(x).is_ok() {
+                    (2, 2)
+                } else if i32::try_from(x).is_ok() {
+                    (4, 4)
+                } else {
+                    (6, 8)
+                };
+                put_varint(&mut types, ty);
+                body.extend_from_slice(&x.to_be_bytes()[8 - len..]);
+            }
+            Value::Large(x) => {
+                let s = x.to_string();
+                put_varint(&mut types, 2 * s.len() as u64 + 13);
+                body.extend_from_slice(s.as_bytes());
+            }
+            Value::Text(s) => {
+                put_varint(&mut types, 2 * s.len() as u64 + 13);
+                body.extend_from_slice(s);
+            }
+        }
+    }
+    let mut size = types.len() + 1;
+    while size != types.len() + varint_len(size as u64) {
+        size += 1;
+    }
+    let mut rec = Vec::with_capacity(size + body.len());
+    put_varint(&mut rec, size as u64);
+    rec.extend(types);
+    rec.extend(body);
+    rec
+}
+
+
+// Lay out a B-tree page of the given type, with the given cells in
+// order (and the right-most child, for interior pages); the header
+// begins at offset start (100 for the first page, which also contains
+// the header of the database, 0 otherwise)
+
+fn page(ty: u8, cells: &[Vec<u8>], right: Option<u32>, start: usize)
+        -> Vec<u8> {
+    let mut page = vec![0; PAGE_SIZE];
+    let mut content = PAGE_SIZE;
+    let header_len = if right.is_some() { 12 } else { 8 };
+    let mut ptr = start + header_len;
+    for cell in cells {
+        content -= cell.len();
+        page[content..content + cell.len()].copy_from_slice(cell);
+        page[ptr..ptr + 2].copy_from_slice(&(content as u16).to_be_bytes());
+        ptr += 2;
+    }
+    page[start] = ty;
+    page[start + 3..start + 5]
+        .copy_from_slice(&(cells.len() as u16).to_be_bytes());
+    page[start + 5..start + 7]
+        .copy_from_slice(&(content as u16).to_be_bytes());
+    if let Some(right) = right {
+        page[start + 8..start + 12].copy_from_slice(&right.to_be_bytes());
+    }
+    page
+}
+
+
+// A level of interior pages being built: the children (page number
+// and largest key) of the current page, and the previous page, which
+// is written only when the next one is started, so that the last page
+// of the level can always take a child from it instead of having no
+// cells
+
+#[derive(Default)]
+struct Level {
+    children: Vec<(u32, u64)>,
+    size: usize,
+    full: Option<Vec<(u32, u64)>>,
+}
+
+
+// Size of the cell of an interior page pointing to child
+
+fn interior_cell_len(child: (u32, u64)) -> usize {
+    4 + varint_len(child.1)
+}
+
+
+// Writer of functional digraphs as the rows of a table digraphs in a
+// new SQLite database, with columns rank (the position of the row,
+// starting from 0), digraph6 (the digraph6 code of the digraph, with
+// its distinguished vertex renamed as vertex 0, as in the digraph6
+// output), code (its internal isomorphism code), vertex (only if
+// pointed is true), size and one for each of the given invariants
+// (with its name in snake case, e.g., periodic_points). The table
+// B-tree is built bottom-up, since the rows are inserted in order of
+// rank: each leaf page is written as soon as it is full, and only the
+// current page of each level of the tree is kept in memory. The
+// first page, which contains the schema, is written by finish
+
+pub struct SqliteWriter {
+    out: BufWriter<File>,
+    pointed: bool,
+    invariants: Vec<&'static dyn Invariant>,
+    pages: u32,
+    rows: u64,
+    leaf: Vec<Vec<u8>>,
+    leaf_size: usize,
+    levels: Vec<Level>,
+}
+
+
+impl SqliteWriter {
+
+    // Create the database at path (an existing file is overwritten)
+
+    pub fn create(path: &Path, pointed: bool,
+                  invariants: &[&'static dyn Invariant])
+                  -> io::Result<SqliteWriter> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(&[0; PAGE_SIZE])?;
+        Ok(SqliteWriter {
+            out,
+            pointed,
+            invariants: invariants.to_vec(),
+            pages: 1,
+            rows: 0,
+            leaf: Vec::new(),
+            leaf_size: 8,
+            levels: Vec::new(),
+        })
+    }
+
+    // SQL statement creating the table; the columns of the unbounded
+    // invariants have no declared type, since they can also contain
+    // text, for the values which do not fit into an i64, and NULL, for
+    // those which overflow (see invariants::OVERFLOW)
+
+    fn schema(&self) -> String {
+        let mut sql = format!("CREATE TABLE {TABLE}(\
+                               rank INTEGER PRIMARY KEY, \
+                               digraph6 TEXT NOT NULL, \
+                               code TEXT NOT NULL");
+        if self.pointed {
+            sql += ", vertex INTEGER NOT NULL";
+        }
+        sql += ", size INTEGER NOT NULL";
+        for inv in &self.invariants {
+            let ty = if inv.is_bounded() { " INTEGER NOT NULL" } else { "" };
+            sql += &format!(", {}{ty}", inv.name().replace('-', "_"));
+        }
+        sql + ")"
+    }
+
+    // Append a page to the file, returning its number
+
+    fn write_page(&mut self, page: &[u8]) -> io::Result<u32> {
+        self.out.write_all(page)?;
+        self.pages += 1;
+        Ok(self.pages)
+    }
+
+    // Write the page of interior cells pointing to children
+
+    fn write_interior(&mut self, children: &[(u32, u64)])
+                      -> io::Result<(u32, u64)> {
+        let (&right, children) = children.split_last().unwrap();
+        let cells: Vec<Vec<u8>> = children.iter().map(|&(child, key)| {
+            let mut cell = child.to_be_bytes().to_vec();
+            put_varint(&mut cell, key);
+            cell
+        }).collect();
+        let n = self.write_page(&page(TABLE_INTERIOR, &cells,
+                                      Some(right.0), 0))?;
+        Ok((n, right.1))
+    }
+
+    // Add child to level i of the interior pages, starting a new
+    // page (and writing the previous full one) if it does not fit
+
+    fn add_child(&mut self, i: usize, child: (u32, u64)) -> io::Result<()> {
+        if i == self.levels.len() {
+            self.levels.push(Level { size: 12, ..Level::default() });
+        }
+        let level = &mut self.levels[i];
+        let mut len = level.children.last()
+            .map_or(0, |&c| 2 + interior_cell_len(c));
+        if level.size + len > PAGE_SIZE {
+            let children = mem::take(&mut level.children);
+            level.size = 12;
+            len = 0;
+            if let Some(full) = level.full.replace(children) {
+                let parent = self.write_interior(&full)?;
+                self.add_child(i + 1, parent)?;
+            }
+        }
+        let level = &mut self.levels[i];
+        level.size += len;
+        level.children.push(child);
+        Ok(())
+    }
+
+    // Write the current leaf page and add it to the interior pages
+
+    fn write_leaf(&mut self) -> io::Result<()> {
+        let leaf = mem::take(&mut self.leaf);
+        self.leaf_size = 8;
+        let n = self.write_page(&page(TABLE_LEAF, &leaf, None, 0))?;
+        self.add_child(0, (n, self.rows.saturating_sub(1)))
+    }
+
+    // Insert functional digraph g, with distinguished vertex v if the
+    // writer is pointed, as the next row
+
+    pub fn insert(&mut self, g: &Func, v: Option<usize>) -> io::Result<()> {
+        let mut a = func_adj(g);
+        if let Some(v) = v {
+            a = swap_adj(&a, v);
+        }
+        let mut digraph6 = Vec::new();
+        write_digraph6_code(&mut digraph6, &a, false)?;
+        let code = format!("{g:?}");
+        let mut values = vec![Value::Null, Value::Text(&digraph6),
+                              Value::Text(code.as_bytes())];
+        if self.pointed {
+            values.push(Value::Int(v.unwrap_or(0) as i64));
+        }
+        values.push(Value::Int(g.size() as i64));
+        for inv in &self.invariants {
+            values.push(match inv.compute(g) {
+                OVERFLOW => Value::Null,
+                x => i64::try_from(x).map_or(Value::Large(x), Value::Int),
+            });
+        }
+        let payload = record(&values);
+        if payload.len() > MAX_PAYLOAD {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "row too large for SQLite output"));
+        }
+        let mut cell = Vec::with_capacity(payload.len() + 10);
+        put_varint(&mut cell, payload.len() as u64);
+        put_varint(&mut cell, self.rows);
+        cell.extend(payload);
+        if self.leaf_size + 2 + cell.len() > PAGE_SIZE {
+            self.write_leaf()?;
+        }
+        self.leaf_size += 2 + cell.len();
+        self.leaf.push(cell);
+        self.rows += 1;
+        Ok(())
+    }
+
+    // Write the remaining pages, then the first page with the header
+    // of the database and the schema, pointing to the root of the
+    // table B-tree
+
+    pub fn finish(mut self) -> io::Result<()> {
+        if !self.leaf.is_empty() || self.levels.is_empty() {
+            self.write_leaf()?;
+        }
+        let mut i = 0;
+        let root = loop {
+            let level = &mut self.levels[i];
+            let mut children = mem::take(&mut level.children);
+            let full = level.full.take();
+            if i + 1 == self.levels.len() && full.is_none()
+                && children.len() == 1 {
+                break children[0].0;
+            }
+            if let Some(mut full) = full {
+                if children.len() == 1 {
+                    children.insert(0, full.pop().unwrap());
+                }
+                let parent = self.write_interior(&full)?;
+                self.add_child(i + 1, parent)?;
+            }
+            let parent = self.write_interior(&children)?;
+            self.add_child(i + 1, parent)?;
+            i += 1;
+        };
+        let schema = self.schema();
+        let mut cell = Vec::new();
+        let payload = record(&[Value::Text(b"table"),
+                               Value::Text(TABLE.as_bytes()),
+                               Value::Text(TABLE.as_bytes()),
+                               Value::Int(root as i64),
+                               Value::Text(schema.as_bytes())]);
+        put_varint(&mut cell, payload.len() as u64);
+        put_varint(&mut cell, 1);
+        cell.extend(payload);
+        let mut first = page(TABLE_LEAF, &[cell], None, 100);
+        first[..16].copy_from_slice(b"SQLite format 3\0");
+        first[16..18].copy_from_slice(&(PAGE_SIZE as u16).to_be_bytes());
+        first[18] = 1;
+        first[19] = 1;
+        first[21] = 64;
+        first[22] = 32;
+        first[23] = 32;
+        first[24..28].copy_from_slice(&1u32.to_be_bytes());
+        first[28..32].copy_from_slice(&self.pages.to_be_bytes());
+        first[40..44].copy_from_slice(&1u32.to_be_bytes());
+        first[44..48].copy_from_slice(&4u32.to_be_bytes());
+        first[56..60].copy_from_slice(&1u32.to_be_bytes());
+        first[92..96].copy_from_slice(&1u32.to_be_bytes());
+        first[96..100].copy_from_slice(&3_008_000u32.to_be_bytes());
+        self.out.seek(SeekFrom::Start(0))?;
+        self.out.write_all(&first)?;
+        self.out.flush()
+    }
+
+}
@@ -0,0 +1,126 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// A pausable generator of the components of n vertices: each call to
+// step computes the next component, so the caller decides when to
+// stop (e.g., for running the generation in an event loop, or under a
+// time limit), and its position can be saved as a short token and
+// restored later, possibly in another process, to continue from the
+// same point; the components are produced in the same order as by
+// generate_comps
+
+
+use std::rc::Rc;
+use crate::{Comp, CompWalk, Tree, comp_size};
+use crate::canon::canon_map;
+use crate::digraph6::func_adj;
+use crate::error::{Error, MAX_VERTICES};
+use crate::parse::{ParseError, parse_comp};
+
+
+// State of the generation: the walk of the reverse search tree,
+// positioned at the last component produced (or at the first one,
+// before the first step)
+
+pub struct GenState {
+    n: usize,
+    walk: CompWalk<Rc<Tree>>,
+    started: bool,
+}
+
+
+impl GenState {
+
+    // Start the generation of the components of n vertices; sizes
+    // larger than MAX_VERTICES give an Error::TooManyVertices
+
+    pub fn new(n: usize) -> Result<GenState, Error> {
+        if n > MAX_VERTICES {
+            return Err(Error::TooManyVertices(n));
+        }
+        Ok(GenState { n, walk: CompWalk::new(n), started: false })
+    }
+
+    // Number of vertices of the components
+
+    pub fn size(&self) -> usize {
+        self.n
+    }
+
+    // Compute the next component, or return None once all of them
+    // have been produced
+
+    pub fn step(&mut self) -> Option<&Comp> {
+        if self.n == 0 {
+            return None;
+        }
+        if !self.started {
+            self.started = true;
+        } else if !self.walk.advance() {
+            return None;
+        }
+        Some(self.walk.comp())
+    }
+
+    // Save the state as a token, consisting of the number of vertices
+    // followed by a colon and the code (in internal format) of the
+    // last component produced, if any
+
+    pub fn save(&self) -> String {
+        if self.started {
+            format!("{}:{:?}", self.n, self.walk.comp())
+        } else {
+            self.n.to_string()
+        }
+    }
+
+    // Restore a state saved as a token by save; the code in the token
+    // must be the canonical code of a component of the given size,
+    // otherwise an Error::Parse is returned
+
+    pub fn restore(token: &str) -> Result<GenState, Error> {
+        let token = token.trim();
+        let (size, code) = match token.split_once(':') {
+            Some((size, code)) => (size, Some(code)),
+            None => (token, None),
+        };
+        let error = |pos, msg: &str| {
+            Error::Parse(ParseError { pos, msg: msg.to_string() })
+        };
+        let n: usize = size.parse().map_err(|_| error(0, "invalid size"))?;
+        let mut state = GenState::new(n)?;
+        let Some(code) = code else { return Ok(state) };
+        let pos = size.len() + 1;
+        let c = parse_comp(code).map_err(|e| ParseError {
+            pos: pos + e.pos,
+            msg: e.msg,
+        })?;
+        if comp_size(&c) != n {
+            return Err(error(pos, "component of the wrong size"));
+        }
+        let a: Vec<usize> = func_adj(&vec![Rc::new(c.clone())]).iter()
+            .map(|&v| v as usize).collect();
+        if *canon_map(&a)?[0] != c {
+            return Err(error(pos, "non-canonical code"));
+        }
+        state.walk = CompWalk::from_comp(c);
+        state.started = true;
+        Ok(state)
+    }
+
+}
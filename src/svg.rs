@@ -0,0 +1,184 @@
+// funkdigen2
+// Copyright (C) 2023 Antonio E. Porreca, Ekaterina Timofeeva
+
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU
+// General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see
+// <https://www.gnu.org/licenses/>.
+
+
+// Drawings of functional digraphs in SVG (https://www.w3.org/TR/SVG2)
+
+
+use std::f64::consts::PI;
+use std::io::{self, Write};
+use crate::{Comp, Func};
+use crate::invariants::TreeInvariants;
+
+
+// Distance between the levels of the trees, and radius of the
+// vertices, in pixels
+
+const STEP: f64 = 28.0;
+const RADIUS: f64 = 5.0;
+
+
+// Minimum distance between the leaves of a component, in pixels,
+// used to choose the radius of its cycle
+
+const LEAF_SPACING: f64 = 16.0;
+
+
+// Margin around each component, in pixels
+
+const MARGIN: f64 = 12.0;
+
+
+// Position of each vertex of a component (in the order of comp_adj,
+// i.e., tree after tree along the cycle, each tree in preorder)
+// relative to the centre of the cycle, together with the index of its
+// parent in its tree (None for the roots) and the radius of the disc
+// containing the drawing. The cycle is a circle and each tree is
+// drawn radially outwards from its root, in an angular sector
+// proportional to its number of leaves; the subtrees of a vertex
+// divide its sector in the same way
+
+fn layout(c: &Comp) -> (Vec<(f64, f64)>, Vec<Option<usize>>, f64) {
+    let k = c.len();
+    let leaves = |t: &[u8]| t.iter().filter(|&&s| s == 1).count();
+    let total: usize = c.iter().map(|t| leaves(t)).sum();
+    let height = c.iter().map(|t| t.height()).max().unwrap_or(0);
+    let base = total as f64 * LEAF_SPACING / (2.0 * PI);
+    let r0 = if k == 1 { (base - STEP).max(0.0) } else { base.max(STEP) };
+    let mut pos = Vec::new();
+    let mut parent = Vec::new();
+    let mut angle = -PI / 2.0;
+    for t in c.iter() {
+        let b = pos.len();
+        let span = 2.0 * PI * leaves(t) as f64 / total as f64;
+        let depth = t.vertex_depths();
+        // Angular sector of each vertex, assigned from its parent's
+        let mut sector = vec![(angle, angle + span); t.len()];
+        let mut stack: Vec<usize> = Vec::new();
+        for j in 0..t.len() {
+            while stack.last().is_some_and(|&p| j >= p + t[p] as usize) {
+                stack.pop();
+            }
+            parent.push(stack.last().map(|&p| b + p));
+            let (lo, hi) = sector[j];
+            let mut a = lo;
+            let mut i = j + 1;
+            while i < j + t[j] as usize {
+                let w = (hi - lo) * leaves(&t[i..i + t[i] as usize]) as f64
+                    / leaves(&t[j..j + t[j] as usize]) as f64;
+                sector[i] = (a, a + w);
+                a += w;
+                i += t[i] as usize;
+            }
+            let r = if k == 1 && j == 0 {
+                0.0
+            } else {
+                r0 + depth[j] as f64 * STEP
+            };
+            let mid = (lo + hi) / 2.0;
+            pos.push((r * mid.cos(), r * mid.sin()));
+            stack.push(j);
+        }
+        angle += span;
+    }
+    (pos, parent, r0 + height as f64 * STEP + RADIUS + MARGIN)
+}
+
+
+// Write an edge from (x1, y1) to (x2, y2), as a straight line or, if
+// bend is not zero, as a quadratic curve bent by that many pixels to
+// the left; the edge stops at the border of the target vertex, so
+// that its arrowhead is visible
+
+fn write_edge(out: &mut dyn Write, (x1, y1): (f64, f64),
+              (x2, y2): (f64, f64), bend: f64) -> io::Result<()> {
+    let (dx, dy) = (x2 - x1, y2 - y1);
+    let len = dx.hypot(dy);
+    let (cx, cy) = ((x1 + x2) / 2.0 + bend * dy / len,
+                    (y1 + y2) / 2.0 - bend * dx / len);
+    let (ex, ey) = (x2 - cx, y2 - cy);
+    let shorten = (RADIUS + 1.0) / ex.hypot(ey);
+    let (x2, y2) = (x2 - ex * shorten, y2 - ey * shorten);
+    writeln!(out, "<path d=\"M {x1:.1} {y1:.1} Q {cx:.1} {cy:.1} \
+                   {x2:.1} {y2:.1}\"/>")
+}
+
+
+// Write to out an SVG drawing of functional digraph g, with its
+// components side by side; periodic vertices are filled, and the
+// edges go from each vertex to its image. The drawing does not depend
+// on any style sheet, so that it can be embedded in an HTML page as
+// well as used on its own
+
+pub fn write_svg(out: &mut dyn Write, g: &Func) -> io::Result<()> {
+    let layouts: Vec<_> = g.iter().map(|c| layout(c)).collect();
+    let width = layouts.iter().map(|(_, _, r)| 2.0 * r).sum::<f64>()
+        .max(2.0 * MARGIN);
+    let height = layouts.iter().map(|(_, _, r)| 2.0 * r)
+        .fold(2.0 * MARGIN, f64::max);
+    writeln!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\" \
+                   width=\"{width:.0}\" height=\"{height:.0}\" \
+                   viewBox=\"0 0 {width:.0} {height:.0}\">")?;
+    writeln!(out, "<defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" \
+                   refX=\"10\" refY=\"5\" markerWidth=\"6\" \
+                   markerHeight=\"6\" orient=\"auto-start-reverse\">\
+                   <path d=\"M 0 0 L 10 5 L 0 10 z\"/></marker></defs>")?;
+    writeln!(out, "<g fill=\"none\" stroke=\"black\" \
+                   marker-end=\"url(#arrow)\">")?;
+    let mut x0 = 0.0;
+    let mut vertices = Vec::new();
+    for (c, (pos, parent, r)) in g.iter().zip(&layouts) {
+        let centre = |(x, y): (f64, f64)| (x0 + r + x, height / 2.0 + y);
+        for (v, p) in parent.iter().enumerate() {
+            if let Some(p) = *p {
+                write_edge(out, centre(pos[v]), centre(pos[p]), 0.0)?;
+            }
+        }
+        // The roots of the trees form the cycle; the edges of a cycle
+        // of length 2 are bent apart, and those of longer cycles are
+        // bent outwards, while a loop is drawn as a small circle
+        let roots: Vec<_> = c.iter().scan(0, |b, t| {
+            *b += t.len();
+            Some(*b - t.len())
+        }).collect();
+        if roots.len() == 1 {
+            let (x, y) = centre(pos[0]);
+            writeln!(out, "<circle cx=\"{x:.1}\" cy=\"{:.1}\" r=\"{:.1}\" \
+                           marker-end=\"none\"/>",
+                     y - 2.0 * RADIUS, 2.0 * RADIUS)?;
+        } else {
+            for (i, &u) in roots.iter().enumerate() {
+                let v = roots[(i + 1) % roots.len()];
+                write_edge(out, centre(pos[u]), centre(pos[v]),
+                           STEP / 4.0)?;
+            }
+        }
+        for (v, &(x, y)) in pos.iter().enumerate() {
+            let (x, y) = centre((x, y));
+            let fill = if parent[v].is_none() { "black" } else { "white" };
+            vertices.push(format!("<circle cx=\"{x:.1}\" cy=\"{y:.1}\" \
+                                   r=\"{RADIUS}\" fill=\"{fill}\"/>"));
+        }
+        x0 += 2.0 * r;
+    }
+    writeln!(out, "</g>")?;
+    writeln!(out, "<g stroke=\"black\">")?;
+    for v in vertices {
+        writeln!(out, "{v}")?;
+    }
+    writeln!(out, "</g>\n</svg>")
+}